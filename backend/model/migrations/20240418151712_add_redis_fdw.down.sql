@@ -1,5 +1,3 @@
 -- Add down migration script here
 
-DROP USER MAPPING FOR PUBLIC SERVER redis_server;
-DROP SERVER redis_server;
-DROP EXTENSION redis_fdw;
\ No newline at end of file
+DROP EXTENSION IF EXISTS redis_fdw CASCADE;
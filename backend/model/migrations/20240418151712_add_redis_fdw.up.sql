@@ -1,4 +1,12 @@
 -- Add up migration script here
-CREATE EXTENSION redis_fdw;
-CREATE SERVER redis_server FOREIGN DATA WRAPPER redis_fdw OPTIONS (address 'tstats-redis', port '6379');
-CREATE USER MAPPING FOR PUBLIC SERVER redis_server;
\ No newline at end of file
+-- Maps used to be read from Redis through redis_fdw. Stock Postgres doesn't ship the extension,
+-- and maps are stored in the beatmap table by now, so it is only set up where it is available.
+DO $$
+BEGIN
+    IF EXISTS (SELECT FROM pg_available_extensions WHERE name = 'redis_fdw') THEN
+        CREATE EXTENSION redis_fdw;
+        CREATE SERVER redis_server FOREIGN DATA WRAPPER redis_fdw OPTIONS (address 'tstats-redis', port '6379');
+        CREATE USER MAPPING FOR PUBLIC SERVER redis_server;
+    END IF;
+END
+$$;
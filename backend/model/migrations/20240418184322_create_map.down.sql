@@ -1,2 +1,2 @@
-DROP VIEW map;
-DROP FOREIGN TABLE raw_map;
\ No newline at end of file
+DROP VIEW IF EXISTS map;
+DROP FOREIGN TABLE IF EXISTS raw_map;
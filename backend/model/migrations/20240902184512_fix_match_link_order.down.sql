@@ -0,0 +1 @@
+ALTER TABLE match_link ADD CONSTRAINT match_link_link_order_key UNIQUE (link_order);
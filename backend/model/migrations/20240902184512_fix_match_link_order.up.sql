@@ -0,0 +1,2 @@
+-- The link order only has to be unique for each match, not across all matches
+ALTER TABLE match_link DROP CONSTRAINT match_link_link_order_key;
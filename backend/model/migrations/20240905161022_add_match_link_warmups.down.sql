@@ -0,0 +1 @@
+ALTER TABLE match_link DROP COLUMN warmups;
@@ -0,0 +1,2 @@
+-- The number of games at the start of a lobby that are warmups and should not be imported
+ALTER TABLE match_link ADD COLUMN warmups SMALLINT NOT NULL DEFAULT 0 CHECK (warmups >= 0);
@@ -0,0 +1,2 @@
+DROP TABLE pick_ban;
+DROP TYPE pick_ban_kind;
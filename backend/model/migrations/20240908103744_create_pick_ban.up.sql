@@ -0,0 +1,15 @@
+CREATE TYPE pick_ban_kind AS ENUM('protect', 'ban', 'pick', 'tiebreaker');
+CREATE TABLE pick_ban (
+    match_id INT NOT NULL REFERENCES versus_match(match_id),
+    action_order SMALLINT NOT NULL CHECK (action_order >= 0),
+    kind pick_ban_kind NOT NULL,
+    -- The team that protected, banned or picked the map. Tiebreakers are not chosen by a team
+    team_id INT REFERENCES team(id),
+    tournament_id INT NOT NULL,
+    stage_order SMALLINT NOT NULL,
+    bracket_order SMALLINT NOT NULL,
+    map_order SMALLINT NOT NULL,
+    PRIMARY KEY (match_id, action_order),
+    FOREIGN KEY (tournament_id, stage_order, bracket_order, map_order) REFERENCES pool_map,
+    CHECK ((kind = 'tiebreaker') = (team_id IS NULL))
+);
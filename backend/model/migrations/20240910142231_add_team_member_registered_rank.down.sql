@@ -0,0 +1 @@
+ALTER TABLE team_member DROP COLUMN registered_rank;
@@ -0,0 +1,3 @@
+-- The global rank of a player at the time they were registered.
+-- This is NULL if the player was unranked or registered before ranks were recorded.
+ALTER TABLE team_member ADD COLUMN registered_rank INT CHECK (registered_rank > 0);
@@ -0,0 +1 @@
+ALTER TABLE tournament DROP COLUMN bws_base, DROP COLUMN bws_exponent;
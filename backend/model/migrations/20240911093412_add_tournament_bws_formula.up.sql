@@ -0,0 +1,4 @@
+-- Tournaments with BWS use rank^(bws_base^(badges^bws_exponent)) as a player's rank
+ALTER TABLE tournament
+    ADD COLUMN bws_base DOUBLE PRECISION NOT NULL DEFAULT 0.9937 CHECK (bws_base > 0 AND bws_base <= 1),
+    ADD COLUMN bws_exponent DOUBLE PRECISION NOT NULL DEFAULT 2 CHECK (bws_exponent >= 0);
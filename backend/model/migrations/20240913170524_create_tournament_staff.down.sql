@@ -0,0 +1,2 @@
+DROP TABLE tournament_staff;
+DROP TYPE tournament_role;
@@ -0,0 +1,8 @@
+CREATE TYPE tournament_role AS ENUM('host', 'admin', 'pooler', 'referee', 'streamer', 'viewer');
+CREATE TABLE tournament_staff (
+    tournament_id INT NOT NULL REFERENCES tournament(id) ON DELETE CASCADE,
+    user_id INT NOT NULL CHECK (user_id >= 0),
+    "role" tournament_role NOT NULL,
+    PRIMARY KEY (tournament_id, user_id, "role")
+);
+CREATE INDEX ix_tournament_staff_user_id ON tournament_staff (user_id);
@@ -0,0 +1,15 @@
+ALTER TABLE pick_ban
+    DROP CONSTRAINT pick_ban_tournament_id_stage_order_bracket_order_map_order_fkey,
+    ADD FOREIGN KEY (tournament_id, stage_order, bracket_order, map_order) REFERENCES pool_map;
+ALTER TABLE score
+    DROP CONSTRAINT score_tournament_id_stage_order_bracket_order_map_order_fkey,
+    ADD FOREIGN KEY (tournament_id, stage_order, bracket_order, map_order) REFERENCES pool_map;
+ALTER TABLE pool_map
+    DROP CONSTRAINT pool_map_tournament_id_stage_order_bracket_order_fkey,
+    ADD FOREIGN KEY (tournament_id, stage_order, bracket_order) REFERENCES pool_bracket;
+ALTER TABLE match
+    DROP CONSTRAINT match_tournament_id_stage_order_fkey,
+    ADD FOREIGN KEY (tournament_id, stage_order) REFERENCES stage;
+ALTER TABLE pool_bracket
+    DROP CONSTRAINT pool_bracket_tournament_id_stage_order_fkey,
+    ADD FOREIGN KEY (tournament_id, stage_order) REFERENCES stage;
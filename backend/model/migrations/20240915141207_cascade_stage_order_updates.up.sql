@@ -0,0 +1,16 @@
+-- Changing the order of a stage, bracket or map moves everything that belongs to it
+ALTER TABLE pool_bracket
+    DROP CONSTRAINT pool_bracket_tournament_id_stage_order_fkey,
+    ADD FOREIGN KEY (tournament_id, stage_order) REFERENCES stage ON UPDATE CASCADE;
+ALTER TABLE match
+    DROP CONSTRAINT match_tournament_id_stage_order_fkey,
+    ADD FOREIGN KEY (tournament_id, stage_order) REFERENCES stage ON UPDATE CASCADE;
+ALTER TABLE pool_map
+    DROP CONSTRAINT pool_map_tournament_id_stage_order_bracket_order_fkey,
+    ADD FOREIGN KEY (tournament_id, stage_order, bracket_order) REFERENCES pool_bracket ON UPDATE CASCADE;
+ALTER TABLE score
+    DROP CONSTRAINT score_tournament_id_stage_order_bracket_order_map_order_fkey,
+    ADD FOREIGN KEY (tournament_id, stage_order, bracket_order, map_order) REFERENCES pool_map ON UPDATE CASCADE;
+ALTER TABLE pick_ban
+    DROP CONSTRAINT pick_ban_tournament_id_stage_order_bracket_order_map_order_fkey,
+    ADD FOREIGN KEY (tournament_id, stage_order, bracket_order, map_order) REFERENCES pool_map ON UPDATE CASCADE;
@@ -0,0 +1,2 @@
+ALTER TABLE tournament
+    DROP COLUMN deleted_at;
@@ -0,0 +1,3 @@
+-- Deleted tournaments are kept until they are purged, so that they can be restored
+ALTER TABLE tournament
+    ADD COLUMN deleted_at TIMESTAMP DEFAULT NULL;
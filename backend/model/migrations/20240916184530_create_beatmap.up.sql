@@ -0,0 +1,23 @@
+-- Beatmaps are stored when they are first requested from the osu API. Unlike the cache in Redis,
+-- they don't expire, so pools can always be joined with the data of their maps.
+DROP VIEW IF EXISTS map;
+DROP EXTENSION IF EXISTS redis_fdw CASCADE;
+CREATE TABLE beatmap (
+    map_id BIGINT PRIMARY KEY NOT NULL,
+    mapset_id BIGINT NOT NULL,
+    artist_name TEXT NOT NULL,
+    name TEXT NOT NULL,
+    difficulty_name TEXT NOT NULL,
+    creator_id BIGINT NOT NULL,
+    creator_name TEXT NOT NULL,
+    creator_country VARCHAR(2) NOT NULL,
+    creator_cover_url TEXT NOT NULL,
+    stars REAL NOT NULL,
+    length INT NOT NULL,
+    bpm REAL NOT NULL,
+    cs REAL NOT NULL,
+    ar REAL NOT NULL,
+    od REAL NOT NULL,
+    hp REAL NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT now()
+);
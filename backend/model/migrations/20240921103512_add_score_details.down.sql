@@ -0,0 +1,15 @@
+ALTER TABLE score
+    DROP COLUMN accuracy,
+    DROP COLUMN max_combo,
+    DROP COLUMN count_300,
+    DROP COLUMN count_100,
+    DROP COLUMN count_50,
+    DROP COLUMN count_miss,
+    DROP COLUMN mods,
+    DROP COLUMN pass,
+    DROP COLUMN perfect,
+    DROP COLUMN scoring_type,
+    DROP COLUMN team,
+    DROP COLUMN slot;
+DROP TYPE lobby_team;
+DROP TYPE scoring_type;
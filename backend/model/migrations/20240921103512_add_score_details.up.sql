@@ -0,0 +1,30 @@
+CREATE TYPE scoring_type AS ENUM('score', 'accuracy', 'combo', 'score_v2');
+CREATE TYPE lobby_team AS ENUM('none', 'blue', 'red');
+-- The details of a score are NULL if it was imported before they were recorded.
+-- Either all of them are NULL or none of them are.
+ALTER TABLE score
+    -- Between 0 and 100
+    ADD COLUMN accuracy REAL CHECK (accuracy BETWEEN 0 AND 100),
+    ADD COLUMN max_combo INT CHECK (max_combo >= 0),
+    ADD COLUMN count_300 INT CHECK (count_300 >= 0),
+    ADD COLUMN count_100 INT CHECK (count_100 >= 0),
+    ADD COLUMN count_50 INT CHECK (count_50 >= 0),
+    ADD COLUMN count_miss INT CHECK (count_miss >= 0),
+    -- The legacy bitflags of the mods the player played with, including those enabled for the whole lobby
+    ADD COLUMN mods INT CHECK (mods >= 0),
+    -- Whether the player passed the map
+    ADD COLUMN pass BOOLEAN,
+    -- Whether the player reached the maximum combo of the map
+    ADD COLUMN perfect BOOLEAN,
+    -- How the lobby decided who won the map
+    ADD COLUMN scoring_type scoring_type,
+    -- The team the player was on in the lobby. This is 'none' if the lobby had no teams.
+    ADD COLUMN team lobby_team,
+    -- The lobby slot of the player, starting at 0
+    ADD COLUMN slot SMALLINT CHECK (slot >= 0),
+    ADD CHECK (
+        num_nulls(
+            accuracy, max_combo, count_300, count_100, count_50, count_miss, mods, pass, perfect,
+            scoring_type, team, slot
+        ) IN (0, 12)
+    );
@@ -0,0 +1,2 @@
+DROP TABLE player_rating_change;
+DROP TABLE player_rating;
@@ -0,0 +1,17 @@
+-- The current rating of every player who played in a rated versus match.
+-- Ratings are derived from the matches and are recalculated from scratch whenever matches change.
+CREATE TABLE player_rating (
+    user_id INT NOT NULL PRIMARY KEY CHECK (user_id >= 0),
+    rating DOUBLE PRECISION NOT NULL,
+    matches_played INT NOT NULL CHECK (matches_played > 0)
+);
+CREATE INDEX ix_player_rating_rating ON player_rating (rating DESC);
+
+-- How the rating of a player changed in a versus match they played in
+CREATE TABLE player_rating_change (
+    user_id INT NOT NULL REFERENCES player_rating(user_id) ON DELETE CASCADE,
+    match_id INT NOT NULL REFERENCES versus_match(match_id) ON DELETE CASCADE,
+    rating_before DOUBLE PRECISION NOT NULL,
+    rating_after DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (user_id, match_id)
+);
@@ -1,7 +1,17 @@
-use crate::sea_orm_active_enums::OsuMode;
+use crate::sea_orm_active_enums::{LobbyTeam, OsuMode, PickBanKind, ScoringType, TournamentRole};
 
 mod tournament;
 
+impl From<LobbyTeam> for i32 {
+    fn from(value: LobbyTeam) -> Self {
+        match value {
+            LobbyTeam::None => 0,
+            LobbyTeam::Blue => 1,
+            LobbyTeam::Red => 2,
+        }
+    }
+}
+
 impl From<OsuMode> for i32 {
     fn from(value: OsuMode) -> Self {
         match value {
@@ -12,3 +22,68 @@ impl From<OsuMode> for i32 {
         }
     }
 }
+
+impl From<PickBanKind> for i32 {
+    fn from(value: PickBanKind) -> Self {
+        match value {
+            PickBanKind::Protect => 0,
+            PickBanKind::Ban => 1,
+            PickBanKind::Pick => 2,
+            PickBanKind::Tiebreaker => 3,
+        }
+    }
+}
+
+impl TryFrom<i32> for PickBanKind {
+    type Error = i32;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PickBanKind::Protect),
+            1 => Ok(PickBanKind::Ban),
+            2 => Ok(PickBanKind::Pick),
+            3 => Ok(PickBanKind::Tiebreaker),
+            _ => Err(value),
+        }
+    }
+}
+
+impl From<ScoringType> for i32 {
+    fn from(value: ScoringType) -> Self {
+        match value {
+            ScoringType::Score => 0,
+            ScoringType::Accuracy => 1,
+            ScoringType::Combo => 2,
+            ScoringType::ScoreV2 => 3,
+        }
+    }
+}
+
+impl From<TournamentRole> for i32 {
+    fn from(value: TournamentRole) -> Self {
+        match value {
+            TournamentRole::Host => 0,
+            TournamentRole::Admin => 1,
+            TournamentRole::Pooler => 2,
+            TournamentRole::Referee => 3,
+            TournamentRole::Streamer => 4,
+            TournamentRole::Viewer => 5,
+        }
+    }
+}
+
+impl TryFrom<i32> for TournamentRole {
+    type Error = i32;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(TournamentRole::Host),
+            1 => Ok(TournamentRole::Admin),
+            2 => Ok(TournamentRole::Pooler),
+            3 => Ok(TournamentRole::Referee),
+            4 => Ok(TournamentRole::Streamer),
+            5 => Ok(TournamentRole::Viewer),
+            _ => Err(value),
+        }
+    }
+}
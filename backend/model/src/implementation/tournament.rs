@@ -1,12 +1,89 @@
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Select};
 use utils::TStatsPaths;
 
 use crate::tournament;
 
+impl tournament::Entity {
+    /// Finds all tournaments that have not been deleted
+    pub fn find_active() -> Select<Self> {
+        Self::find().filter(tournament::Column::DeletedAt.is_null())
+    }
+
+    /// Finds the tournament with the given id, unless it has been deleted
+    pub fn find_active_by_id(id: i32) -> Select<Self> {
+        Self::find_by_id(id).filter(tournament::Column::DeletedAt.is_null())
+    }
+}
+
 impl tournament::Model {
     pub fn fetch_banner(&self, paths: &TStatsPaths) -> Option<Vec<u8>> {
-        let Some(ref file) = self.banner else {
-            return None;
+        let file = self.banner.as_ref()?;
+        std::fs::read(paths.banner(file)).ok()
+    }
+
+    /// Returns the rank by which a player is placed in this tournament.
+    /// For tournaments with BWS, this is the badge-weighted rank
+    /// `rank^(bws_base^(badges^bws_exponent))`, rounded to the nearest integer.
+    /// Otherwise, and for players without badges, this is the player's global rank.
+    pub fn seeding_rank(&self, rank: u32, badges: u32) -> u32 {
+        // `0^0` is 1, so with an exponent of 0 the formula would boost players without badges
+        if !self.bws || badges == 0 {
+            return rank;
+        }
+        let weight = self.bws_base.powf((badges as f64).powf(self.bws_exponent));
+        ((rank as f64).powf(weight).round() as u32).max(1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{sea_orm_active_enums::OsuMode, tournament};
+
+    fn tournament(bws_base: f64, bws_exponent: f64) -> tournament::Model {
+        tournament::Model {
+            id: 1,
+            name: "osu! World Cup 2023".to_owned(),
+            shorthand: "OWC23".to_owned(),
+            format: 4,
+            bws: true,
+            mode: OsuMode::Osu,
+            banner: None,
+            start_date: None,
+            end_date: None,
+            bws_base,
+            bws_exponent,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn seeding_rank_uses_default_bws_formula() {
+        let tournament = tournament(0.9937, 2.0);
+
+        assert_eq!(tournament.seeding_rank(1000, 0), 1000);
+        assert_eq!(tournament.seeding_rank(1000, 1), 957);
+        assert_eq!(tournament.seeding_rank(1000, 2), 842);
+        assert_eq!(tournament.seeding_rank(50_000, 4), 17662);
+        // Ranks never drop below 1
+        assert_eq!(tournament.seeding_rank(1, 3), 1);
+    }
+
+    #[test]
+    fn seeding_rank_keeps_rank_without_badges() {
+        // With an exponent of 0, every badge count would be weighted the same as one badge
+        let tournament = tournament(0.9937, 0.0);
+
+        assert_eq!(tournament.seeding_rank(1000, 0), 1000);
+        assert_eq!(tournament.seeding_rank(1000, 3), 957);
+    }
+
+    #[test]
+    fn seeding_rank_ignores_badges_without_bws() {
+        let tournament = tournament::Model {
+            bws: false,
+            ..tournament(0.9937, 2.0)
         };
-        std::fs::read(paths.banner(&file)).ok()
+
+        assert_eq!(tournament.seeding_rank(1000, 2), 1000);
     }
 }
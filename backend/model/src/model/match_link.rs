@@ -17,6 +17,7 @@ pub struct Model {
     pub match_id: i32,
     pub link_order: i16,
     pub osu_mp_id: i32,
+    pub warmups: i16,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveColumn)]
@@ -24,6 +25,7 @@ pub enum Column {
     MatchId,
     LinkOrder,
     OsuMpId,
+    Warmups,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DerivePrimaryKey)]
@@ -49,8 +51,9 @@ impl ColumnTrait for Column {
     fn def(&self) -> ColumnDef {
         match self {
             Self::MatchId => ColumnType::Integer.def(),
-            Self::LinkOrder => ColumnType::SmallInteger.def().unique(),
+            Self::LinkOrder => ColumnType::SmallInteger.def(),
             Self::OsuMpId => ColumnType::Integer.def(),
+            Self::Warmups => ColumnType::SmallInteger.def(),
         }
     }
 }
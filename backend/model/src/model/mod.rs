@@ -2,9 +2,13 @@
 
 pub mod prelude;
 
+pub mod beatmap;
 pub mod country_restriction;
 pub mod r#match;
 pub mod match_link;
+pub mod pick_ban;
+pub mod player_rating;
+pub mod player_rating_change;
 pub mod pool_bracket;
 pub mod pool_map;
 pub mod qualifier_run;
@@ -15,4 +19,5 @@ pub mod stage;
 pub mod team;
 pub mod team_member;
 pub mod tournament;
+pub mod tournament_staff;
 pub mod versus_match;
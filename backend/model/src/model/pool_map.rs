@@ -47,6 +47,7 @@ impl PrimaryKeyTrait for PrimaryKey {
 
 #[derive(Copy, Clone, Debug, EnumIter)]
 pub enum Relation {
+    PickBan,
     PoolBracket,
     Score,
 }
@@ -67,6 +68,7 @@ impl ColumnTrait for Column {
 impl RelationTrait for Relation {
     fn def(&self) -> RelationDef {
         match self {
+            Self::PickBan => Entity::has_many(super::pick_ban::Entity).into(),
             Self::PoolBracket => Entity::belongs_to(super::pool_bracket::Entity)
                 .from((
                     Column::TournamentId,
@@ -84,6 +86,12 @@ impl RelationTrait for Relation {
     }
 }
 
+impl Related<super::pick_ban::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PickBan.def()
+    }
+}
+
 impl Related<super::pool_bracket::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::PoolBracket.def()
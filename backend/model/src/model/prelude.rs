@@ -1,7 +1,11 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.1
 
+pub use super::beatmap::Entity as Beatmap;
 pub use super::country_restriction::Entity as CountryRestriction;
 pub use super::match_link::Entity as MatchLink;
+pub use super::pick_ban::Entity as PickBan;
+pub use super::player_rating::Entity as PlayerRating;
+pub use super::player_rating_change::Entity as PlayerRatingChange;
 pub use super::pool_bracket::Entity as PoolBracket;
 pub use super::pool_map::Entity as PoolMap;
 pub use super::qualifier_run::Entity as QualifierRun;
@@ -12,4 +16,5 @@ pub use super::stage::Entity as Stage;
 pub use super::team::Entity as Team;
 pub use super::team_member::Entity as TeamMember;
 pub use super::tournament::Entity as Tournament;
+pub use super::tournament_staff::Entity as TournamentStaff;
 pub use super::versus_match::Entity as VersusMatch;
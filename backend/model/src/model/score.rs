@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.1
 
+use super::sea_orm_active_enums::{LobbyTeam, ScoringType};
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -12,7 +13,7 @@ impl EntityName for Entity {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, DeriveModel, DeriveActiveModel, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, DeriveModel, DeriveActiveModel, Serialize, Deserialize)]
 pub struct Model {
     pub player_id: i32,
     pub tournament_id: i32,
@@ -21,6 +22,18 @@ pub struct Model {
     pub map_order: i16,
     pub match_id: i32,
     pub score: i64,
+    pub accuracy: Option<f32>,
+    pub max_combo: Option<i32>,
+    pub count_300: Option<i32>,
+    pub count_100: Option<i32>,
+    pub count_50: Option<i32>,
+    pub count_miss: Option<i32>,
+    pub mods: Option<i32>,
+    pub pass: Option<bool>,
+    pub perfect: Option<bool>,
+    pub scoring_type: Option<ScoringType>,
+    pub team: Option<LobbyTeam>,
+    pub slot: Option<i16>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveColumn)]
@@ -32,6 +45,21 @@ pub enum Column {
     MapOrder,
     MatchId,
     Score,
+    Accuracy,
+    MaxCombo,
+    #[sea_orm(column_name = "count_300")]
+    Count300,
+    #[sea_orm(column_name = "count_100")]
+    Count100,
+    #[sea_orm(column_name = "count_50")]
+    Count50,
+    CountMiss,
+    Mods,
+    Pass,
+    Perfect,
+    ScoringType,
+    Team,
+    Slot,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DerivePrimaryKey)]
@@ -68,6 +96,18 @@ impl ColumnTrait for Column {
             Self::MapOrder => ColumnType::SmallInteger.def(),
             Self::MatchId => ColumnType::Integer.def(),
             Self::Score => ColumnType::BigInteger.def(),
+            Self::Accuracy => ColumnType::Float.def().null(),
+            Self::MaxCombo => ColumnType::Integer.def().null(),
+            Self::Count300 => ColumnType::Integer.def().null(),
+            Self::Count100 => ColumnType::Integer.def().null(),
+            Self::Count50 => ColumnType::Integer.def().null(),
+            Self::CountMiss => ColumnType::Integer.def().null(),
+            Self::Mods => ColumnType::Integer.def().null(),
+            Self::Pass => ColumnType::Boolean.def().null(),
+            Self::Perfect => ColumnType::Boolean.def().null(),
+            Self::ScoringType => ScoringType::db_type().def().null(),
+            Self::Team => LobbyTeam::db_type().def().null(),
+            Self::Slot => ColumnType::SmallInteger.def().null(),
         }
     }
 }
@@ -3,6 +3,16 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Copy, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "lobby_team")]
+pub enum LobbyTeam {
+    #[sea_orm(string_value = "blue")]
+    Blue,
+    #[sea_orm(string_value = "none")]
+    None,
+    #[sea_orm(string_value = "red")]
+    Red,
+}
 #[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Copy, Serialize, Deserialize)]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "match_type")]
 pub enum MatchType {
@@ -23,3 +33,43 @@ pub enum OsuMode {
     #[sea_orm(string_value = "taiko")]
     Taiko,
 }
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Copy, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "pick_ban_kind")]
+pub enum PickBanKind {
+    #[sea_orm(string_value = "ban")]
+    Ban,
+    #[sea_orm(string_value = "pick")]
+    Pick,
+    #[sea_orm(string_value = "protect")]
+    Protect,
+    #[sea_orm(string_value = "tiebreaker")]
+    Tiebreaker,
+}
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Copy, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "scoring_type")]
+pub enum ScoringType {
+    #[sea_orm(string_value = "accuracy")]
+    Accuracy,
+    #[sea_orm(string_value = "combo")]
+    Combo,
+    #[sea_orm(string_value = "score")]
+    Score,
+    #[sea_orm(string_value = "score_v2")]
+    ScoreV2,
+}
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Copy, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "tournament_role")]
+pub enum TournamentRole {
+    #[sea_orm(string_value = "admin")]
+    Admin,
+    #[sea_orm(string_value = "host")]
+    Host,
+    #[sea_orm(string_value = "pooler")]
+    Pooler,
+    #[sea_orm(string_value = "referee")]
+    Referee,
+    #[sea_orm(string_value = "streamer")]
+    Streamer,
+    #[sea_orm(string_value = "viewer")]
+    Viewer,
+}
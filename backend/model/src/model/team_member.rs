@@ -16,12 +16,14 @@ impl EntityName for Entity {
 pub struct Model {
     pub team_id: i32,
     pub user_id: i32,
+    pub registered_rank: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveColumn)]
 pub enum Column {
     TeamId,
     UserId,
+    RegisteredRank,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DerivePrimaryKey)]
@@ -48,6 +50,7 @@ impl ColumnTrait for Column {
         match self {
             Self::TeamId => ColumnType::Integer.def(),
             Self::UserId => ColumnType::Integer.def(),
+            Self::RegisteredRank => ColumnType::Integer.def().null(),
         }
     }
 }
@@ -13,7 +13,7 @@ impl EntityName for Entity {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, DeriveModel, DeriveActiveModel, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, DeriveModel, DeriveActiveModel, Serialize, Deserialize)]
 pub struct Model {
     pub id: i32,
     pub name: String,
@@ -24,6 +24,9 @@ pub struct Model {
     pub banner: Option<String>,
     pub start_date: Option<DateTime>,
     pub end_date: Option<DateTime>,
+    pub bws_base: f64,
+    pub bws_exponent: f64,
+    pub deleted_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveColumn)]
@@ -37,6 +40,9 @@ pub enum Column {
     Banner,
     StartDate,
     EndDate,
+    BwsBase,
+    BwsExponent,
+    DeletedAt,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DerivePrimaryKey)]
@@ -57,6 +63,7 @@ pub enum Relation {
     RankRestriction,
     Stage,
     Team,
+    TournamentStaff,
 }
 
 impl ColumnTrait for Column {
@@ -72,6 +79,9 @@ impl ColumnTrait for Column {
             Self::Banner => ColumnType::String(StringLen::N(48u32)).def().null(),
             Self::StartDate => ColumnType::DateTime.def().null(),
             Self::EndDate => ColumnType::DateTime.def().null(),
+            Self::BwsBase => ColumnType::Double.def(),
+            Self::BwsExponent => ColumnType::Double.def(),
+            Self::DeletedAt => ColumnType::DateTime.def().null(),
         }
     }
 }
@@ -83,6 +93,7 @@ impl RelationTrait for Relation {
             Self::RankRestriction => Entity::has_many(super::rank_restriction::Entity).into(),
             Self::Stage => Entity::has_many(super::stage::Entity).into(),
             Self::Team => Entity::has_many(super::team::Entity).into(),
+            Self::TournamentStaff => Entity::has_many(super::tournament_staff::Entity).into(),
         }
     }
 }
@@ -111,4 +122,10 @@ impl Related<super::team::Entity> for Entity {
     }
 }
 
+impl Related<super::tournament_staff::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TournamentStaff.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -48,6 +48,7 @@ impl PrimaryKeyTrait for PrimaryKey {
 #[derive(Copy, Clone, Debug, EnumIter)]
 pub enum Relation {
     Match,
+    PickBan,
     Team2,
     Team1,
 }
@@ -76,6 +77,7 @@ impl RelationTrait for Relation {
                     super::r#match::Column::MatchType,
                 ))
                 .into(),
+            Self::PickBan => Entity::has_many(super::pick_ban::Entity).into(),
             Self::Team2 => Entity::belongs_to(super::team::Entity)
                 .from(Column::TeamBlue)
                 .to(super::team::Column::Id)
@@ -94,4 +96,10 @@ impl Related<super::r#match::Entity> for Entity {
     }
 }
 
+impl Related<super::pick_ban::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PickBan.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
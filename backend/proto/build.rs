@@ -19,6 +19,9 @@ fn main() -> std::io::Result<()> {
                 "../../proto/utils.proto",
                 "../../proto/scores.proto",
                 "../../proto/team.proto",
+                "../../proto/matches.proto",
+                "../../proto/stats.proto",
+                "../../proto/ratings.proto",
             ],
             &["../../proto/"],
         )?;
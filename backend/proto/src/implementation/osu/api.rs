@@ -1,60 +1,399 @@
-use deadpool_redis::Pool as RedisConnectionPool;
-use miette::IntoDiagnostic;
-use rosu_v2::{error::OsuError, Osu};
-use utils::{cache::{CacheError, CacheResult}, Cacheable};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
+use futures::future::try_join_all;
+use miette::{miette, IntoDiagnostic};
+use rosu_v2::{
+    error::OsuError,
+    model::{
+        beatmap::{BeatmapExtended, BeatmapsetExtended},
+        matches::{MatchEvent, OsuMatch},
+        user::{User, UserExtended},
+        GameMode,
+    },
+    Osu, OsuResult,
+};
+use sqlx::PgPool;
+use utils::{
+    cache::{
+        cache_entries, get_entries, spawn_refresh, Cache, CacheError, CachePolicy, CacheResult,
+    },
+    Cacheable,
+};
 
-/// Gets information about a map from the osu API.
+/// The maximum number of ids the osu API accepts in a single request for multiple users.
+/// This is also the number of map requests that are sent at the same time.
+pub const MAX_IDS_PER_REQUEST: usize = 50;
+
+/// The requests to the osu API that are needed by the server.
 ///
-/// # Panics
+/// This is implemented by [`Osu`] and can be implemented by fakes, so that the server can be run
+/// without access to the osu API.
+#[tonic::async_trait]
+pub trait OsuApi: Send + Sync {
+    /// Gets the beatmapset containing the map with the given id.
+    /// The beatmapset's `maps` are guaranteed to contain the map.
+    async fn beatmapset_from_map_id(&self, map_id: u32) -> OsuResult<BeatmapsetExtended>;
+
+    /// Gets a user in the given mode or in their default mode if no mode is given
+    async fn user(&self, user_id: u32, mode: Option<GameMode>) -> OsuResult<UserExtended>;
+
+    /// Gets a multiplayer lobby with up to 100 of its events. These are its latest events, or
+    /// the ones right before the given event. Use [`get_match`] to get all events of a lobby.
+    async fn osu_match(&self, match_id: u32, before_event_id: Option<u64>) -> OsuResult<OsuMatch>;
+
+    /// Gets the users with the given ids in their default modes.
+    /// At most [`MAX_IDS_PER_REQUEST`] ids are accepted. Users that don't exist are left out.
+    async fn users(&self, user_ids: &[u32]) -> OsuResult<Vec<User>>;
+}
+
+#[tonic::async_trait]
+impl OsuApi for Osu {
+    async fn beatmapset_from_map_id(&self, map_id: u32) -> OsuResult<BeatmapsetExtended> {
+        Osu::beatmapset_from_map_id(self, map_id).await
+    }
+
+    async fn user(&self, user_id: u32, mode: Option<GameMode>) -> OsuResult<UserExtended> {
+        match mode {
+            Some(mode) => Osu::user(self, user_id).mode(mode).await,
+            None => Osu::user(self, user_id).await,
+        }
+    }
+
+    async fn osu_match(&self, match_id: u32, before_event_id: Option<u64>) -> OsuResult<OsuMatch> {
+        match before_event_id {
+            Some(event_id) => {
+                Osu::osu_match(self, match_id)
+                    .before(event_id)
+                    .limit(100)
+                    .await
+            }
+            None => Osu::osu_match(self, match_id).await,
+        }
+    }
+
+    async fn users(&self, user_ids: &[u32]) -> OsuResult<Vec<User>> {
+        Osu::users(self, user_ids.iter().copied()).await
+    }
+}
+
+/// How long maps are cached. Maps are stored in the database, so they don't need to be refreshed.
+const MAP_CACHE_POLICY: CachePolicy = CachePolicy {
+    soft_ttl: 3600,
+    hard_ttl: 3600,
+    not_found_ttl: 600,
+};
+
+/// How long users are cached. Stale users are served for up to a day while they are refreshed.
+const USER_CACHE_POLICY: CachePolicy = CachePolicy {
+    soft_ttl: 60,
+    hard_ttl: 86400,
+    not_found_ttl: 600,
+};
+
+/// Gets information about a map.
 ///
-/// Panics if the map has no beatmapset.
+/// See [`get_maps`] for where the information comes from.
 ///
 /// # Errors
 ///
-/// This function will return an error if something goes wrong during cacheing or communicating with the osu api.
+/// This function will return an error if the map doesn't exist or if something goes wrong during
+/// cacheing, accessing the database or communicating with the osu api.
 pub async fn get_map(
-    redis: &RedisConnectionPool,
-    osu: &Osu,
+    cache: &Cache,
+    db: &PgPool,
+    osu: &Arc<dyn OsuApi>,
     map_id: u32,
 ) -> CacheResult<crate::osu::Beatmap> {
-    // Find the map's data
-    let map =
-        crate::osu::Beatmap::get_cached_or::<CacheError, _>(redis, &map_id, Some(3600), || async {
-            // If it doesn't exist in the cache, request it from the osu api
-            let mapset = osu
-                .beatmapset_from_map_id(map_id)
+    let mut maps = get_maps(cache, db, osu, &[map_id]).await?;
+    Ok(maps.remove(0))
+}
+
+/// Gets information about multiple maps at once. The maps are returned in the order of their ids.
+///
+/// Maps are looked up in the cache first and then in the `beatmap` table. Maps that are in
+/// neither are requested from the osu API and stored in both. Maps that don't exist are
+/// remembered for a while, so that they aren't requested again on every call.
+///
+/// # Errors
+///
+/// This function will return an error if one of the maps doesn't exist or if something goes
+/// wrong during cacheing, accessing the database or communicating with the osu api.
+pub async fn get_maps(
+    cache: &Cache,
+    db: &PgPool,
+    osu: &Arc<dyn OsuApi>,
+    map_ids: &[u32],
+) -> CacheResult<Vec<crate::osu::Beatmap>> {
+    let unique_ids = map_ids.iter().copied().collect::<HashSet<_>>();
+    let mut maps = HashMap::with_capacity(unique_ids.len());
+    let mut known_missing = HashSet::new();
+
+    // Look for the maps in the cache. If the cache fails, everything is looked up elsewhere
+    let keys = unique_ids.iter().collect::<Vec<_>>();
+    let entries = get_entries::<crate::osu::Beatmap>(cache, &keys)
+        .await
+        .unwrap_or_default();
+    for (&id, entry) in keys.iter().copied().zip(entries) {
+        match entry.map(|entry| entry.value) {
+            Some(Some(map)) => {
+                maps.insert(id, map);
+            }
+            Some(None) => {
+                known_missing.insert(id);
+            }
+            None => {}
+        }
+    }
+
+    // Then look for the rest in the database
+    let missing = unique_ids
+        .iter()
+        .copied()
+        .filter(|id| !maps.contains_key(id) && !known_missing.contains(id))
+        .collect::<Vec<_>>();
+    let mut found = crate::osu::Beatmap::load_many(db, &missing)
+        .await
+        .into_diagnostic()
+        .map_err(CacheError::Request)?;
+
+    // And request the remaining ones from the osu api. The multi-map endpoint doesn't include
+    // the difficulty attributes, so the beatmapsets are requested concurrently instead
+    let missing = missing
+        .into_iter()
+        .filter(|id| !found.iter().any(|map| map.map_id == *id))
+        .collect::<Vec<_>>();
+    let mut not_found = vec![];
+    for chunk in missing.chunks(MAX_IDS_PER_REQUEST) {
+        let mapsets = try_join_all(chunk.iter().map(|&id| async move {
+            match osu.beatmapset_from_map_id(id).await {
+                Ok(mapset) => Ok(Some(mapset)),
+                Err(OsuError::NotFound) => Ok(None),
+                Err(error) => Err(error),
+            }
+        }))
+        .await
+        .into_diagnostic()
+        .map_err(CacheError::Request)?;
+
+        let creator_ids = chunk
+            .iter()
+            .zip(&mapsets)
+            .filter_map(|(&id, mapset)| find_map(mapset.as_ref()?, id))
+            .map(|map| map.creator_id)
+            .collect::<Vec<_>>();
+        let creators = fetch_users(cache, osu, &creator_ids).await?;
+
+        for (id, mapset) in chunk.iter().zip(&mapsets) {
+            let Some((mapset, map)) = mapset
+                .as_ref()
+                .and_then(|mapset| Some((mapset, find_map(mapset, *id)?)))
+            else {
+                not_found.push(id);
+                continue;
+            };
+            // Maps of deleted users still have the name of their creator
+            let creator =
+                creators
+                    .get(&map.creator_id)
+                    .cloned()
+                    .unwrap_or_else(|| crate::osu::User {
+                        user_id: map.creator_id,
+                        username: mapset.creator_name.to_string(),
+                        ..Default::default()
+                    });
+
+            let map = crate::osu::Beatmap::from_map_set_and_creator(map, mapset, &creator);
+            map.store(db)
                 .await
                 .into_diagnostic()
                 .map_err(CacheError::Request)?;
-            let maps = mapset.maps.as_ref().unwrap();
-            // beatmapset_from_map_id guarantees that maps has entries and we also know that the map with the given id exists
-            let map = maps.iter().find(|map| map.map_id == map_id).unwrap();
-            let creator = crate::osu::User::get_cached_or::<OsuError, _>(
-                redis,
-                &map.creator_id,
-                Some(3600),
-                || async { osu.user(map.creator_id).await.map(crate::osu::User::from) },
-            )
-            .await?;
-
-            Ok(crate::osu::Beatmap::from_map_set_and_creator(
-                map, &mapset, &creator,
-            ))
+            found.push(map);
+        }
+    }
+
+    cache_entries(cache, &found, &not_found, MAP_CACHE_POLICY).await?;
+    maps.extend(found.into_iter().map(|map| (map.map_id, map)));
+
+    map_ids
+        .iter()
+        .map(|id| {
+            maps.get(id)
+                .cloned()
+                .ok_or_else(|| CacheError::Request(miette!("map {id} does not exist")))
         })
-        .await?;
+        .collect()
+}
 
-    Ok(map)
+/// Finds the map with the given id in a beatmapset
+fn find_map(mapset: &BeatmapsetExtended, map_id: u32) -> Option<&BeatmapExtended> {
+    mapset
+        .maps
+        .as_ref()?
+        .iter()
+        .find(|map| map.map_id == map_id)
 }
 
+/// Gets a multiplayer lobby with all of its events.
+///
+/// The osu API only sends up to 100 events per request, so earlier events are requested until
+/// the lobby's first event is reached.
+///
+/// # Errors
+///
+/// This function will return an error if something goes wrong while communicating with the osu
+/// api, e.g. [`OsuError::NotFound`] if the lobby doesn't exist.
+pub async fn get_match(osu: &Arc<dyn OsuApi>, match_id: u32) -> OsuResult<OsuMatch> {
+    let mut lobby = osu.osu_match(match_id, None).await?;
+
+    while let Some(first_id) = lobby
+        .events
+        .first()
+        .map(MatchEvent::event_id)
+        .filter(|&first_id| first_id != lobby.first_event_id)
+    {
+        let mut previous = osu.osu_match(match_id, Some(first_id)).await?;
+        // Stop instead of requesting the same events forever if the lobby is missing events
+        if previous.events.is_empty() {
+            break;
+        }
+        previous.events.append(&mut lobby.events);
+        previous.users.extend(lobby.users.drain());
+        lobby.events = previous.events;
+        lobby.users = previous.users;
+    }
+
+    Ok(lobby)
+}
+
+/// Gets information about a user, or `None` if the user doesn't exist.
+///
+/// # Errors
+///
+/// This function will return an error if something goes wrong during cacheing or communicating
+/// with the osu api.
 pub async fn get_user(
-    redis: &RedisConnectionPool,
-    osu: &Osu,
+    cache: &Cache,
+    osu: &Arc<dyn OsuApi>,
     user_id: u32,
-) -> CacheResult<crate::osu::User> {
-    crate::osu::User::get_cached_or::<OsuError, _>(redis, &user_id, Some(60), || async {
-        let usr = osu.user(user_id).await?;
-        Ok(usr.into())
-    })
-    .await
-}
\ No newline at end of file
+) -> CacheResult<Option<crate::osu::User>> {
+    let mut users = get_users(cache, osu, &[user_id]).await?;
+    Ok(users.remove(0))
+}
+
+/// Gets information about multiple users at once. The users are returned in the order of their
+/// ids, with `None` for users that don't exist, e.g. because they were deleted or restricted.
+///
+/// Users are looked up in the cache first. Users that aren't cached are requested from the
+/// osu API in batches and cached. Stale users are returned as well, but refreshed in the
+/// background.
+///
+/// # Errors
+///
+/// This function will return an error if something goes wrong during cacheing or communicating
+/// with the osu api.
+pub async fn get_users(
+    cache: &Cache,
+    osu: &Arc<dyn OsuApi>,
+    user_ids: &[u32],
+) -> CacheResult<Vec<Option<crate::osu::User>>> {
+    let users = fetch_users(cache, osu, user_ids).await?;
+    Ok(user_ids.iter().map(|id| users.get(id).cloned()).collect())
+}
+
+/// Gets the users with the given ids from the cache or the osu API, leaving out those that don't
+/// exist.
+async fn fetch_users(
+    cache: &Cache,
+    osu: &Arc<dyn OsuApi>,
+    user_ids: &[u32],
+) -> CacheResult<HashMap<u32, crate::osu::User>> {
+    let unique_ids = user_ids.iter().copied().collect::<HashSet<_>>();
+    let mut users = HashMap::with_capacity(unique_ids.len());
+    let mut known_missing = HashSet::new();
+    let mut stale = vec![];
+
+    // Look for the users in the cache. If the cache fails, everything is requested
+    let keys = unique_ids.iter().collect::<Vec<_>>();
+    let entries = get_entries::<crate::osu::User>(cache, &keys)
+        .await
+        .unwrap_or_default();
+    for (&id, entry) in keys.iter().copied().zip(entries) {
+        let Some(entry) = entry else {
+            continue;
+        };
+        if entry.is_stale() {
+            stale.push(id);
+        }
+        match entry.value {
+            Some(user) => {
+                users.insert(id, user);
+            }
+            None => {
+                known_missing.insert(id);
+            }
+        }
+    }
+
+    // Request the rest from the osu api
+    let missing = unique_ids
+        .into_iter()
+        .filter(|id| !users.contains_key(id) && !known_missing.contains(id))
+        .collect::<Vec<_>>();
+    let requested = request_users(cache, osu.as_ref(), &missing).await?;
+    users.extend(requested.into_iter().map(|user| (user.user_id, user)));
+
+    // Refresh the stale users without making the caller wait for it
+    if !stale.is_empty() {
+        stale.sort_unstable();
+        let name = format!(
+            "{}:{}",
+            crate::osu::User::type_key(),
+            stale
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        let (refresh_cache, refresh_osu) = (cache.clone(), osu.clone());
+        spawn_refresh(cache, name, async move {
+            request_users(&refresh_cache, refresh_osu.as_ref(), &stale)
+                .await
+                .map(|_| ())
+        });
+    }
+
+    Ok(users)
+}
+
+/// Requests users from the osu API in batches and caches them along with the users that don't
+/// exist
+async fn request_users(
+    cache: &Cache,
+    osu: &dyn OsuApi,
+    user_ids: &[u32],
+) -> CacheResult<Vec<crate::osu::User>> {
+    let mut users = Vec::with_capacity(user_ids.len());
+    for chunk in user_ids.chunks(MAX_IDS_PER_REQUEST) {
+        let requested = osu
+            .users(chunk)
+            .await
+            .into_diagnostic()
+            .map_err(CacheError::Request)?
+            .into_iter()
+            .map(crate::osu::User::from)
+            .collect::<Vec<_>>();
+
+        // Users that weren't returned don't exist
+        let not_found = chunk
+            .iter()
+            .filter(|&&id| !requested.iter().any(|user| user.user_id == id))
+            .collect::<Vec<_>>();
+        cache_entries(cache, &requested, &not_found, USER_CACHE_POLICY).await?;
+        users.extend(requested);
+    }
+
+    Ok(users)
+}
@@ -1,5 +1,8 @@
+use std::{sync::LazyLock, time::Duration};
+
 use rosu_v2::prelude::*;
-use utils::Cacheable;
+use sqlx::PgPool;
+use utils::{cache::LocalCache, Cacheable};
 
 impl Cacheable for crate::osu::Beatmap {
     type KeyType = u32;
@@ -11,6 +14,13 @@ impl Cacheable for crate::osu::Beatmap {
     fn key(&self) -> &Self::KeyType {
         &self.map_id
     }
+
+    fn local_cache() -> Option<&'static LocalCache<Self>> {
+        // Maps are read on almost every request and never change
+        static CACHE: LazyLock<LocalCache<crate::osu::Beatmap>> =
+            LazyLock::new(|| LocalCache::new(10_000, Duration::from_secs(600)));
+        Some(&CACHE)
+    }
 }
 
 impl crate::osu::Beatmap {
@@ -38,3 +48,126 @@ impl crate::osu::Beatmap {
         }
     }
 }
+
+/// A row of the `beatmap` table
+#[derive(sqlx::FromRow)]
+struct BeatmapRow {
+    map_id: i64,
+    mapset_id: i64,
+    artist_name: String,
+    name: String,
+    difficulty_name: String,
+    creator_id: i64,
+    creator_name: String,
+    creator_country: String,
+    creator_cover_url: String,
+    stars: f32,
+    length: i32,
+    bpm: f32,
+    cs: f32,
+    ar: f32,
+    od: f32,
+    hp: f32,
+}
+
+impl From<BeatmapRow> for crate::osu::Beatmap {
+    fn from(row: BeatmapRow) -> Self {
+        Self {
+            artist_name: row.artist_name,
+            name: row.name,
+            difficulty_name: row.difficulty_name,
+            mapset_id: row.mapset_id as u32,
+            map_id: row.map_id as u32,
+            creator: Some(crate::osu::User {
+                user_id: row.creator_id as u32,
+                username: row.creator_name,
+                country: row.creator_country,
+                cover_url: row.creator_cover_url,
+            }),
+            difficulty: Some(crate::osu::Difficulty {
+                stars: row.stars,
+                length: row.length as u32,
+                bpm: row.bpm,
+                cs: row.cs,
+                ar: row.ar,
+                od: row.od,
+                hp: row.hp,
+            }),
+        }
+    }
+}
+
+impl crate::osu::Beatmap {
+    /// Loads all maps with the given ids that were stored in the `beatmap` table before
+    pub async fn load_many(db: &PgPool, map_ids: &[u32]) -> sqlx::Result<Vec<Self>> {
+        if map_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let map_ids = map_ids.iter().map(|&id| id as i64).collect::<Vec<_>>();
+        let rows = sqlx::query_as::<_, BeatmapRow>(
+            "
+            SELECT map_id, mapset_id, artist_name, name, difficulty_name,
+                creator_id, creator_name, creator_country, creator_cover_url,
+                stars, length, bpm, cs, ar, od, hp
+            FROM beatmap WHERE map_id = ANY($1)
+            ",
+        )
+        .bind(map_ids)
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows.into_iter().map(Self::from).collect())
+    }
+
+    /// Stores this map in the `beatmap` table, replacing the data that was stored for it before
+    pub async fn store(&self, db: &PgPool) -> sqlx::Result<()> {
+        let creator = self.creator.clone().unwrap_or_default();
+        let difficulty = self.difficulty.unwrap_or_default();
+        sqlx::query(
+            "
+            INSERT INTO beatmap (map_id, mapset_id, artist_name, name, difficulty_name,
+                creator_id, creator_name, creator_country, creator_cover_url,
+                stars, length, bpm, cs, ar, od, hp)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+            ON CONFLICT (map_id) DO UPDATE SET
+                mapset_id = EXCLUDED.mapset_id,
+                artist_name = EXCLUDED.artist_name,
+                name = EXCLUDED.name,
+                difficulty_name = EXCLUDED.difficulty_name,
+                creator_id = EXCLUDED.creator_id,
+                creator_name = EXCLUDED.creator_name,
+                creator_country = EXCLUDED.creator_country,
+                creator_cover_url = EXCLUDED.creator_cover_url,
+                stars = EXCLUDED.stars,
+                length = EXCLUDED.length,
+                bpm = EXCLUDED.bpm,
+                cs = EXCLUDED.cs,
+                ar = EXCLUDED.ar,
+                od = EXCLUDED.od,
+                hp = EXCLUDED.hp,
+                updated_at = now()
+            ",
+        )
+        .bind(self.map_id as i64)
+        .bind(self.mapset_id as i64)
+        .bind(&self.artist_name)
+        .bind(&self.name)
+        .bind(&self.difficulty_name)
+        .bind(creator.user_id as i64)
+        .bind(creator.username)
+        .bind(creator.country)
+        .bind(creator.cover_url)
+        .bind(difficulty.stars)
+        .bind(difficulty.length as i32)
+        .bind(difficulty.bpm)
+        .bind(difficulty.cs)
+        .bind(difficulty.ar)
+        .bind(difficulty.od)
+        .bind(difficulty.hp)
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1,22 +1,55 @@
-use rosu_v2::prelude::UserExtended;
+use std::{sync::LazyLock, time::Duration};
+
+use rosu_v2::prelude::{User, UserExtended};
 use url::Url;
-use utils::Cacheable;
+use utils::{cache::LocalCache, Cacheable};
+
+/// Extracts the file name of a user's profile banner from its url
+fn cover_file_name(cover_url: String) -> String {
+    match Url::parse(&cover_url) {
+        Ok(url) => url
+            .path_segments()
+            .and_then(|mut iter| iter.next_back())
+            .unwrap_or(&cover_url)
+            .to_owned(),
+        Err(_) => cover_url,
+    }
+}
 
 impl From<UserExtended> for crate::osu::User {
     fn from(value: UserExtended) -> Self {
-        let url = match Url::parse(&value.cover.url) {
-            Ok(url) => url
-                .path_segments()
-                .and_then(|iter| iter.last())
-                .unwrap_or(&value.cover.url)
-                .to_owned(),
-            Err(_) => value.cover.url,
-        };
         Self {
             user_id: value.user_id,
             username: value.username.to_string(),
             country: value.country_code.to_string(),
-            cover_url: url,
+            cover_url: cover_file_name(value.cover.url),
+        }
+    }
+}
+
+impl From<User> for crate::osu::User {
+    fn from(value: User) -> Self {
+        Self {
+            user_id: value.user_id,
+            username: value.username.to_string(),
+            country: value.country_code.to_string(),
+            cover_url: value
+                .cover
+                .map(|cover| cover_file_name(cover.url))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl crate::osu::User {
+    /// Stands in for a user who doesn't exist anymore, e.g. because they were deleted or
+    /// restricted. Only their id is known, so it is shown in place of their name.
+    pub fn missing(user_id: u32) -> Self {
+        Self {
+            user_id,
+            username: user_id.to_string(),
+            country: String::new(),
+            cover_url: String::new(),
         }
     }
 }
@@ -31,4 +64,11 @@ impl Cacheable for crate::osu::User {
     fn key(&self) -> &Self::KeyType {
         &self.user_id
     }
+
+    fn local_cache() -> Option<&'static LocalCache<Self>> {
+        // Users are only cached locally for a short time, since they are refreshed every minute
+        static CACHE: LazyLock<LocalCache<crate::osu::User>> =
+            LazyLock::new(|| LocalCache::new(10_000, Duration::from_secs(30)));
+        Some(&CACHE)
+    }
 }
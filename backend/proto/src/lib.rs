@@ -43,3 +43,15 @@ pub mod scores {
 pub mod team {
     tonic::include_proto!("team");
 }
+
+pub mod matches {
+    tonic::include_proto!("matches");
+}
+
+pub mod stats {
+    tonic::include_proto!("stats");
+}
+
+pub mod ratings {
+    tonic::include_proto!("ratings");
+}
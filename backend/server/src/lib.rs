@@ -1,13 +1,21 @@
+// tonic::Status is large, but it is the error type of every handler
+#![allow(clippy::result_large_err)]
+
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::ops::Deref;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
 use deadpool_redis::Config;
 use http::{HeaderName, HeaderValue, Method};
-use miette::{Context, IntoDiagnostic};
-use proto::osu::osu_user_service_server::OsuUserServiceServer;
+use miette::{miette, Context, IntoDiagnostic};
+use proto::matches::match_service_server::MatchServiceServer;
+use proto::osu::{api::OsuApi, osu_user_service_server::OsuUserServiceServer};
+use proto::ratings::rating_service_server::RatingServiceServer;
 use proto::scores::score_service_server::ScoreServiceServer;
+use proto::stats::stats_service_server::StatsServiceServer;
+use proto::team::team_service_server::TeamServiceServer;
 use proto::{
     osu_auth::osu_auth_service_server::OsuAuthServiceServer,
     pool::pool_service_server::PoolServiceServer, stages::stage_service_server::StageServiceServer,
@@ -26,43 +34,53 @@ use tower_http::{
     cors::{AllowHeaders, CorsLayer, ExposeHeaders},
     trace::{self, TraceLayer},
 };
-use tracing::{error, info, info_span, warn, Level};
+use tracing::{info, info_span, warn, Level};
 
 use proto::debug_data::debug_service_server::DebugServiceServer;
 use proto::tournaments::tournament_service_server::TournamentServiceServer;
 
 use crate::osu::auth::Session;
 use crate::routes::debug::DebugServiceImpl;
+use crate::routes::matches::MatchServiceImpl;
 use crate::routes::osu_auth::OsuAuthServiceImpl;
 use crate::routes::osu_user::OsuUserServiceImpl;
 use crate::routes::pool::PoolServiceImpl;
+use crate::routes::rating::RatingServiceImpl;
 use crate::routes::score::ScoreServiceImpl;
 use crate::routes::stage::StageServiceImpl;
+use crate::routes::stats::StatsServiceImpl;
+use crate::routes::team::TeamServiceImpl;
 use crate::routes::tournament::TournamentServiceImpl;
 
-use utils::{consts::*, Cacheable, LogStatus, TStatsPaths};
-
-type RedisConnection = deadpool_redis::Connection;
-type RedisConnectionPool = deadpool_redis::Pool;
+use utils::{
+    cache::{Cache, MemoryBackend, RedisBackend},
+    consts::*,
+    Cacheable, LogStatus, TStatsPaths,
+};
 
 mod osu;
+mod permission;
+mod restriction;
 mod routes;
+mod stats;
 
+/// The state shared by all services. Cloning it is cheap, since all clones share the same state.
 #[derive(Clone)]
-pub struct AppState {
+pub struct AppState(Arc<AppStateInner>);
+
+pub struct AppStateInner {
     pub db: DatabaseConnection,
     pub sqlx: PgPool,
-    pub osu: Arc<Osu>,
-    pub redis: RedisConnectionPool,
+    pub osu: Arc<dyn OsuApi>,
+    pub cache: Cache,
     pub paths: TStatsPaths,
 }
 
-impl AppState {
-    pub async fn redis_connection(&self) -> Result<RedisConnection, tonic::Status> {
-        self.redis.get().await.map_err(|e| {
-            error!(source = %e, "could not get redis connection");
-            Status::internal("could not connect to redis")
-        })
+impl Deref for AppState {
+    type Target = AppStateInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
 }
 
@@ -76,8 +94,8 @@ pub async fn run_server() -> miette::Result<()> {
 
     utils::crypt::verify_aes_key().into_diagnostic()?;
 
-    let (db, redis, osu) = tokio::join!(setup_database(), setup_redis(), setup_osu());
-    let ((db, sqlx), redis, osu) = (db?, redis?, osu?);
+    let (db, cache, osu) = tokio::join!(setup_database(), setup_cache(), setup_osu());
+    let ((db, sqlx), cache, osu) = (db?, cache?, osu?);
 
     let base_path = parse_env(TSTATS_DATA_DIR, || {
         std::env::current_dir()
@@ -89,13 +107,16 @@ pub async fn run_server() -> miette::Result<()> {
         .wrap_err("could not canonicalize path")?;
     info!("Storing data in {:?}", paths.base());
 
-    let state = AppState {
+    let state = AppState(Arc::new(AppStateInner {
         db,
         sqlx,
-        redis,
+        cache,
         osu,
         paths,
-    };
+    }));
+
+    // Ratings are derived from the matches, which might have changed since the last start
+    routes::rating::spawn_rating_replay(state.clone());
 
     let reflection_server = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
@@ -108,15 +129,29 @@ pub async fn run_server() -> miette::Result<()> {
     health_reporter
         .set_serving::<TournamentServiceServer<TournamentServiceImpl>>()
         .await;
-    health_reporter
-        .set_serving::<DebugServiceServer<DebugServiceImpl>>()
-        .await;
+    if cfg!(debug_assertions) {
+        health_reporter
+            .set_serving::<DebugServiceServer<DebugServiceImpl>>()
+            .await;
+    }
     health_reporter
         .set_serving::<StageServiceServer<StageServiceImpl>>()
         .await;
     health_reporter
         .set_serving::<PoolServiceServer<PoolServiceImpl>>()
         .await;
+    health_reporter
+        .set_serving::<TeamServiceServer<TeamServiceImpl>>()
+        .await;
+    health_reporter
+        .set_serving::<MatchServiceServer<MatchServiceImpl>>()
+        .await;
+    health_reporter
+        .set_serving::<StatsServiceServer<StatsServiceImpl>>()
+        .await;
+    health_reporter
+        .set_serving::<RatingServiceServer<RatingServiceImpl>>()
+        .await;
 
     // Type fun
     async fn set_serving<T: NamedService>(rep: &mut HealthReporter, _: &T) {
@@ -143,7 +178,7 @@ pub async fn run_server() -> miette::Result<()> {
 
     drop(server_setup_span);
 
-    let _auth_interceptor = AuthInterceptor {
+    let auth_interceptor = AuthInterceptor {
         state: state.clone(),
     };
 
@@ -183,24 +218,57 @@ pub async fn run_server() -> miette::Result<()> {
         .add_service(reflection_server)
         .add_service(health_server)
         .add_service(InterceptorFor::new(
-            OsuAuthServiceServer::new(OsuAuthServiceImpl(
-                state.clone(),
-                osu::auth::get_auth_client(),
-            )),
-            CorsInterceptor,
+            InterceptorFor::new(
+                OsuAuthServiceServer::new(OsuAuthServiceImpl(
+                    state.clone(),
+                    osu::auth::get_auth_client(),
+                )),
+                CorsInterceptor,
+            ),
+            auth_interceptor.clone(),
+        ))
+        // The debug service writes generated data without any permission checks, so it is only
+        // served by debug builds
+        .add_optional_service(
+            cfg!(debug_assertions)
+                .then(|| DebugServiceServer::new(DebugServiceImpl(state.clone()))),
+        )
+        .add_service(InterceptorFor::new(
+            TournamentServiceServer::new(TournamentServiceImpl(state.clone())),
+            auth_interceptor.clone(),
+        ))
+        .add_service(InterceptorFor::new(
+            StageServiceServer::new(StageServiceImpl(state.clone())),
+            auth_interceptor.clone(),
+        ))
+        .add_service(InterceptorFor::new(
+            PoolServiceServer::new(PoolServiceImpl(state.clone())),
+            auth_interceptor.clone(),
+        ))
+        .add_service(InterceptorFor::new(
+            OsuUserServiceServer::new(OsuUserServiceImpl(state.clone())),
+            auth_interceptor.clone(),
+        ))
+        .add_service(InterceptorFor::new(
+            ScoreServiceServer::new(ScoreServiceImpl(state.clone())),
+            auth_interceptor.clone(),
+        ))
+        .add_service(InterceptorFor::new(
+            TeamServiceServer::new(TeamServiceImpl(state.clone())),
+            auth_interceptor.clone(),
+        ))
+        .add_service(InterceptorFor::new(
+            MatchServiceServer::new(MatchServiceImpl(state.clone())),
+            auth_interceptor.clone(),
+        ))
+        .add_service(InterceptorFor::new(
+            StatsServiceServer::new(StatsServiceImpl(state.clone())),
+            auth_interceptor.clone(),
+        ))
+        .add_service(InterceptorFor::new(
+            RatingServiceServer::new(RatingServiceImpl(state.clone())),
+            auth_interceptor,
         ))
-        .add_service(DebugServiceServer::new(DebugServiceImpl(state.clone())))
-        .add_service(TournamentServiceServer::new(TournamentServiceImpl(
-            state.clone(),
-        )))
-        .add_service(StageServiceServer::new(StageServiceImpl(state.clone())))
-        .add_service(PoolServiceServer::new(PoolServiceImpl(state.clone())))
-        .add_service(OsuUserServiceServer::new(OsuUserServiceImpl(state.clone())))
-        .add_service(ScoreServiceServer::new(ScoreServiceImpl(state.clone())))
-        // .add_service(InterceptorFor::new(
-        //     OsuUserServiceServer::new(OsuUserServiceImpl(state.clone())),
-        //     auth_interceptor.clone(),
-        // ))
         .serve(addr)
         .await
         .into_diagnostic()
@@ -244,31 +312,34 @@ struct AuthInterceptor {
     state: AppState,
 }
 
+/// Attaches the [`Session`] of the logged in user to the request's extensions.
+/// Requests without a session token or with an expired or unknown one are passed on without a
+/// session, so that everyone can read data. Whether a user may change something is checked by the
+/// handlers, see [`permission`].
 #[tonic::async_trait]
 impl RequestInterceptor for AuthInterceptor {
     #[tracing::instrument(skip(self), rename = "authorize", level = "trace")]
-    async fn intercept(&self, req: tonic::codegen::http::Request<BoxBody>) -> Result<http::Request<BoxBody>, Status> {
-        let auth_header_token = match req.headers().get("authorization") {
-            Some(c) => c
-                .to_str()
-                .map_err(|_| Status::unauthenticated("non-unicode session token"))?,
-            _ => {
-                return Err(Status::unauthenticated("authorization cookie not set"));
-            }
+    async fn intercept(&self, mut req: tonic::codegen::http::Request<BoxBody>) -> Result<http::Request<BoxBody>, Status> {
+        let Some(auth_header_token) = req.headers().get("authorization") else {
+            return Ok(req);
         };
+        let auth_header_token = auth_header_token
+            .to_str()
+            .map_err(|_| Status::unauthenticated("non-unicode session token"))?;
 
-        if !auth_header_token.starts_with("Bearer ") {
+        let Some(auth_header_token) = auth_header_token.strip_prefix("Bearer ") else {
             return Err(Status::unauthenticated("invalid session token")).warn_status();
-        }
-        let auth_header_token = &auth_header_token[7..];
+        };
 
-        let Some(_) = Session::get_cached(auth_header_token, &self.state.redis)
+        let Some(session) = Session::get_cached(auth_header_token, &self.state.cache)
             .await
             .map_err(|_| Status::internal("error reading session token"))?
         else {
-            return Err(Status::unauthenticated("expired or unknown session token"));
+            tracing::debug!("expired or unknown session token");
+            return Ok(req);
         };
 
+        req.extensions_mut().insert(session);
         Ok(req)
     }
 }
@@ -326,26 +397,42 @@ async fn setup_database() -> miette::Result<(DatabaseConnection, PgPool)> {
     Ok((db, pool))
 }
 
+/// Connects to redis if `REDIS_URL` is set. Otherwise, values are cached in memory, which only
+/// works if a single instance of the backend is running.
 #[tracing::instrument]
-async fn setup_redis() -> miette::Result<deadpool_redis::Pool> {
-    let redis_url = std::env::var(REDIS_URL)
-        .into_diagnostic()
-        .wrap_err("REDIS_URL not set")?;
+async fn setup_cache() -> miette::Result<Cache> {
+    let Ok(redis_url) = std::env::var(REDIS_URL) else {
+        warn!("REDIS_URL not set. caching in memory, which only works for a single instance");
+        return Ok(Arc::new(MemoryBackend::new()));
+    };
     info!("connecting to redis");
 
-    let cfg = Config::from_url(redis_url);
+    let cfg = Config::from_url(redis_url.clone());
     let pool = cfg
         .create_pool(Some(deadpool_redis::Runtime::Tokio1))
         .into_diagnostic()
         .wrap_err("could not create redis connection pool")?;
 
+    // Keep the in-process caches consistent with the other instances
+    tokio::spawn(utils::cache::local::listen_for_invalidations(redis_url));
+
     info!("connection to redis successful");
 
-    Ok(pool)
+    Ok(Arc::new(RedisBackend::new(pool)))
 }
 
+/// Connects to the osu API.
+/// If `OSU_FIXTURES_DIR` is set, the fixtures in that directory are served instead, so that no
+/// network access or osu API credentials are needed.
 #[tracing::instrument]
-async fn setup_osu() -> miette::Result<Arc<Osu>> {
+async fn setup_osu() -> miette::Result<Arc<dyn OsuApi>> {
+    if let Ok(fixtures_dir) = std::env::var(OSU_FIXTURES_DIR) {
+        warn!("serving osu api responses from fixtures in {fixtures_dir}");
+        let fixtures = osu::fixtures::FixtureOsuApi::load(fixtures_dir)
+            .wrap_err("could not load osu api fixtures")?;
+        return Ok(Arc::new(fixtures));
+    }
+
     let osu_client_id = std::env::var(OSU_CLIENT_ID)
         .into_diagnostic()
         .wrap_err("OSU_CLIENT_ID not set")?
@@ -356,13 +443,24 @@ async fn setup_osu() -> miette::Result<Arc<Osu>> {
     let osu_client_secret = std::env::var(OSU_CLIENT_SECRET)
         .into_diagnostic()
         .wrap_err("OSU_CLIENT_SECRET not set")?;
+
+    // The osu API asks for at most 60 requests per minute
+    let requests_per_minute = parse_env(OSU_REQUESTS_PER_MINUTE, || 60u32)?;
+    let burst = parse_env(OSU_REQUEST_BURST, || 10u32)?;
+    if requests_per_minute == 0 || burst == 0 {
+        return Err(miette!(
+            "{OSU_REQUESTS_PER_MINUTE} and {OSU_REQUEST_BURST} must be positive"
+        ));
+    }
+
     info!("connecting to osu api...");
-    let osu = Arc::new(
-        Osu::new(osu_client_id, osu_client_secret)
-            .await
-            .into_diagnostic()
-            .wrap_err("error connecting to osu api")?,
-    );
+    let osu = Osu::new(osu_client_id, osu_client_secret)
+        .await
+        .into_diagnostic()
+        .wrap_err("error connecting to osu api")?;
     info!("connection to osu api successful");
-    Ok(osu)
+    Ok(Arc::new(osu::limiter::RateLimitedOsuApi::new(
+        Arc::new(osu),
+        osu::limiter::RateLimiter::new(requests_per_minute, burst),
+    )))
 }
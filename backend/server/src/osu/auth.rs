@@ -6,12 +6,16 @@ use oauth2::{
 };
 use rand::{RngCore, SeedableRng};
 use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::Utc;
 use url::Url;
 
-use utils::{cache::CacheResult, crypt::EncryptedToken, Cacheable};
-
-use crate::RedisConnectionPool;
+use utils::{
+    cache::{Cache, CacheResult},
+    crypt::EncryptedToken,
+    Cacheable,
+};
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct OsuRefreshToken {
     pub user_id: u32,
@@ -30,6 +34,7 @@ impl Cacheable for OsuRefreshToken {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct OsuAccessToken {
     pub user_id: u32,
@@ -66,14 +71,14 @@ impl Deref for OsuCsrfToken {
 }
 
 impl OsuAuthCode {
-    pub async fn request(client: &BasicClient, redis: &RedisConnectionPool) -> CacheResult<Url> {
+    pub async fn request(client: &BasicClient, cache: &Cache) -> CacheResult<Url> {
         let (auth_url, csrf_token) = client
             .authorize_url(CsrfToken::new_random)
             .add_scope(Scope::new("public".into()))
             .add_scope(Scope::new("identify".into()))
             .url();
 
-        OsuCsrfToken(csrf_token).cache(redis, Some(300)).await?;
+        OsuCsrfToken(csrf_token).cache(cache, Some(300)).await?;
 
         Ok(auth_url)
     }
@@ -104,9 +109,10 @@ impl Cacheable for OsuCsrfToken {
 }
 
 pub fn get_auth_client() -> BasicClient {
-    // We know these exist and are valid. Otherwise, this app wouldn't be running
-    let client_id = std::env::var(crate::OSU_CLIENT_ID).unwrap();
-    let client_secret = std::env::var(crate::OSU_CLIENT_SECRET).unwrap();
+    // These exist unless the osu API is served from fixtures, in which case logging in is not
+    // possible anyway
+    let client_id = std::env::var(crate::OSU_CLIENT_ID).unwrap_or_default();
+    let client_secret = std::env::var(crate::OSU_CLIENT_SECRET).unwrap_or_default();
     BasicClient::new(
         ClientId::new(client_id),
         Some(ClientSecret::new(client_secret)),
@@ -118,10 +124,25 @@ pub fn get_auth_client() -> BasicClient {
     .set_auth_type(oauth2::AuthType::RequestBody)
 }
 
-#[derive(Serialize, Deserialize)]
+/// The number of seconds a session lasts unless it is refreshed
+pub const SESSION_LIFETIME: usize = 600;
+/// The number of seconds the osu api tokens of a user are kept.
+/// This needs to outlive the access token, so the refresh token can be used to get a new one.
+pub const API_TOKENS_LIFETIME: usize = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     pub session_id: String,
     pub osu_user_id: u32,
+    /// An id for this session which, unlike the session id, can be shown to the user
+    #[serde(default)]
+    pub public_id: String,
+    /// When this session was created in milliseconds since epoch
+    #[serde(default)]
+    pub created_at: i64,
+    /// When this session expires in milliseconds since epoch
+    #[serde(default)]
+    pub expires_at: i64,
 }
 
 impl Cacheable for Session {
@@ -138,21 +159,80 @@ impl Cacheable for Session {
 
 impl Session {
     pub fn new(osu_user_id: u32) -> Self {
+        let now = Utc::now().timestamp_millis();
         Self {
-            session_id: Self::generate_session_id(),
+            session_id: Self::generate_id(16),
             osu_user_id,
+            public_id: Self::generate_id(6),
+            created_at: now,
+            expires_at: now + SESSION_LIFETIME as i64 * 1000,
         }
     }
 
-    fn generate_session_id() -> String {
+    fn generate_id(bytes: usize) -> String {
         let mut rng = rand_chacha::ChaCha20Rng::from_entropy();
-        let mut buf = [0u8; 16];
+        let mut buf = vec![0u8; bytes];
         rng.fill_bytes(&mut buf);
         BASE64_STANDARD.encode(buf)
     }
+
+    /// The cache key of the hash that maps the public ids of a user's sessions to their session ids
+    fn user_sessions_key(osu_user_id: u32) -> String {
+        format!("usersessions:{osu_user_id}")
+    }
+
+    /// Stores this session for [`SESSION_LIFETIME`] seconds and adds it to the user's sessions
+    pub async fn store(&self, cache: &Cache) -> CacheResult<()> {
+        self.cache(cache, Some(SESSION_LIFETIME)).await?;
+        cache
+            .hash_set(
+                &Self::user_sessions_key(self.osu_user_id),
+                &self.public_id,
+                &self.session_id,
+            )
+            .await
+    }
+
+    /// Extends this session by another [`SESSION_LIFETIME`] seconds
+    pub async fn refresh(&mut self, cache: &Cache) -> CacheResult<()> {
+        self.expires_at = Utc::now().timestamp_millis() + SESSION_LIFETIME as i64 * 1000;
+        self.store(cache).await
+    }
+
+    /// Ends this session
+    pub async fn end(&self, cache: &Cache) -> CacheResult<()> {
+        Self::uncache(cache, &self.session_id).await?;
+        cache
+            .hash_delete(
+                &Self::user_sessions_key(self.osu_user_id),
+                std::slice::from_ref(&self.public_id),
+            )
+            .await
+    }
+
+    /// Gets all active sessions of a user ordered by their creation.
+    /// Sessions that have expired are removed from the user's sessions.
+    pub async fn all_of_user(osu_user_id: u32, cache: &Cache) -> CacheResult<Vec<Session>> {
+        let key = Self::user_sessions_key(osu_user_id);
+        let session_ids = cache.hash_get_all(&key).await?;
+
+        let mut sessions = Vec::with_capacity(session_ids.len());
+        let mut expired = vec![];
+        for (public_id, session_id) in session_ids {
+            match Self::get_cached(&session_id, cache).await? {
+                Some(session) => sessions.push(session),
+                None => expired.push(public_id),
+            }
+        }
+
+        cache.hash_delete(&key, &expired).await?;
+
+        sessions.sort_by_key(|session| session.created_at);
+        Ok(sessions)
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct OsuApiTokens {
     pub user_id: u32,
     pub access_token: EncryptedToken,
@@ -0,0 +1,191 @@
+//! An implementation of the osu API that serves responses from JSON files instead of requesting
+//! them, so that the server can be run without network access or osu API credentials.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use miette::{Context, IntoDiagnostic};
+use proto::osu::api::OsuApi;
+use rosu_v2::{
+    error::OsuError,
+    model::{
+        beatmap::BeatmapsetExtended,
+        matches::OsuMatch,
+        user::{User, UserExtended},
+        GameMode,
+    },
+    OsuResult,
+};
+use serde::de::DeserializeOwned;
+use tracing::info;
+
+/// An osu API serving beatmapsets, users and multiplayer lobbies loaded from fixture files.
+///
+/// Requests for anything that has no fixture fail with [`OsuError::NotFound`], just like requests
+/// for something that doesn't exist would with the actual osu API.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureOsuApi {
+    /// The beatmapsets, indexed by the ids of each of their maps
+    beatmapsets: HashMap<u32, BeatmapsetExtended>,
+    /// The users, indexed by their user id
+    users: HashMap<u32, UserExtended>,
+    /// The multiplayer lobbies, indexed by their match id
+    matches: HashMap<u32, OsuMatch>,
+}
+
+impl FixtureOsuApi {
+    /// Loads the fixtures in a directory.
+    ///
+    /// The directory may contain the subdirectories `beatmapsets`, `users` and `matches`. Each of
+    /// them contains one JSON file per beatmapset, user or lobby in the format the osu API responds
+    /// with. Beatmapsets need to include their maps. Subdirectories that don't exist are treated
+    /// as empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file can't be read or doesn't contain a valid response.
+    pub fn load(dir: impl AsRef<Path>) -> miette::Result<Self> {
+        let dir = dir.as_ref();
+        let mut fixtures = Self::default();
+
+        for mapset in load_all::<BeatmapsetExtended>(&dir.join("beatmapsets"))? {
+            for map in mapset.maps.iter().flatten() {
+                fixtures.beatmapsets.insert(map.map_id, mapset.clone());
+            }
+        }
+        for user in load_all::<UserExtended>(&dir.join("users"))? {
+            fixtures.users.insert(user.user_id, user);
+        }
+        for lobby in load_all::<OsuMatch>(&dir.join("matches"))? {
+            fixtures.matches.insert(lobby.match_id, lobby);
+        }
+
+        info!(
+            maps = fixtures.beatmapsets.len(),
+            users = fixtures.users.len(),
+            matches = fixtures.matches.len(),
+            "loaded osu api fixtures from {dir:?}"
+        );
+
+        Ok(fixtures)
+    }
+}
+
+/// Deserializes every JSON file in a directory
+fn load_all<T: DeserializeOwned>(dir: &Path) -> miette::Result<Vec<T>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut paths = fs::read_dir(dir)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("could not read fixture directory {dir:?}"))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()
+        .into_diagnostic()
+        .wrap_err_with(|| format!("could not read fixture directory {dir:?}"))?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let content = fs::read_to_string(path)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("could not read fixture {path:?}"))?;
+            serde_json::from_str(&content)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("invalid fixture {path:?}"))
+        })
+        .collect()
+}
+
+#[tonic::async_trait]
+impl OsuApi for FixtureOsuApi {
+    async fn beatmapset_from_map_id(&self, map_id: u32) -> OsuResult<BeatmapsetExtended> {
+        self.beatmapsets
+            .get(&map_id)
+            .cloned()
+            .ok_or(OsuError::NotFound)
+    }
+
+    async fn user(&self, user_id: u32, mode: Option<GameMode>) -> OsuResult<UserExtended> {
+        let mut user = self
+            .users
+            .get(&user_id)
+            .cloned()
+            .ok_or(OsuError::NotFound)?;
+        // There is only one fixture per user, so it is used for every mode
+        if let Some(mode) = mode {
+            user.mode = mode;
+        }
+        Ok(user)
+    }
+
+    async fn osu_match(&self, match_id: u32, before_event_id: Option<u64>) -> OsuResult<OsuMatch> {
+        let mut lobby = self
+            .matches
+            .get(&match_id)
+            .cloned()
+            .ok_or(OsuError::NotFound)?;
+        // Fixtures contain all events of a lobby, so they are served in a single page unless
+        // earlier events are requested
+        if let Some(event_id) = before_event_id {
+            lobby.events.retain(|event| event.event_id() < event_id);
+        }
+        Ok(lobby)
+    }
+
+    async fn users(&self, user_ids: &[u32]) -> OsuResult<Vec<User>> {
+        Ok(user_ids
+            .iter()
+            .filter_map(|id| self.users.get(id).cloned().map(User::from))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixtures() -> FixtureOsuApi {
+        FixtureOsuApi::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/osu")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_fixtures() {
+        let osu = fixtures();
+
+        let mapset = osu.beatmapset_from_map_id(2230996).await.unwrap();
+        assert!(mapset.maps.unwrap().iter().any(|map| map.map_id == 2230996));
+
+        let user = osu.user(2, Some(GameMode::Taiko)).await.unwrap();
+        assert_eq!(user.username.as_str(), "peppy");
+        assert_eq!(user.mode, GameMode::Taiko);
+
+        let lobby = osu.osu_match(111534249, None).await.unwrap();
+        assert_eq!(lobby.games().count(), 1);
+
+        let users = osu.users(&[2, 1]).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].user_id, 2);
+    }
+
+    #[tokio::test]
+    async fn missing_fixtures_are_not_found() {
+        let osu = fixtures();
+
+        assert!(matches!(
+            osu.beatmapset_from_map_id(1).await,
+            Err(OsuError::NotFound)
+        ));
+        assert!(matches!(osu.user(1, None).await, Err(OsuError::NotFound)));
+        assert!(matches!(
+            osu.osu_match(1, None).await,
+            Err(OsuError::NotFound)
+        ));
+    }
+}
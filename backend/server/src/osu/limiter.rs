@@ -0,0 +1,406 @@
+//! A wrapper around the osu API that limits how fast requests are sent and merges concurrent
+//! requests for the same resource into a single request.
+
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::{
+    future::{join_all, BoxFuture, Shared},
+    FutureExt,
+};
+use proto::osu::api::OsuApi;
+use rosu_v2::{
+    error::{ApiError, OsuError},
+    model::{
+        beatmap::BeatmapsetExtended,
+        matches::OsuMatch,
+        user::{User, UserExtended},
+        GameMode,
+    },
+    OsuResult,
+};
+
+/// A token bucket which allows a number of requests per minute with bursts of up to a number of
+/// requests.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// The number of tokens added to the bucket per second
+    rate: f64,
+    /// The maximum number of tokens in the bucket
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// The number of available tokens. This is negative if requests are waiting for tokens.
+    tokens: f64,
+    /// When tokens were last added to the bucket
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter with a full bucket.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests_per_minute` or `burst` is zero.
+    pub fn new(requests_per_minute: u32, burst: u32) -> Self {
+        assert!(
+            requests_per_minute > 0,
+            "requests per minute must be positive"
+        );
+        assert!(burst > 0, "burst must be positive");
+        Self {
+            rate: f64::from(requests_per_minute) / 60.,
+            burst: f64::from(burst),
+            bucket: Mutex::new(Bucket {
+                tokens: f64::from(burst),
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a request may be sent.
+    /// Tokens are reserved in the order this is called, so no request waits forever.
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+            bucket.refilled_at = now;
+            bucket.tokens -= 1.;
+            (bucket.tokens < 0.).then(|| Duration::from_secs_f64(-bucket.tokens / self.rate))
+        };
+
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// A request that may be awaited by several callers at once
+type SharedCall<V> = Shared<BoxFuture<'static, Result<V, Arc<OsuError>>>>;
+
+/// The requests which are currently in flight, indexed by what they request
+struct InFlight<K, V> {
+    calls: Mutex<HashMap<K, SharedCall<V>>>,
+}
+
+impl<K, V> Default for InFlight<K, V> {
+    fn default() -> Self {
+        Self {
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K, V> InFlight<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Awaits the request for the given key if one is in flight. Otherwise, sends the request
+    /// created by `call` and lets later callers with the same key await it.
+    async fn run<F>(&self, key: K, call: impl FnOnce() -> F) -> OsuResult<V>
+    where
+        F: Future<Output = OsuResult<V>> + Send + 'static,
+    {
+        let shared = self
+            .calls
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                call()
+                    .map(|result| result.map_err(Arc::new))
+                    .boxed()
+                    .shared()
+            })
+            .clone();
+
+        let result = shared.clone().await;
+
+        // The request is done, so the next caller needs to send a new one
+        let mut calls = self.calls.lock().unwrap();
+        if calls.get(&key).is_some_and(|call| call.ptr_eq(&shared)) {
+            calls.remove(&key);
+        }
+        drop(calls);
+
+        result.map_err(unshare_error)
+    }
+}
+
+impl<K, V> InFlight<K, Option<V>>
+where
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Awaits the requests in flight for the given keys, and sends a single request created by
+    /// `call` for all keys without one. Later callers with any of these keys await that request.
+    /// Returns the value for each key, or `None` if the request didn't return one for it.
+    async fn run_many<F>(
+        &self,
+        keys: &[K],
+        call: impl FnOnce(Vec<K>) -> F,
+    ) -> OsuResult<Vec<Option<V>>>
+    where
+        F: Future<Output = OsuResult<HashMap<K, V>>> + Send + 'static,
+    {
+        let shared = {
+            let mut calls = self.calls.lock().unwrap();
+            let mut seen = HashSet::new();
+            let missing = keys
+                .iter()
+                .filter(|&key| !calls.contains_key(key) && seen.insert(key))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if !missing.is_empty() {
+                let batch = call(missing.clone())
+                    .map(|result| result.map_err(Arc::new))
+                    .boxed()
+                    .shared();
+                for key in missing {
+                    let (batch, value_key) = (batch.clone(), key.clone());
+                    let value =
+                        async move { batch.await.map(|values| values.get(&value_key).cloned()) };
+                    calls.insert(key, value.boxed().shared());
+                }
+            }
+            keys.iter()
+                .map(|key| calls[key].clone())
+                .collect::<Vec<_>>()
+        };
+
+        let results = join_all(shared.iter().cloned()).await;
+
+        // The requests are done, so the next callers need to send new ones
+        let mut calls = self.calls.lock().unwrap();
+        for (key, shared) in keys.iter().zip(&shared) {
+            if calls.get(key).is_some_and(|call| call.ptr_eq(shared)) {
+                calls.remove(key);
+            }
+        }
+        drop(calls);
+
+        results
+            .into_iter()
+            .map(|result| result.map_err(unshare_error))
+            .collect()
+    }
+}
+
+/// Returns the error of a request shared with other callers, copying it if it is still shared
+fn unshare_error(error: Arc<OsuError>) -> OsuError {
+    Arc::try_unwrap(error).unwrap_or_else(|error| copy_error(&error))
+}
+
+/// Creates an error equivalent to one returned by a request shared with other callers.
+/// [`OsuError`] can't be cloned, so errors without a meaningful copy are reported as the osu API
+/// being unavailable with the original error's message.
+fn copy_error(error: &OsuError) -> OsuError {
+    match error {
+        OsuError::NotFound => OsuError::NotFound,
+        OsuError::RequestTimeout => OsuError::RequestTimeout,
+        OsuError::ServiceUnavailable(message) => OsuError::ServiceUnavailable(message.clone()),
+        OsuError::Response {
+            body,
+            source,
+            status,
+        } => OsuError::Response {
+            body: body.clone(),
+            source: ApiError {
+                error: source.error.clone(),
+            },
+            status: *status,
+        },
+        error => OsuError::ServiceUnavailable(error.to_string()),
+    }
+}
+
+/// An osu API which sends its requests to another osu API while respecting a [`RateLimiter`].
+/// Requests for the same beatmap, user or page of a lobby that are sent at the same time are
+/// merged. Requests for multiple users only request the users that aren't requested already.
+pub struct RateLimitedOsuApi {
+    inner: Arc<dyn OsuApi>,
+    limiter: Arc<RateLimiter>,
+    beatmapsets: InFlight<u32, BeatmapsetExtended>,
+    user: InFlight<(u32, Option<GameMode>), UserExtended>,
+    users: InFlight<u32, Option<User>>,
+    matches: InFlight<(u32, Option<u64>), OsuMatch>,
+}
+
+impl RateLimitedOsuApi {
+    pub fn new(inner: Arc<dyn OsuApi>, limiter: RateLimiter) -> Self {
+        Self {
+            inner,
+            limiter: Arc::new(limiter),
+            beatmapsets: InFlight::default(),
+            user: InFlight::default(),
+            users: InFlight::default(),
+            matches: InFlight::default(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl OsuApi for RateLimitedOsuApi {
+    async fn beatmapset_from_map_id(&self, map_id: u32) -> OsuResult<BeatmapsetExtended> {
+        let (inner, limiter) = (self.inner.clone(), self.limiter.clone());
+        self.beatmapsets
+            .run(map_id, || async move {
+                limiter.acquire().await;
+                inner.beatmapset_from_map_id(map_id).await
+            })
+            .await
+    }
+
+    async fn user(&self, user_id: u32, mode: Option<GameMode>) -> OsuResult<UserExtended> {
+        let (inner, limiter) = (self.inner.clone(), self.limiter.clone());
+        self.user
+            .run((user_id, mode), || async move {
+                limiter.acquire().await;
+                inner.user(user_id, mode).await
+            })
+            .await
+    }
+
+    async fn osu_match(&self, match_id: u32, before_event_id: Option<u64>) -> OsuResult<OsuMatch> {
+        let (inner, limiter) = (self.inner.clone(), self.limiter.clone());
+        self.matches
+            .run((match_id, before_event_id), || async move {
+                limiter.acquire().await;
+                inner.osu_match(match_id, before_event_id).await
+            })
+            .await
+    }
+
+    async fn users(&self, user_ids: &[u32]) -> OsuResult<Vec<User>> {
+        let mut user_ids = user_ids.to_vec();
+        user_ids.sort_unstable();
+        user_ids.dedup();
+
+        let (inner, limiter) = (self.inner.clone(), self.limiter.clone());
+        let users = self
+            .users
+            .run_many(&user_ids, |user_ids| async move {
+                limiter.acquire().await;
+                let users = inner.users(&user_ids).await?;
+                Ok(users.into_iter().map(|user| (user.user_id, user)).collect())
+            })
+            .await?;
+        Ok(users.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// An osu API that counts the requests it receives and takes a while to respond to them
+    #[derive(Default)]
+    struct CountingOsuApi {
+        requests: AtomicUsize,
+        /// The ids of each request for multiple users
+        user_batches: Mutex<Vec<Vec<u32>>>,
+    }
+
+    #[tonic::async_trait]
+    impl OsuApi for CountingOsuApi {
+        async fn beatmapset_from_map_id(&self, _: u32) -> OsuResult<BeatmapsetExtended> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err(OsuError::NotFound)
+        }
+
+        async fn user(&self, _: u32, _: Option<GameMode>) -> OsuResult<UserExtended> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err(OsuError::NotFound)
+        }
+
+        async fn osu_match(&self, _: u32, _: Option<u64>) -> OsuResult<OsuMatch> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err(OsuError::NotFound)
+        }
+
+        async fn users(&self, user_ids: &[u32]) -> OsuResult<Vec<User>> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            self.user_batches.lock().unwrap().push(user_ids.to_vec());
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn merges_concurrent_requests() {
+        let inner = Arc::new(CountingOsuApi::default());
+        let osu = RateLimitedOsuApi::new(inner.clone(), RateLimiter::new(600, 10));
+
+        let (first, second, other) = tokio::join!(
+            osu.beatmapset_from_map_id(1),
+            osu.beatmapset_from_map_id(1),
+            osu.beatmapset_from_map_id(2),
+        );
+        assert!(matches!(first, Err(OsuError::NotFound)));
+        assert!(matches!(second, Err(OsuError::NotFound)));
+        assert!(matches!(other, Err(OsuError::NotFound)));
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 2);
+
+        let (first, second) = tokio::join!(osu.users(&[2, 1]), osu.users(&[1, 2, 2]));
+        assert!(first.is_ok() && second.is_ok());
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 3);
+
+        // Requests after the merged ones are done are sent again
+        let _ = osu.user(1, None).await;
+        let _ = osu.user(1, None).await;
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 5);
+
+        // Each page of a lobby is a separate request
+        let (first, second, earlier) = tokio::join!(
+            osu.osu_match(1, None),
+            osu.osu_match(1, None),
+            osu.osu_match(1, Some(100)),
+        );
+        assert!(matches!(first, Err(OsuError::NotFound)));
+        assert!(matches!(second, Err(OsuError::NotFound)));
+        assert!(matches!(earlier, Err(OsuError::NotFound)));
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 7);
+    }
+
+    #[tokio::test]
+    async fn merges_requested_users_by_id() {
+        let inner = Arc::new(CountingOsuApi::default());
+        let osu = RateLimitedOsuApi::new(inner.clone(), RateLimiter::new(600, 10));
+
+        // Users that are already requested aren't requested again
+        let (first, second, third) =
+            tokio::join!(osu.users(&[1, 2]), osu.users(&[2, 3]), osu.users(&[3]));
+        assert!(first.is_ok() && second.is_ok() && third.is_ok());
+        assert_eq!(*inner.user_batches.lock().unwrap(), [vec![1, 2], vec![3]]);
+    }
+
+    #[tokio::test]
+    async fn limits_requests_after_burst() {
+        let limiter = RateLimiter::new(600, 2);
+        let start = Instant::now();
+
+        // The burst is available immediately, after that one request is allowed every 100ms
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+}
@@ -0,0 +1,63 @@
+use model::sea_orm_active_enums::{LobbyTeam, ScoringType};
+use rosu_v2::model::matches::{self, MatchGame, MatchScore, OsuMatch, Team};
+
+/// Returns the games of a lobby that were actually played, in the order they were played.
+/// This skips the first `warmups` games, as well as games that were aborted or in which nobody
+/// set a score.
+pub fn played_games(lobby: &OsuMatch, warmups: usize) -> impl Iterator<Item = &MatchGame> {
+    lobby
+        .games()
+        .skip(warmups)
+        .filter(|game| game.end_time.is_some() && game.scores.iter().any(|s| s.score > 0))
+}
+
+/// Returns the legacy bitflags of the mods a score was set with.
+/// Lobbies without free mod only list the mods on the game, while lobbies with free mod list the
+/// mods every player picked on their score, so both are combined.
+pub fn score_mods(game: &MatchGame, score: &MatchScore) -> u32 {
+    game.mods.bits() | score.mods.bits()
+}
+
+/// Returns how the lobby in which a game was played decided who won
+pub fn scoring_type(game: &MatchGame) -> ScoringType {
+    match game.scoring_type {
+        matches::ScoringType::Score => ScoringType::Score,
+        matches::ScoringType::Accuracy => ScoringType::Accuracy,
+        matches::ScoringType::Combo => ScoringType::Combo,
+        matches::ScoringType::ScoreV2 => ScoringType::ScoreV2,
+    }
+}
+
+/// Returns the team a player was on in the lobby when they set a score
+pub fn lobby_team(score: &MatchScore) -> LobbyTeam {
+    match score.team {
+        Team::None => LobbyTeam::None,
+        Team::Blue => LobbyTeam::Blue,
+        Team::Red => LobbyTeam::Red,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use proto::osu::api::OsuApi;
+
+    use super::*;
+    use crate::osu::fixtures::FixtureOsuApi;
+
+    #[tokio::test]
+    async fn reads_score_details() {
+        let osu = FixtureOsuApi::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/osu"))
+            .unwrap();
+        let lobby = osu.osu_match(111534249, None).await.unwrap();
+
+        let game = played_games(&lobby, 0).next().unwrap();
+        let score = &game.scores[0];
+        assert_eq!(scoring_type(game), ScoringType::ScoreV2);
+        assert_eq!(lobby_team(score), LobbyTeam::Blue);
+        assert_eq!(score_mods(game, score), 0);
+        assert_eq!(score.slot, 0);
+        assert!(score.pass);
+    }
+}
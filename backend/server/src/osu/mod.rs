@@ -2,3 +2,7 @@
 //! and also convenience methods to access the osu API with caching.
 
 pub mod auth;
+pub mod fixtures;
+pub mod limiter;
+pub mod lobby;
+pub mod profile;
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use model::sea_orm_active_enums::OsuMode;
+use proto::osu::api::OsuApi;
+use rosu_v2::{error::OsuError, prelude::GameMode};
+use serde::{Deserialize, Serialize};
+use utils::{
+    cache::{Cache, CachePolicy, CacheResult},
+    Cacheable,
+};
+
+/// How long profiles are cached. Stale profiles are served for up to a day while they are
+/// refreshed.
+const PROFILE_CACHE_POLICY: CachePolicy = CachePolicy {
+    soft_ttl: 600,
+    hard_ttl: 86400,
+    not_found_ttl: 600,
+};
+
+/// The parts of an osu user's profile in a specific mode that are relevant for registering them
+/// in a tournament
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerProfile {
+    /// The key in the cache, made up of the user id and the mode
+    key: String,
+    /// The user as shown in responses, with the 2-character country code of the user
+    pub user: proto::osu::User,
+    /// The user's global rank in the mode. This is `None` if the user is unranked.
+    pub global_rank: Option<u32>,
+    /// The number of badges on the user's profile
+    #[serde(default)]
+    pub badges: u32,
+}
+
+impl Cacheable for PlayerProfile {
+    type KeyType = str;
+
+    fn type_key() -> &'static str {
+        "osuprofile"
+    }
+
+    fn key(&self) -> &Self::KeyType {
+        &self.key
+    }
+}
+
+/// Converts the mode of a tournament into the mode used by the osu API
+pub fn game_mode(mode: OsuMode) -> GameMode {
+    match mode {
+        OsuMode::Osu => GameMode::Osu,
+        OsuMode::Taiko => GameMode::Taiko,
+        OsuMode::Catch => GameMode::Catch,
+        OsuMode::Mania => GameMode::Mania,
+    }
+}
+
+/// Gets the profile of an osu user in the given mode, or `None` if the user doesn't exist.
+/// Stale profiles are returned while they are refreshed in the background.
+///
+/// # Errors
+///
+/// This function will return an error if something goes wrong during cacheing or communicating
+/// with the osu api.
+pub async fn get_profile(
+    cache: &Cache,
+    osu: &Arc<dyn OsuApi>,
+    user_id: u32,
+    mode: GameMode,
+) -> CacheResult<Option<PlayerProfile>> {
+    let key = format!("{user_id}:{mode}");
+    let (osu, profile_key) = (osu.clone(), key.clone());
+    PlayerProfile::get_cached_or_revalidate::<OsuError, _>(
+        cache,
+        &key,
+        PROFILE_CACHE_POLICY,
+        move || async move {
+            let user = match osu.user(user_id, Some(mode)).await {
+                Ok(user) => user,
+                Err(OsuError::NotFound) => return Ok(None),
+                Err(error) => return Err(error),
+            };
+            Ok(Some(PlayerProfile {
+                key: profile_key,
+                global_rank: user.statistics.as_ref().and_then(|stats| stats.global_rank),
+                badges: user.badges.as_ref().map_or(0, |badges| badges.len() as u32),
+                user: user.into(),
+            }))
+        },
+    )
+    .await
+}
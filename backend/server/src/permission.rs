@@ -0,0 +1,112 @@
+//! Permissions of users in a tournament.
+//!
+//! Users are identified by the [`Session`] that the [`crate::AuthInterceptor`] attaches to a
+//! request. Every method that changes a tournament declares the [`Permission`] it needs, which
+//! is granted by the roles a user has in the tournament.
+
+use model::{sea_orm_active_enums::TournamentRole, tournament_staff};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use tonic::{Request, Status};
+use tracing::error;
+
+use crate::osu::auth::Session;
+
+/// The things a user can be allowed to do in a tournament
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Changing the tournament's settings and restrictions
+    EditTournament,
+    /// Deleting and purging the tournament
+    DeleteTournament,
+    /// Restoring the tournament after it was deleted
+    RestoreTournament,
+    /// Adding and removing staff roles other than hosts and admins
+    ManageStaff,
+    /// Adding and removing hosts and admins
+    ManageAdmins,
+    /// Creating, changing and deleting stages
+    EditStages,
+    /// Creating, changing and deleting mod brackets and the maps in them
+    EditPool,
+    /// Registering teams and changing their members
+    EditTeams,
+    /// Scheduling and deleting matches
+    ScheduleMatches,
+    /// Reporting the results of matches, i.e. their lobbies, scores and picks and bans
+    RefereeMatches,
+}
+
+impl Permission {
+    /// The roles that grant this permission
+    pub fn roles(self) -> &'static [TournamentRole] {
+        use TournamentRole as R;
+        match self {
+            Self::DeleteTournament | Self::ManageAdmins => &[R::Host],
+            Self::EditTournament
+            | Self::RestoreTournament
+            | Self::ManageStaff
+            | Self::EditStages
+            | Self::EditTeams
+            | Self::ScheduleMatches => &[R::Host, R::Admin],
+            Self::EditPool => &[R::Host, R::Admin, R::Pooler],
+            Self::RefereeMatches => &[R::Host, R::Admin, R::Referee],
+        }
+    }
+
+    /// A short description of what this permission allows, to be used in error messages
+    fn description(self) -> &'static str {
+        match self {
+            Self::EditTournament => "edit the tournament",
+            Self::DeleteTournament => "delete the tournament",
+            Self::RestoreTournament => "restore the tournament",
+            Self::ManageStaff => "manage staff",
+            Self::ManageAdmins => "manage hosts and admins",
+            Self::EditStages => "edit stages",
+            Self::EditPool => "edit the pool",
+            Self::EditTeams => "edit teams",
+            Self::ScheduleMatches => "schedule matches",
+            Self::RefereeMatches => "referee matches",
+        }
+    }
+}
+
+/// Returns the session of the user who sent the request, if they are logged in
+pub fn session<T>(request: &Request<T>) -> Option<&Session> {
+    request.extensions().get::<Session>()
+}
+
+/// Makes sure that the request was sent by a logged in user and returns their session.
+pub fn require_login<T>(request: &Request<T>) -> tonic::Result<&Session> {
+    session(request).ok_or_else(|| Status::unauthenticated("you need to be logged in"))
+}
+
+/// Makes sure that the user who sent the request has a role in the tournament that grants the
+/// given permission.
+/// Returns the osu user id of the user.
+pub async fn require_permission<T>(
+    request: &Request<T>,
+    tournament_id: i32,
+    permission: Permission,
+    db: &DatabaseConnection,
+) -> tonic::Result<u32> {
+    let session = require_login(request)?;
+
+    let role = tournament_staff::Entity::find()
+        .filter(tournament_staff::Column::TournamentId.eq(tournament_id))
+        .filter(tournament_staff::Column::UserId.eq(session.osu_user_id as i32))
+        .filter(tournament_staff::Column::Role.is_in(permission.roles().iter().copied()))
+        .one(db)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to get staff roles from database");
+            Status::internal("failed to check permissions")
+        })?;
+
+    match role {
+        Some(_) => Ok(session.osu_user_id),
+        None => Err(Status::permission_denied(format!(
+            "missing permission to {} in tournament {tournament_id}",
+            permission.description()
+        ))),
+    }
+}
@@ -0,0 +1,194 @@
+//! Checks whether players are allowed to register in a tournament
+
+use std::fmt::Display;
+
+use model::{country_restriction, rank_restriction, tournament};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use tonic::{Code, Status};
+use tonic_types::{ErrorDetails, PreconditionViolation, StatusExt};
+use tracing::error;
+
+use crate::osu::profile::PlayerProfile;
+
+/// The violation type of players whose rank is outside of every rank tier
+pub const RANK_VIOLATION: &str = "RANK";
+/// The violation type of players whose country is not allowed
+pub const COUNTRY_VIOLATION: &str = "COUNTRY";
+
+/// The rank and country restrictions of a tournament
+#[derive(Debug, Clone)]
+pub struct Restrictions {
+    /// The tournament the restrictions belong to
+    tournament: tournament::Model,
+    /// The inclusive rank range of each tier
+    rank_ranges: Vec<(u32, u32)>,
+    /// The allowed country codes
+    countries: Vec<String>,
+}
+
+/// A restriction that a player does not fulfill
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestrictionViolation {
+    /// The player's rank is not in any of the tournament's rank tiers.
+    /// For tournaments with BWS, the rank is the badge-weighted rank.
+    Rank {
+        user_id: u32,
+        rank: Option<u32>,
+        bws: bool,
+    },
+    /// The player's country is not one of the tournament's allowed countries
+    Country { user_id: u32, country_code: String },
+}
+
+impl Display for RestrictionViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rank {
+                user_id,
+                rank: Some(rank),
+                bws: false,
+            } => write!(f, "rank {rank} of user {user_id} is not in any rank tier"),
+            Self::Rank {
+                user_id,
+                rank: Some(rank),
+                bws: true,
+            } => write!(
+                f,
+                "BWS rank {rank} of user {user_id} is not in any rank tier"
+            ),
+            Self::Rank {
+                user_id,
+                rank: None,
+                ..
+            } => write!(f, "user {user_id} is unranked"),
+            Self::Country {
+                user_id,
+                country_code,
+            } => write!(f, "country {country_code} of user {user_id} is not allowed"),
+        }
+    }
+}
+
+impl RestrictionViolation {
+    fn to_precondition_violation(&self) -> PreconditionViolation {
+        let (violation_type, user_id) = match self {
+            Self::Rank { user_id, .. } => (RANK_VIOLATION, user_id),
+            Self::Country { user_id, .. } => (COUNTRY_VIOLATION, user_id),
+        };
+        PreconditionViolation::new(violation_type, format!("user:{user_id}"), self.to_string())
+    }
+}
+
+impl Restrictions {
+    /// Loads the restrictions of a tournament from the database
+    pub async fn load(
+        tournament: &tournament::Model,
+        db: &DatabaseConnection,
+    ) -> tonic::Result<Self> {
+        let tournament_id = tournament.id;
+        let rank_ranges = rank_restriction::Entity::find()
+            .filter(rank_restriction::Column::TournamentId.eq(tournament_id))
+            .all(db);
+        let countries = country_restriction::Entity::find()
+            .filter(country_restriction::Column::TournamentId.eq(tournament_id))
+            .all(db);
+        let (rank_ranges, countries) =
+            tokio::try_join!(rank_ranges, countries).map_err(|error| {
+                error!(%error, "failed to get restrictions from database");
+                Status::internal("failed to get tournament restrictions")
+            })?;
+
+        Ok(Self {
+            tournament: tournament.clone(),
+            rank_ranges: rank_ranges
+                .into_iter()
+                .map(|range| (range.min as u32, range.max as u32))
+                .collect(),
+            countries: countries
+                .into_iter()
+                .map(|country| country.country_code)
+                .collect(),
+        })
+    }
+
+    /// Returns the rank of a player that is checked against the rank restrictions.
+    /// This is the badge-weighted rank for tournaments with BWS and the global rank otherwise.
+    pub fn registration_rank(&self, profile: &PlayerProfile) -> Option<u32> {
+        profile
+            .global_rank
+            .map(|rank| self.tournament.seeding_rank(rank, profile.badges))
+    }
+
+    /// Checks whether a player fulfills the restrictions.
+    /// Without any rank tiers or countries, every rank or country respectively is allowed.
+    pub fn check(&self, profile: &PlayerProfile) -> Vec<RestrictionViolation> {
+        let mut violations = vec![];
+
+        if !self.rank_ranges.is_empty() {
+            let rank = self.registration_rank(profile);
+            let in_tier = rank.is_some_and(|rank| {
+                self.rank_ranges
+                    .iter()
+                    .any(|&(min, max)| (min..=max).contains(&rank))
+            });
+            if !in_tier {
+                violations.push(RestrictionViolation::Rank {
+                    user_id: profile.user.user_id,
+                    rank,
+                    bws: self.tournament.bws,
+                });
+            }
+        }
+
+        if !self.countries.is_empty()
+            && !self
+                .countries
+                .iter()
+                .any(|country| country.eq_ignore_ascii_case(&profile.user.country))
+        {
+            violations.push(RestrictionViolation::Country {
+                user_id: profile.user.user_id,
+                country_code: profile.user.country.clone(),
+            });
+        }
+
+        violations
+    }
+
+    /// Checks whether all players fulfill the restrictions.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `failed_precondition` status if any player does not fulfill the restrictions.
+    /// The status' details contain a `PreconditionFailure` with one violation per failed
+    /// restriction. The violation's type is either [`RANK_VIOLATION`] or [`COUNTRY_VIOLATION`] and
+    /// its subject is `user:<user id>`.
+    pub fn check_all<'a>(
+        &self,
+        profiles: impl IntoIterator<Item = &'a PlayerProfile>,
+    ) -> tonic::Result<()> {
+        let violations = profiles
+            .into_iter()
+            .flat_map(|profile| self.check(profile))
+            .collect::<Vec<_>>();
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let message = violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(Status::with_error_details(
+            Code::FailedPrecondition,
+            format!("tournament restrictions not met: {message}"),
+            ErrorDetails::with_precondition_failure(
+                violations
+                    .iter()
+                    .map(RestrictionViolation::to_precondition_violation)
+                    .collect::<Vec<_>>(),
+            ),
+        ))
+    }
+}
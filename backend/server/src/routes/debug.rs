@@ -3,7 +3,7 @@ use futures::future::FutureExt;
 use model::sea_orm_active_enums::MatchType;
 use model::sea_orm_active_enums::OsuMode;
 use rand::prelude::*;
-use sea_orm::{ActiveModelTrait, ActiveValue};
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection};
 use sqlx::types::chrono::NaiveDate;
 use sqlx::types::chrono::NaiveDateTime;
 use sqlx::types::chrono::NaiveTime;
@@ -34,10 +34,98 @@ static RANK_RANGES: [(i32, i32); 3] = [(100, 2500), (2500, 5000), (5000, 10000)]
 
 const FORMATS: [i16; 4] = [1, 2, 3, 4];
 
-const MAP_IDS: [usize; 9] = [
+const MAP_IDS: [u32; 9] = [
     3883456, 4192228, 4189337, 3917025, 4141288, 4186607, 3876751, 4130092, 4149939,
 ];
 
+// The teams of the osu! World Cup 2023 that are seeded, each with the ids of their players
+const OWC23_TEAMS: [(&str, &[i32]); 3] = [
+    (
+        "Germany",
+        &[
+            8116659, 4504101, 3765989, 14385814, 13300203, 12952320, 11921197,
+        ],
+    ),
+    (
+        "USA",
+        &[
+            7075211, 7813296, 4108547, 2590257, 4787150, 13380270, 3533958, 4830687,
+        ],
+    ),
+    (
+        "Spain",
+        &[
+            6995685, 12296128, 12975612, 9582556, 13962152, 6735738, 12760743, 6216284,
+        ],
+    ),
+];
+
+// The pools of the seeded stages. Each bracket is listed with the ids of its maps.
+const OWC23_QUALIFIER_POOL: [(&str, &[u32]); 4] = [
+    ("NM", &[4344435, 4344451, 4344441, 4344442]),
+    ("HD", &[4344469, 4344423]),
+    ("HR", &[4344412, 4344450]),
+    ("DT", &[4344474, 4344475, 4344422]),
+];
+const OWC23_RO32_POOL: [(&str, &[u32]); 6] = [
+    ("NM", &[4352819, 4352824, 4351786, 3332588]),
+    ("HD", &[4352411, 4352324]),
+    ("HR", &[1414172, 2020374]),
+    ("DT", &[4352790, 3840580, 2149694]),
+    ("FM", &[2583501, 4351866, 4352856]),
+    ("TB", &[3121101]),
+];
+const DM8_QUALIFIER_POOL: [(&str, &[u32]); 4] = [
+    ("NM", &[2230996, 3263098, 2593243, 3142496, 3129534]),
+    ("HD", &[3544219, 2588430]),
+    ("HR", &[2314568, 434438]),
+    ("DT", &[429797, 3153512]),
+];
+const DM8_RO64_POOL: [(&str, &[u32]); 5] = [
+    ("NM", &[3160790, 3650832, 2465287, 637391]),
+    ("HD", &[3830079, 1957037, 2134428]),
+    ("HR", &[1982100, 886269, 3167107]),
+    ("DT", &[2188430, 3457575, 3541087]),
+    ("TB", &[1233051, 1721284, 1295837]),
+];
+
+/// The lobby in which Germany played Spain in the round of 32 of the osu! World Cup 2023
+const OWC23_RO32_LOBBY: i32 = 111087337;
+
+/// Inserts the brackets of a stage's pool along with their maps
+async fn insert_pool(
+    db: &DatabaseConnection,
+    tournament_id: i32,
+    stage_order: i16,
+    pool: &[(&str, &[u32])],
+) {
+    use ActiveValue as A;
+    for (bracket_order, &(bracket_name, map_ids)) in pool.iter().enumerate() {
+        pool_bracket::ActiveModel {
+            bracket_order: A::Set(bracket_order as i16),
+            name: A::Set(bracket_name.to_owned()),
+            tournament_id: A::Set(tournament_id),
+            stage_order: A::Set(stage_order),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+
+        join_all(map_ids.iter().enumerate().map(|(map_order, &map_id)| {
+            pool_map::ActiveModel {
+                tournament_id: A::Set(tournament_id),
+                stage_order: A::Set(stage_order),
+                bracket_order: A::Set(bracket_order as i16),
+                map_id: A::Set(map_id as i64),
+                map_order: A::Set(map_order as i16),
+            }
+            .insert(db)
+            .map(Result::unwrap)
+        }))
+        .await;
+    }
+}
+
 pub struct DebugServiceImpl(pub AppState);
 
 #[tonic::async_trait]
@@ -79,6 +167,9 @@ impl DebugService for DebugServiceImpl {
             banner: A::NotSet,
             start_date: A::Set(None),
             end_date: A::Set(None),
+            bws_base: A::NotSet,
+            bws_exponent: A::NotSet,
+            deleted_at: A::NotSet,
         };
 
         let tournament = tournament.insert(db).await.unwrap();
@@ -169,91 +260,38 @@ impl DebugService for DebugServiceImpl {
             banner: A::Set(None),
             start_date: A::Set(None),
             end_date: A::Set(None),
+            bws_base: A::NotSet,
+            bws_exponent: A::NotSet,
+            deleted_at: A::NotSet,
         }
         .insert(db)
         .await
         .unwrap();
 
-        let add_team_member = |id, player_id| async move {
-            team_member::ActiveModel {
-                team_id: A::Set(id),
-                user_id: A::Set(player_id),
+        let mut teams = vec![];
+        for (name, player_ids) in OWC23_TEAMS {
+            let team = team::ActiveModel {
+                id: A::NotSet,
+                tournament_id: A::Set(owc23.id),
+                name: A::Set(name.to_owned()),
             }
             .insert(db)
             .await
             .unwrap();
-        };
-
-        let team_germany = team::ActiveModel {
-            id: A::NotSet,
-            tournament_id: A::Set(owc23.id),
-            name: A::Set("Germany".to_string()),
-        }
-        .insert(db)
-        .await
-        .unwrap();
-
-        add_team_member(team_germany.id, 8116659).await;
-        add_team_member(team_germany.id, 4504101).await;
-        add_team_member(team_germany.id, 3765989).await;
-        add_team_member(team_germany.id, 14385814).await;
-        add_team_member(team_germany.id, 13300203).await;
-        add_team_member(team_germany.id, 12952320).await;
-        add_team_member(team_germany.id, 11921197).await;
-
-        let team_usa = team::ActiveModel {
-            id: A::NotSet,
-            tournament_id: A::Set(owc23.id),
-            name: A::Set("USA".to_string()),
-        }
-        .insert(db)
-        .await
-        .unwrap();
 
-        add_team_member(team_usa.id, 7075211).await;
-        add_team_member(team_usa.id, 7813296).await;
-        add_team_member(team_usa.id, 4108547).await;
-        add_team_member(team_usa.id, 2590257).await;
-        add_team_member(team_usa.id, 4787150).await;
-        add_team_member(team_usa.id, 13380270).await;
-        add_team_member(team_usa.id, 3533958).await;
-        add_team_member(team_usa.id, 4830687).await;
-
-        let team_spain = team::ActiveModel {
-            id: A::NotSet,
-            tournament_id: A::Set(owc23.id),
-            name: A::Set("Spain".to_string()),
-        }
-        .insert(db)
-        .await
-        .unwrap();
-
-        add_team_member(team_spain.id, 6995685).await;
-        add_team_member(team_spain.id, 12296128).await;
-        add_team_member(team_spain.id, 12975612).await;
-        add_team_member(team_spain.id, 9582556).await;
-        add_team_member(team_spain.id, 13962152).await;
-        add_team_member(team_spain.id, 6735738).await;
-        add_team_member(team_spain.id, 12760743).await;
-        add_team_member(team_spain.id, 6216284).await;
-
-        macro_rules! pool {
-            {$pool:ident, $bracket:ident => $($maps:literal),+} => {
-                [$($maps),+].iter().copied().enumerate().map(|(i, map_id)| {
-                    pool_map::ActiveModel {
-                        tournament_id: A::Set(owc23.id),
-                        stage_order: A::Set($pool.stage_order),
-                        bracket_order: A::Set($bracket.bracket_order),
-                        map_id: A::Set(map_id as i64),
-                        map_order: A::Set(i as i16),
-                    }.insert(db).map(Result::unwrap)
-                })
-            };
-            {$pool:ident, $bracket:ident => $($maps:literal),+; $($other_brackets:ident => $($other_maps:literal),+);+} => {
-                pool!($pool, $($other_brackets => $($other_maps),+);+).chain(
-                pool!($pool, $bracket => $($maps),+))
-            };
+            for &player_id in player_ids {
+                team_member::ActiveModel {
+                    team_id: A::Set(team.id),
+                    user_id: A::Set(player_id),
+                    registered_rank: A::Set(None),
+                }
+                .insert(db)
+                .await
+                .unwrap();
+            }
+            teams.push(team);
         }
+        let (team_germany, team_spain) = (&teams[0], &teams[2]);
 
         {
             let qualis = stage::ActiveModel {
@@ -268,50 +306,7 @@ impl DebugService for DebugServiceImpl {
             .await
             .unwrap();
 
-            let nm = pool_bracket::ActiveModel {
-                bracket_order: A::Set(0),
-                name: A::Set("NM".to_owned()),
-                tournament_id: A::Set(owc23.id),
-                stage_order: A::Set(qualis.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let hd = pool_bracket::ActiveModel {
-                bracket_order: A::Set(1),
-                name: A::Set("HD".to_owned()),
-                tournament_id: A::Set(owc23.id),
-                stage_order: A::Set(qualis.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let hr = pool_bracket::ActiveModel {
-                bracket_order: A::Set(2),
-                name: A::Set("HR".to_owned()),
-                tournament_id: A::Set(owc23.id),
-                stage_order: A::Set(qualis.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let dt = pool_bracket::ActiveModel {
-                bracket_order: A::Set(3),
-                name: A::Set("DT".to_owned()),
-                tournament_id: A::Set(owc23.id),
-                stage_order: A::Set(qualis.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-
-            let _res = join_all(pool! { qualis,
-                nm => 4344435, 4344451, 4344441, 4344442;
-                hd => 4344469, 4344423;
-                hr => 4344412, 4344450;
-                dt => 4344474, 4344475, 4344422
-            })
-            .await;
+            insert_pool(db, owc23.id, qualis.stage_order, &OWC23_QUALIFIER_POOL).await;
         }
         {
             let ro32 = stage::ActiveModel {
@@ -326,70 +321,7 @@ impl DebugService for DebugServiceImpl {
             .await
             .unwrap();
 
-            let nm = pool_bracket::ActiveModel {
-                bracket_order: A::Set(0),
-                name: A::Set("NM".to_owned()),
-                tournament_id: A::Set(owc23.id),
-                stage_order: A::Set(ro32.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let hd = pool_bracket::ActiveModel {
-                bracket_order: A::Set(1),
-                name: A::Set("HD".to_owned()),
-                tournament_id: A::Set(owc23.id),
-                stage_order: A::Set(ro32.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let hr = pool_bracket::ActiveModel {
-                bracket_order: A::Set(2),
-                name: A::Set("HR".to_owned()),
-                tournament_id: A::Set(owc23.id),
-                stage_order: A::Set(ro32.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let dt = pool_bracket::ActiveModel {
-                bracket_order: A::Set(3),
-                name: A::Set("DT".to_owned()),
-                tournament_id: A::Set(owc23.id),
-                stage_order: A::Set(ro32.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let fm = pool_bracket::ActiveModel {
-                bracket_order: A::Set(4),
-                name: A::Set("FM".to_owned()),
-                tournament_id: A::Set(owc23.id),
-                stage_order: A::Set(ro32.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let tb = pool_bracket::ActiveModel {
-                bracket_order: A::Set(5),
-                name: A::Set("TB".to_owned()),
-                tournament_id: A::Set(owc23.id),
-                stage_order: A::Set(ro32.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-
-            let _res = join_all(pool! { ro32,
-                nm => 4352819, 4352824,4351786,3332588;
-                hd => 4352411,4352324;
-                hr => 1414172,2020374;
-                dt => 4352790,3840580,2149694;
-                fm => 2583501,4351866,4352856;
-                tb => 3121101
-            })
-            .await;
+            insert_pool(db, owc23.id, ro32.stage_order, &OWC23_RO32_POOL).await;
 
             let germany_spain_match = model::r#match::ActiveModel {
                 id: A::NotSet,
@@ -420,7 +352,8 @@ impl DebugService for DebugServiceImpl {
             model::match_link::ActiveModel {
                 match_id: A::Set(germany_spain_match.id),
                 link_order: A::Set(0),
-                osu_mp_id: A::Set(111087337),
+                osu_mp_id: A::Set(OWC23_RO32_LOBBY),
+                warmups: A::Set(0),
             }
             .insert(db)
             .await
@@ -434,6 +367,7 @@ impl DebugService for DebugServiceImpl {
                 map_order: A::Set(3),
                 match_id: A::Set(germany_spain_match.id),
                 score: A::Set(987576),
+                ..Default::default()
             }
             .insert(db)
             .await
@@ -447,6 +381,7 @@ impl DebugService for DebugServiceImpl {
                 map_order: A::Set(3),
                 match_id: A::Set(germany_spain_match.id),
                 score: A::Set(982767),
+                ..Default::default()
             }
             .insert(db)
             .await
@@ -460,6 +395,7 @@ impl DebugService for DebugServiceImpl {
                 map_order: A::Set(3),
                 match_id: A::Set(germany_spain_match.id),
                 score: A::Set(813145),
+                ..Default::default()
             }
             .insert(db)
             .await
@@ -473,6 +409,7 @@ impl DebugService for DebugServiceImpl {
                 map_order: A::Set(3),
                 match_id: A::Set(germany_spain_match.id),
                 score: A::Set(699198),
+                ..Default::default()
             }
             .insert(db)
             .await
@@ -497,29 +434,14 @@ impl DebugService for DebugServiceImpl {
             banner: A::Set(None),
             start_date: A::Set(None),
             end_date: A::Set(None),
+            bws_base: A::NotSet,
+            bws_exponent: A::NotSet,
+            deleted_at: A::NotSet,
         }
         .insert(db)
         .await
         .unwrap();
 
-        macro_rules! pool {
-            {$pool:ident, $bracket:ident => $($maps:literal),+} => {
-                [$($maps),+].iter().copied().enumerate().map(|(i, map_id)| {
-                    pool_map::ActiveModel {
-                        tournament_id: A::Set(dm8.id),
-                        stage_order: A::Set($pool.stage_order),
-                        bracket_order: A::Set($bracket.bracket_order),
-                        map_id: A::Set(map_id as i64),
-                        map_order: A::Set(i as i16),
-                    }.insert(db).map(Result::unwrap)
-                })
-            };
-            {$pool:ident, $bracket:ident => $($maps:literal),+; $($other_brackets:ident => $($other_maps:literal),+);+} => {
-                pool!($pool, $($other_brackets => $($other_maps),+);+).chain(
-                pool!($pool, $bracket => $($maps),+))
-            };
-        }
-
         // Qualis
         {
             let qualis = stage::ActiveModel {
@@ -534,50 +456,7 @@ impl DebugService for DebugServiceImpl {
             .await
             .unwrap();
 
-            let nm = pool_bracket::ActiveModel {
-                bracket_order: A::Set(0),
-                name: A::Set("NM".to_owned()),
-                tournament_id: A::Set(dm8.id),
-                stage_order: A::Set(qualis.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let hd = pool_bracket::ActiveModel {
-                bracket_order: A::Set(1),
-                name: A::Set("HD".to_owned()),
-                tournament_id: A::Set(dm8.id),
-                stage_order: A::Set(qualis.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let hr = pool_bracket::ActiveModel {
-                bracket_order: A::Set(2),
-                name: A::Set("HR".to_owned()),
-                tournament_id: A::Set(dm8.id),
-                stage_order: A::Set(qualis.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let dt = pool_bracket::ActiveModel {
-                bracket_order: A::Set(3),
-                name: A::Set("DT".to_owned()),
-                tournament_id: A::Set(dm8.id),
-                stage_order: A::Set(qualis.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-
-            let _res = join_all(pool! { qualis,
-                nm => 2230996, 3263098, 2593243, 3142496, 3129534;
-                hd => 3544219, 2588430;
-                hr => 2314568, 434438;
-                dt => 429797, 3153512
-            })
-            .await;
+            insert_pool(db, dm8.id, qualis.stage_order, &DM8_QUALIFIER_POOL).await;
         }
 
         // RO64
@@ -594,62 +473,73 @@ impl DebugService for DebugServiceImpl {
             .await
             .unwrap();
 
-            let nm = pool_bracket::ActiveModel {
-                bracket_order: A::Set(0),
-                name: A::Set("NM".to_owned()),
-                tournament_id: A::Set(dm8.id),
-                stage_order: A::Set(ro64.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let hd = pool_bracket::ActiveModel {
-                bracket_order: A::Set(1),
-                name: A::Set("HD".to_owned()),
-                tournament_id: A::Set(dm8.id),
-                stage_order: A::Set(ro64.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let hr = pool_bracket::ActiveModel {
-                bracket_order: A::Set(2),
-                name: A::Set("HR".to_owned()),
-                tournament_id: A::Set(dm8.id),
-                stage_order: A::Set(ro64.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let dt = pool_bracket::ActiveModel {
-                bracket_order: A::Set(3),
-                name: A::Set("DT".to_owned()),
-                tournament_id: A::Set(dm8.id),
-                stage_order: A::Set(ro64.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-            let tb = pool_bracket::ActiveModel {
-                bracket_order: A::Set(4),
-                name: A::Set("TB".to_owned()),
-                tournament_id: A::Set(dm8.id),
-                stage_order: A::Set(ro64.stage_order),
-            }
-            .insert(db)
-            .await
-            .unwrap();
-
-            let _res = join_all(pool! { ro64,
-                nm => 3160790,3650832,2465287, 637391;
-                hd => 3830079, 1957037, 2134428;
-                hr => 1982100, 886269, 3167107;
-                dt => 2188430, 3457575, 3541087;
-                tb => 1233051, 1721284, 1295837
-            })
-            .await;
+            insert_pool(db, dm8.id, ro64.stage_order, &DM8_RO64_POOL).await;
         }
 
         Ok(Response::new(()))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{path::Path, sync::Arc};
+
+    use proto::osu::api::{get_match, OsuApi};
+    use rosu_v2::model::GameMode;
+
+    use super::*;
+    use crate::osu::{fixtures::FixtureOsuApi, lobby::played_games};
+
+    fn fixtures() -> FixtureOsuApi {
+        FixtureOsuApi::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/osu")).unwrap()
+    }
+
+    /// The seeded tournaments can be viewed and their match imported while serving fixtures
+    #[tokio::test]
+    async fn seeded_data_has_fixtures() {
+        let osu = fixtures();
+
+        let pool_maps = [
+            &OWC23_QUALIFIER_POOL[..],
+            &OWC23_RO32_POOL,
+            &DM8_QUALIFIER_POOL,
+            &DM8_RO64_POOL,
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|(_, map_ids)| map_ids.iter().copied());
+        for map_id in MAP_IDS.into_iter().chain(pool_maps) {
+            let mapset = osu.beatmapset_from_map_id(map_id).await.unwrap();
+            assert!(mapset.maps.unwrap().iter().any(|map| map.map_id == map_id));
+        }
+
+        let player_ids = OWC23_TEAMS
+            .iter()
+            .flat_map(|(_, player_ids)| player_ids.iter().map(|&id| id as u32))
+            .collect::<Vec<_>>();
+        let players = osu.users(&player_ids).await.unwrap();
+        assert_eq!(players.len(), player_ids.len());
+        for &player_id in &player_ids {
+            let profile = osu.user(player_id, Some(GameMode::Osu)).await.unwrap();
+            assert!(profile.statistics.unwrap().global_rank.is_some());
+        }
+
+        let osu: Arc<dyn OsuApi> = Arc::new(osu);
+        let lobby = get_match(&osu, OWC23_RO32_LOBBY as u32).await.unwrap();
+        let (_, germany) = OWC23_TEAMS[0];
+        let (_, spain) = OWC23_TEAMS[2];
+        let mut games = 0;
+        for game in played_games(&lobby, 0) {
+            games += 1;
+            assert!(OWC23_RO32_POOL.iter().any(|(_, map_ids)| game
+                .map
+                .as_ref()
+                .is_some_and(|map| map_ids.contains(&map.map_id))));
+            for score in &game.scores {
+                let player_id = score.user_id as i32;
+                assert!(germany.contains(&player_id) || spain.contains(&player_id));
+            }
+        }
+        assert_eq!(games, 5);
+    }
+}
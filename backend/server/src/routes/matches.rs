@@ -0,0 +1,1490 @@
+use std::collections::{HashMap, HashSet};
+
+use futures::TryFutureExt;
+use model::{
+    match_link, pick_ban, pool_map, qualifier_run, r#match, score,
+    sea_orm_active_enums::{LobbyTeam, MatchType, PickBanKind},
+    stage, team, team_member, versus_match,
+};
+use proto::{
+    keys::{MatchKey, PoolBracketKey, PoolMapKey, StageKey, TournamentKey},
+    matches::{
+        create_match_request, match_service_server::MatchService, r#match::Details,
+        update_match_request, AddMatchLinkRequest, AddMatchLinkResponse, CreateMatchRequest,
+        CreateMatchResponse, DeleteMatchRequest, DeleteMatchResponse, GetAllMatchesRequest,
+        GetAllMatchesResponse, GetMatchRequest, GetMatchResponse, GetPickBansRequest,
+        GetPickBansResponse, ImportScoresRequest, ImportScoresResponse, Match, MatchLink, PickBan,
+        QualifierRun, RemoveMatchLinkRequest, RemoveMatchLinkResponse, SetPickBansRequest,
+        SetPickBansResponse, UpdateMatchRequest, UpdateMatchResponse, VersusMatch,
+    },
+    osu::api::get_match,
+};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait,
+    FromQueryResult, IntoActiveModel, LoaderTrait, ModelTrait, QueryFilter, QueryOrder,
+    QuerySelect, TransactionTrait,
+};
+use sqlx::types::chrono::NaiveDateTime;
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use super::{
+    rating::{player_team, spawn_rating_replay},
+    stats::find_stage_pool,
+    tournament::find_stage,
+};
+use crate::{
+    osu::lobby::{lobby_team, played_games, score_mods, scoring_type},
+    permission::{require_permission, Permission},
+    AppState,
+};
+
+/// Finds the match with the given key and makes sure it is played in the stage in the key.
+pub async fn find_match(
+    match_key: &MatchKey,
+    db: &DatabaseConnection,
+) -> tonic::Result<(stage::Model, r#match::Model)> {
+    let stage_key = match_key
+        .stage_key
+        .as_ref()
+        .ok_or_else(|| Status::invalid_argument("missing stage key in match key"))?;
+    let (_tournament, stage) = find_stage(stage_key, db).await?;
+
+    let found_match = r#match::Entity::find_by_id(match_key.match_id as i32)
+        .filter(r#match::Column::TournamentId.eq(stage.tournament_id))
+        .filter(r#match::Column::StageOrder.eq(stage.stage_order))
+        .one(db)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to get match from database");
+            Status::internal("failed to get match")
+        })?
+        .ok_or_else(|| {
+            Status::not_found(format!(
+                "match {} in stage {} of tournament {} does not exist",
+                match_key.match_id, stage.stage_order, stage.tournament_id
+            ))
+        })?;
+
+    Ok((stage, found_match))
+}
+
+/// Makes sure that all given teams exist and are registered in the tournament.
+async fn ensure_teams_exist(
+    tournament_id: i32,
+    team_ids: &[u32],
+    db: &DatabaseConnection,
+) -> tonic::Result<()> {
+    let found = team::Entity::find()
+        .filter(team::Column::TournamentId.eq(tournament_id))
+        .filter(team::Column::Id.is_in(team_ids.iter().map(|&id| id as i32)))
+        .all(db)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to get teams from database");
+            Status::internal("failed to get teams")
+        })?;
+
+    match team_ids
+        .iter()
+        .find(|&&id| !found.iter().any(|team| team.id == id as i32))
+    {
+        Some(missing) => Err(Status::not_found(format!(
+            "team {missing} in tournament {tournament_id} does not exist"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Checks whether the teams and scores of a versus match make sense for the stage it is played in.
+fn validate_versus_match(stage: &stage::Model, versus_match: &VersusMatch) -> tonic::Result<()> {
+    if versus_match.team_red == versus_match.team_blue {
+        return Err(Status::invalid_argument(
+            "a team cannot play against itself",
+        ));
+    }
+    // Neither team can win more maps than are needed to win the match
+    let win_score = stage.best_of as u32 / 2 + 1;
+    if versus_match
+        .score_red
+        .into_iter()
+        .chain(versus_match.score_blue)
+        .any(|score| score > win_score)
+    {
+        return Err(Status::invalid_argument(format!(
+            "scores cannot exceed {win_score} in a best-of {}",
+            stage.best_of
+        )));
+    }
+    // The match is over as soon as one team wins
+    if versus_match.score_red == Some(win_score) && versus_match.score_blue == Some(win_score) {
+        return Err(Status::invalid_argument(format!(
+            "only one team can win {win_score} maps in a best-of {}",
+            stage.best_of
+        )));
+    }
+    Ok(())
+}
+
+/// Checks whether a match of the given kind can be played in the stage.
+/// Qualifier runs are played in stages without a best-of, versus matches in every other stage.
+fn validate_match_type(stage: &stage::Model, match_type: MatchType) -> tonic::Result<()> {
+    match match_type {
+        MatchType::Qualifier if stage.best_of != 0 => Err(Status::invalid_argument(format!(
+            "stage {} is not a qualifier stage",
+            stage.name
+        ))),
+        MatchType::VersusMatch if stage.best_of == 0 => Err(Status::invalid_argument(format!(
+            "stage {} is a qualifier stage",
+            stage.name
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn convert_date(date: Option<proto::utils::DateMillis>) -> tonic::Result<NaiveDateTime> {
+    date.ok_or_else(|| Status::invalid_argument("missing match date"))?
+        .try_into()
+        .map_err(|error| {
+            error!(%error, "could not convert date millis into date");
+            Status::invalid_argument("invalid match date")
+        })
+}
+
+/// Finds the versus match with the given key.
+/// Returns an error if the match is a qualifier run.
+async fn find_versus_match(
+    match_key: &MatchKey,
+    db: &DatabaseConnection,
+) -> tonic::Result<(stage::Model, versus_match::Model)> {
+    let (stage, found_match) = find_match(match_key, db).await?;
+    let versus_match = found_match
+        .find_related(versus_match::Entity)
+        .one(db)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to get versus match from database");
+            Status::internal("failed to get versus match")
+        })?
+        .ok_or_else(|| {
+            Status::failed_precondition(format!("match {} is not a versus match", found_match.id))
+        })?;
+
+    Ok((stage, versus_match))
+}
+
+/// Converts a pick or ban into the on-the-wire format
+fn pick_ban_to_proto(stage_key: StageKey, pick_ban: &pick_ban::Model) -> PickBan {
+    PickBan {
+        kind: pick_ban.kind.into(),
+        team_id: pick_ban.team_id.map(|id| id as u32),
+        map_key: Some(PoolMapKey {
+            bracket_key: Some(PoolBracketKey {
+                stage_key: Some(stage_key),
+                bracket_order: pick_ban.bracket_order as u32,
+            }),
+            map_order: pick_ban.map_order as u32,
+        }),
+    }
+}
+
+/// Checks that a pick or ban is valid for the given versus match and converts it into a database
+/// row with the given action order.
+fn validate_pick_ban(
+    stage: &stage::Model,
+    versus_match: &versus_match::Model,
+    pool: &[pool_map::Model],
+    action_order: usize,
+    pick_ban: &PickBan,
+) -> tonic::Result<pick_ban::Model> {
+    let kind = PickBanKind::try_from(pick_ban.kind)
+        .map_err(|kind| Status::invalid_argument(format!("invalid pick ban kind {kind}")))?;
+
+    let team_id = match (kind, pick_ban.team_id) {
+        (PickBanKind::Tiebreaker, None) => None,
+        (PickBanKind::Tiebreaker, Some(_)) => {
+            return Err(Status::invalid_argument(
+                "tiebreakers can not be chosen by a team",
+            ))
+        }
+        (_, None) => {
+            return Err(Status::invalid_argument(format!(
+                "pick ban {action_order} is missing a team"
+            )))
+        }
+        (_, Some(id))
+            if id as i32 == versus_match.team_red || id as i32 == versus_match.team_blue =>
+        {
+            Some(id as i32)
+        }
+        (_, Some(id)) => {
+            return Err(Status::invalid_argument(format!(
+                "team {id} does not play in match {}",
+                versus_match.match_id
+            )))
+        }
+    };
+
+    let map_key = pick_ban
+        .map_key
+        .as_ref()
+        .ok_or_else(|| Status::invalid_argument("missing pool map key"))?;
+    let bracket_key = map_key
+        .bracket_key
+        .as_ref()
+        .ok_or_else(|| Status::invalid_argument("missing bracket key in pool map key"))?;
+    let stage_key = bracket_key
+        .stage_key
+        .as_ref()
+        .ok_or_else(|| Status::invalid_argument("missing stage key in pool map key"))?;
+    let in_stage = stage_key.tournament_key.map(|key| key.id) == Some(stage.tournament_id)
+        && stage_key.stage_order as i16 == stage.stage_order;
+    let in_pool = pool.iter().any(|map| {
+        map.bracket_order as u32 == bracket_key.bracket_order
+            && map.map_order as u32 == map_key.map_order
+    });
+    if !in_stage || !in_pool {
+        return Err(Status::invalid_argument(format!(
+            "map {} in bracket {} is not in the pool of stage {}",
+            map_key.map_order, bracket_key.bracket_order, stage.stage_order
+        )));
+    }
+
+    Ok(pick_ban::Model {
+        match_id: versus_match.match_id,
+        action_order: action_order as i16,
+        kind,
+        team_id,
+        tournament_id: stage.tournament_id,
+        stage_order: stage.stage_order,
+        bracket_order: bracket_key.bracket_order as i16,
+        map_order: map_key.map_order as i16,
+    })
+}
+
+/// Inserts a match along with its details and links.
+/// This happens in a single transaction, so either all of it is created or none of it.
+async fn insert_match(
+    db: &DatabaseConnection,
+    new_match: r#match::ActiveModel,
+    details: create_match_request::Details,
+    osu_mp_ids: &[u32],
+) -> tonic::Result<r#match::Model> {
+    use ActiveValue as A;
+    let match_type = new_match.match_type.clone().unwrap();
+    let txn = db.begin().await.map_err(|error| {
+        error!(%error, "failed to start transaction");
+        Status::internal("failed to create match")
+    })?;
+
+    let new_match = new_match.insert(&txn).await.map_err(|error| {
+        error!(%error, "failed to insert match");
+        Status::internal("failed to create match")
+    })?;
+
+    match details {
+        create_match_request::Details::QualifierRun(run) => qualifier_run::ActiveModel {
+            match_id: A::Set(new_match.id),
+            team_id: A::Set(run.team_id as i32),
+            match_type: A::Set(match_type),
+        }
+        .insert(&txn)
+        .await
+        .map(|_| ()),
+        create_match_request::Details::VersusMatch(versus) => versus_match::ActiveModel {
+            match_id: A::Set(new_match.id),
+            team_red: A::Set(versus.team_red as i32),
+            team_blue: A::Set(versus.team_blue as i32),
+            score_red: A::Set(versus.score_red.map(|score| score as i16)),
+            score_blue: A::Set(versus.score_blue.map(|score| score as i16)),
+            match_type: A::Set(match_type),
+        }
+        .insert(&txn)
+        .await
+        .map(|_| ()),
+    }
+    .map_err(|error| {
+        error!(%error, "failed to insert match details");
+        Status::internal("failed to create match")
+    })?;
+
+    if !osu_mp_ids.is_empty() {
+        match_link::Entity::insert_many(osu_mp_ids.iter().enumerate().map(
+            |(link_order, &osu_mp_id)| match_link::ActiveModel {
+                match_id: A::Set(new_match.id),
+                link_order: A::Set(link_order as i16),
+                osu_mp_id: A::Set(osu_mp_id as i32),
+                warmups: A::Set(0),
+            },
+        ))
+        .exec(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to insert match links");
+            Status::internal("failed to create match links")
+        })?;
+    }
+
+    txn.commit().await.map_err(|error| {
+        error!(%error, "failed to commit transaction");
+        Status::internal("failed to create match")
+    })?;
+
+    Ok(new_match)
+}
+
+/// Updates the details and the date of a match.
+/// This happens in a single transaction, so either both are updated or neither is.
+async fn update_match(
+    db: &DatabaseConnection,
+    found_match: r#match::Model,
+    details: Option<update_match_request::Details>,
+    date: Option<NaiveDateTime>,
+) -> tonic::Result<r#match::Model> {
+    use ActiveValue as A;
+    let txn = db.begin().await.map_err(|error| {
+        error!(%error, "failed to start transaction");
+        Status::internal("failed to update match")
+    })?;
+
+    match details {
+        Some(update_match_request::Details::QualifierRun(run)) => qualifier_run::ActiveModel {
+            match_id: A::Unchanged(found_match.id),
+            team_id: A::Set(run.team_id as i32),
+            match_type: A::Unchanged(found_match.match_type),
+        }
+        .update(&txn)
+        .await
+        .map(|_| ()),
+        Some(update_match_request::Details::VersusMatch(versus)) => versus_match::ActiveModel {
+            match_id: A::Unchanged(found_match.id),
+            team_red: A::Set(versus.team_red as i32),
+            team_blue: A::Set(versus.team_blue as i32),
+            score_red: A::Set(versus.score_red.map(|score| score as i16)),
+            score_blue: A::Set(versus.score_blue.map(|score| score as i16)),
+            match_type: A::Unchanged(found_match.match_type),
+        }
+        .update(&txn)
+        .await
+        .map(|_| ()),
+        None => Ok(()),
+    }
+    .map_err(|error| {
+        error!(%error, "failed to update match details");
+        Status::internal("failed to update match")
+    })?;
+
+    let found_match = match date {
+        Some(date) => {
+            let mut active_match = found_match.into_active_model();
+            active_match.date = A::Set(date);
+            active_match.update(&txn).await.map_err(|error| {
+                error!(%error, "failed to update match");
+                Status::internal("failed to update match")
+            })?
+        }
+        None => found_match,
+    };
+
+    txn.commit().await.map_err(|error| {
+        error!(%error, "failed to commit transaction");
+        Status::internal("failed to update match")
+    })?;
+
+    Ok(found_match)
+}
+
+/// Deletes a match along with everything referencing it.
+/// This happens in a single transaction, so either everything is deleted or nothing is.
+async fn delete_match(db: &DatabaseConnection, found_match: r#match::Model) -> tonic::Result<()> {
+    let txn = db.begin().await.map_err(|error| {
+        error!(%error, "failed to start transaction");
+        Status::internal("failed to delete match")
+    })?;
+
+    // Everything referencing the match has to be deleted first
+    pick_ban::Entity::delete_many()
+        .filter(pick_ban::Column::MatchId.eq(found_match.id))
+        .exec(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to delete pick bans");
+            Status::internal("failed to delete match pick bans")
+        })?;
+    score::Entity::delete_many()
+        .filter(score::Column::MatchId.eq(found_match.id))
+        .exec(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to delete scores");
+            Status::internal("failed to delete match scores")
+        })?;
+    match_link::Entity::delete_many()
+        .filter(match_link::Column::MatchId.eq(found_match.id))
+        .exec(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to delete match links");
+            Status::internal("failed to delete match links")
+        })?;
+    qualifier_run::Entity::delete_by_id(found_match.id)
+        .exec(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to delete qualifier run");
+            Status::internal("failed to delete match")
+        })?;
+    versus_match::Entity::delete_by_id(found_match.id)
+        .exec(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to delete versus match");
+            Status::internal("failed to delete match")
+        })?;
+    found_match.delete(&txn).await.map_err(|error| {
+        error!(%error, "failed to delete match");
+        Status::internal("failed to delete match")
+    })?;
+
+    txn.commit().await.map_err(|error| {
+        error!(%error, "failed to commit transaction");
+        Status::internal("failed to delete match")
+    })
+}
+
+/// Adds a link to a match, after the links it already has.
+/// The match is locked while the link is added, so that concurrent additions get different link
+/// orders.
+async fn insert_link(
+    db: &DatabaseConnection,
+    match_id: i32,
+    osu_mp_id: i32,
+    warmups: i16,
+) -> tonic::Result<match_link::Model> {
+    use ActiveValue as A;
+    #[derive(FromQueryResult, Debug)]
+    struct MaxLink {
+        link_order: Option<i16>,
+    }
+
+    let txn = db.begin().await.map_err(|error| {
+        error!(%error, "failed to start transaction");
+        Status::internal("failed to add match link")
+    })?;
+
+    // Postgres can't lock the rows of an aggregate, so the match itself is locked instead
+    r#match::Entity::find_by_id(match_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to lock match");
+            Status::internal("failed to add match link")
+        })?;
+
+    // Find the max link_order for this match so far
+    let max_link = match_link::Entity::find()
+        .select_only()
+        .column_as(Expr::col(match_link::Column::LinkOrder).max(), "link_order")
+        .filter(match_link::Column::MatchId.eq(match_id))
+        .into_model::<MaxLink>()
+        .one(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to get match links from database");
+            Status::internal("failed to add match link")
+        })?;
+
+    let link = match_link::ActiveModel {
+        match_id: A::Set(match_id),
+        // If there already is a link, use a link order one higher than the highest
+        // one that exists. Otherwise, just use 0
+        link_order: A::Set(
+            max_link
+                .and_then(|max| max.link_order)
+                .map(|max| max + 1)
+                .unwrap_or_default(),
+        ),
+        osu_mp_id: A::Set(osu_mp_id),
+        warmups: A::Set(warmups),
+    }
+    .insert(&txn)
+    .await
+    .map_err(|error| {
+        error!(%error, "failed to insert match link");
+        Status::internal("failed to add match link")
+    })?;
+
+    txn.commit().await.map_err(|error| {
+        error!(%error, "failed to commit transaction");
+        Status::internal("failed to add match link")
+    })?;
+
+    Ok(link)
+}
+
+/// Replaces all scores of a match, so that scores of games and links that were removed since the
+/// last import don't remain.
+/// This happens in a single transaction, so the old scores are kept if the new ones can't be
+/// inserted.
+async fn replace_scores(
+    db: &DatabaseConnection,
+    match_id: i32,
+    scores: impl IntoIterator<Item = score::ActiveModel>,
+) -> tonic::Result<()> {
+    let scores = scores.into_iter().collect::<Vec<_>>();
+    let txn = db.begin().await.map_err(|error| {
+        error!(%error, "failed to start transaction");
+        Status::internal("failed to import scores")
+    })?;
+
+    score::Entity::delete_many()
+        .filter(score::Column::MatchId.eq(match_id))
+        .exec(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to delete scores");
+            Status::internal("failed to delete old scores")
+        })?;
+    if !scores.is_empty() {
+        score::Entity::insert_many(scores)
+            .exec_without_returning(&txn)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to insert scores");
+                Status::internal("failed to insert scores")
+            })?;
+    }
+
+    txn.commit().await.map_err(|error| {
+        error!(%error, "failed to commit transaction");
+        Status::internal("failed to import scores")
+    })
+}
+
+pub struct MatchServiceImpl(pub AppState);
+
+impl MatchServiceImpl {
+    /// Loads a match's links and details and converts it into the on-the-wire format
+    async fn to_proto(&self, found_match: r#match::Model) -> tonic::Result<Match> {
+        let db = &self.0.db;
+        let links = found_match
+            .find_related(match_link::Entity)
+            .order_by_asc(match_link::Column::LinkOrder)
+            .all(db)
+            .map_err(|error| {
+                error!(%error, "failed to get match links from database");
+                Status::internal("failed to get match links")
+            });
+        let qualifier_run = found_match
+            .find_related(qualifier_run::Entity)
+            .one(db)
+            .map_err(|error| {
+                error!(%error, "failed to get qualifier run from database");
+                Status::internal("failed to get qualifier run")
+            });
+        let versus_match = found_match
+            .find_related(versus_match::Entity)
+            .one(db)
+            .map_err(|error| {
+                error!(%error, "failed to get versus match from database");
+                Status::internal("failed to get versus match")
+            });
+        let (links, qualifier_run, versus_match) =
+            tokio::try_join!(links, qualifier_run, versus_match)?;
+
+        Ok(match_to_proto(
+            found_match,
+            links,
+            qualifier_run,
+            versus_match,
+        ))
+    }
+}
+
+/// Converts a match and its related rows into the on-the-wire format
+fn match_to_proto(
+    found_match: r#match::Model,
+    links: Vec<match_link::Model>,
+    qualifier_run: Option<qualifier_run::Model>,
+    versus_match: Option<versus_match::Model>,
+) -> Match {
+    let details = match (qualifier_run, versus_match) {
+        (Some(run), _) => Some(Details::QualifierRun(QualifierRun {
+            team_id: run.team_id as u32,
+        })),
+        (None, Some(versus)) => Some(Details::VersusMatch(VersusMatch {
+            team_red: versus.team_red as u32,
+            team_blue: versus.team_blue as u32,
+            score_red: versus.score_red.map(|score| score as u32),
+            score_blue: versus.score_blue.map(|score| score as u32),
+        })),
+        (None, None) => None,
+    };
+
+    Match {
+        key: Some(MatchKey {
+            stage_key: Some(StageKey {
+                tournament_key: Some(TournamentKey {
+                    id: found_match.tournament_id,
+                }),
+                stage_order: found_match.stage_order as u32,
+            }),
+            match_id: found_match.id as u32,
+        }),
+        date: Some(found_match.date.into()),
+        links: links
+            .into_iter()
+            .map(|link| MatchLink {
+                link_order: link.link_order as u32,
+                osu_mp_id: link.osu_mp_id as u32,
+                warmups: link.warmups as u32,
+            })
+            .collect(),
+        details,
+    }
+}
+
+#[tonic::async_trait]
+impl MatchService for MatchServiceImpl {
+    type GetAllStream =
+        futures::stream::Iter<std::vec::IntoIter<Result<GetAllMatchesResponse, Status>>>;
+
+    #[tracing::instrument(skip_all, fields(stage_key = ?request.get_ref().stage_key))]
+    async fn get_all(
+        &self,
+        request: Request<GetAllMatchesRequest>,
+    ) -> Result<Response<Self::GetAllStream>, Status> {
+        let db = &self.0.db;
+        let stage_key = request
+            .get_ref()
+            .stage_key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing stage key"))?;
+        let (_tournament, stage) = find_stage(stage_key, db).await?;
+
+        let matches = stage
+            .find_related(r#match::Entity)
+            .order_by_asc(r#match::Column::Date)
+            .order_by_asc(r#match::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get matches from database");
+                Status::internal("failed to get matches")
+            })?;
+
+        let links = matches
+            .load_many(
+                match_link::Entity::find().order_by_asc(match_link::Column::LinkOrder),
+                db,
+            )
+            .map_err(|error| {
+                error!(%error, "failed to get match links from database");
+                Status::internal("failed to get match links")
+            });
+        let qualifier_runs = matches
+            .load_many(qualifier_run::Entity, db)
+            .map_err(|error| {
+                error!(%error, "failed to get qualifier runs from database");
+                Status::internal("failed to get qualifier runs")
+            });
+        let versus_matches = matches
+            .load_many(versus_match::Entity, db)
+            .map_err(|error| {
+                error!(%error, "failed to get versus matches from database");
+                Status::internal("failed to get versus matches")
+            });
+        let (links, qualifier_runs, versus_matches) =
+            tokio::try_join!(links, qualifier_runs, versus_matches)?;
+
+        let matches = itertools::izip!(matches, links, qualifier_runs, versus_matches)
+            .map(|(found_match, links, qualifier_runs, versus_matches)| {
+                Ok(GetAllMatchesResponse {
+                    r#match: Some(match_to_proto(
+                        found_match,
+                        links,
+                        qualifier_runs.into_iter().next(),
+                        versus_matches.into_iter().next(),
+                    )),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Response::new(futures::stream::iter(matches)))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key))]
+    async fn get(
+        &self,
+        request: Request<GetMatchRequest>,
+    ) -> Result<Response<GetMatchResponse>, Status> {
+        let match_key = request
+            .get_ref()
+            .key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing match key"))?;
+        let (_stage, found_match) = find_match(match_key, &self.0.db).await?;
+
+        Ok(Response::new(GetMatchResponse {
+            r#match: Some(self.to_proto(found_match).await?),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(stage_key = ?request.get_ref().stage_key))]
+    async fn create(
+        &self,
+        request: Request<CreateMatchRequest>,
+    ) -> Result<Response<CreateMatchResponse>, Status> {
+        use ActiveValue as A;
+        let db = &self.0.db;
+        let stage_key = request
+            .get_ref()
+            .stage_key
+            .ok_or_else(|| Status::invalid_argument("missing stage key"))?;
+        let (tournament, stage) = find_stage(&stage_key, db).await?;
+        require_permission(&request, tournament.id, Permission::ScheduleMatches, db).await?;
+        let request = request.into_inner();
+        let date = convert_date(request.date)?;
+        let details = request
+            .details
+            .ok_or_else(|| Status::invalid_argument("missing match details"))?;
+
+        // Validate the match before inserting anything
+        let match_type = match details {
+            create_match_request::Details::QualifierRun(ref run) => {
+                validate_match_type(&stage, MatchType::Qualifier)?;
+                ensure_teams_exist(tournament.id, &[run.team_id], db).await?;
+                MatchType::Qualifier
+            }
+            create_match_request::Details::VersusMatch(ref versus) => {
+                validate_match_type(&stage, MatchType::VersusMatch)?;
+                validate_versus_match(&stage, versus)?;
+                ensure_teams_exist(tournament.id, &[versus.team_red, versus.team_blue], db).await?;
+                MatchType::VersusMatch
+            }
+        };
+
+        let new_match = r#match::ActiveModel {
+            id: A::NotSet,
+            tournament_id: A::Set(tournament.id),
+            stage_order: A::Set(stage.stage_order),
+            date: A::Set(date),
+            match_type: A::Set(match_type),
+        };
+        let new_match = insert_match(db, new_match, details, &request.osu_mp_ids).await?;
+
+        Ok(Response::new(CreateMatchResponse {
+            r#match: Some(self.to_proto(new_match).await?),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key))]
+    async fn update(
+        &self,
+        request: Request<UpdateMatchRequest>,
+    ) -> Result<Response<UpdateMatchResponse>, Status> {
+        let db = &self.0.db;
+        let match_key = request
+            .get_ref()
+            .key
+            .ok_or_else(|| Status::invalid_argument("missing match key"))?;
+        let (stage, found_match) = find_match(&match_key, db).await?;
+        require_permission(
+            &request,
+            found_match.tournament_id,
+            Permission::RefereeMatches,
+            db,
+        )
+        .await?;
+        // Rescheduling is up to the people scheduling matches, not the referees
+        if request.get_ref().date.is_some() {
+            require_permission(
+                &request,
+                found_match.tournament_id,
+                Permission::ScheduleMatches,
+                db,
+            )
+            .await?;
+        }
+        let request = request.into_inner();
+
+        match &request.details {
+            Some(update_match_request::Details::QualifierRun(run)) => {
+                if found_match.match_type != MatchType::Qualifier {
+                    return Err(Status::invalid_argument("match is not a qualifier run"));
+                }
+                ensure_teams_exist(stage.tournament_id, &[run.team_id], db).await?;
+            }
+            Some(update_match_request::Details::VersusMatch(versus)) => {
+                if found_match.match_type != MatchType::VersusMatch {
+                    return Err(Status::invalid_argument("match is not a versus match"));
+                }
+                validate_versus_match(&stage, versus)?;
+                ensure_teams_exist(
+                    stage.tournament_id,
+                    &[versus.team_red, versus.team_blue],
+                    db,
+                )
+                .await?;
+            }
+            None => {}
+        }
+        let date = request
+            .date
+            .map(|date| convert_date(Some(date)))
+            .transpose()?;
+        let found_match = update_match(db, found_match, request.details, date).await?;
+
+        spawn_rating_replay(self.0.clone());
+
+        Ok(Response::new(UpdateMatchResponse {
+            r#match: Some(self.to_proto(found_match).await?),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key))]
+    async fn delete(
+        &self,
+        request: Request<DeleteMatchRequest>,
+    ) -> Result<Response<DeleteMatchResponse>, Status> {
+        let db = &self.0.db;
+        let match_key = request
+            .get_ref()
+            .key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing match key"))?;
+        let (_stage, found_match) = find_match(match_key, db).await?;
+        require_permission(
+            &request,
+            found_match.tournament_id,
+            Permission::ScheduleMatches,
+            db,
+        )
+        .await?;
+
+        delete_match(db, found_match).await?;
+
+        spawn_rating_replay(self.0.clone());
+
+        Ok(Response::new(DeleteMatchResponse {}))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key, osu_mp_id = request.get_ref().osu_mp_id))]
+    async fn add_link(
+        &self,
+        request: Request<AddMatchLinkRequest>,
+    ) -> Result<Response<AddMatchLinkResponse>, Status> {
+        let db = &self.0.db;
+        let match_key = request
+            .get_ref()
+            .key
+            .ok_or_else(|| Status::invalid_argument("missing match key"))?;
+        let (_stage, found_match) = find_match(&match_key, db).await?;
+        require_permission(
+            &request,
+            found_match.tournament_id,
+            Permission::RefereeMatches,
+            db,
+        )
+        .await?;
+        let request = request.into_inner();
+
+        let link = insert_link(
+            db,
+            found_match.id,
+            request.osu_mp_id as i32,
+            request.warmups as i16,
+        )
+        .await?;
+
+        Ok(Response::new(AddMatchLinkResponse {
+            link: Some(MatchLink {
+                link_order: link.link_order as u32,
+                osu_mp_id: link.osu_mp_id as u32,
+                warmups: link.warmups as u32,
+            }),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key, link_order = request.get_ref().link_order))]
+    async fn remove_link(
+        &self,
+        request: Request<RemoveMatchLinkRequest>,
+    ) -> Result<Response<RemoveMatchLinkResponse>, Status> {
+        let db = &self.0.db;
+        let match_key = request
+            .get_ref()
+            .key
+            .ok_or_else(|| Status::invalid_argument("missing match key"))?;
+        let (_stage, found_match) = find_match(&match_key, db).await?;
+        require_permission(
+            &request,
+            found_match.tournament_id,
+            Permission::RefereeMatches,
+            db,
+        )
+        .await?;
+        let request = request.into_inner();
+
+        let delete_result =
+            match_link::Entity::delete_by_id((found_match.id, request.link_order as i16))
+                .exec(db)
+                .await
+                .map_err(|error| {
+                    error!(%error, "failed to delete match link");
+                    Status::internal("failed to remove match link")
+                })?;
+
+        // If no link was deleted, that means that it didn't exist
+        if delete_result.rows_affected == 0 {
+            return Err(Status::not_found(format!(
+                "link {} of match {} does not exist",
+                request.link_order, found_match.id
+            )));
+        }
+
+        Ok(Response::new(RemoveMatchLinkResponse {}))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key))]
+    async fn import_scores(
+        &self,
+        request: Request<ImportScoresRequest>,
+    ) -> Result<Response<ImportScoresResponse>, Status> {
+        use ActiveValue as A;
+        let db = &self.0.db;
+        let match_key = request
+            .get_ref()
+            .key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing match key"))?;
+        let (stage, found_match) = find_match(match_key, db).await?;
+        require_permission(
+            &request,
+            found_match.tournament_id,
+            Permission::RefereeMatches,
+            db,
+        )
+        .await?;
+
+        let links = found_match
+            .find_related(match_link::Entity)
+            .order_by_asc(match_link::Column::LinkOrder)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get match links from database");
+                Status::internal("failed to get match links")
+            })?;
+        if links.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "match {} has no links to import scores from",
+                found_match.id
+            )));
+        }
+
+        // Map the osu beatmap ids to the maps in the stage's pool
+        let pool = pool_map::Entity::find()
+            .filter(pool_map::Column::TournamentId.eq(stage.tournament_id))
+            .filter(pool_map::Column::StageOrder.eq(stage.stage_order))
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get pool maps from database");
+                Status::internal("failed to get pool maps")
+            })?
+            .into_iter()
+            .map(|map| (map.map_id, map))
+            .collect::<HashMap<_, _>>();
+
+        // Only the players of the match's teams are imported, so that e.g. a referee testing the
+        // lobby doesn't count. Substitutes who aren't members are kept if their lobby color puts
+        // them on a side.
+        let versus = versus_match::Entity::find_by_id(found_match.id)
+            .one(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get versus match from database");
+                Status::internal("failed to get versus match")
+            })?;
+        let team_ids = match &versus {
+            Some(versus) => vec![versus.team_red, versus.team_blue],
+            None => qualifier_run::Entity::find_by_id(found_match.id)
+                .one(db)
+                .await
+                .map_err(|error| {
+                    error!(%error, "failed to get qualifier run from database");
+                    Status::internal("failed to get qualifier run")
+                })?
+                .map(|run| run.team_id)
+                .into_iter()
+                .collect(),
+        };
+        let members = team_member::Entity::find()
+            .filter(team_member::Column::TeamId.is_in(team_ids.iter().copied()))
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get team members from database");
+                Status::internal("failed to get team members")
+            })?
+            .into_iter()
+            .map(|member| (member.team_id, member.user_id))
+            .collect::<HashSet<_>>();
+        let plays_for_team = |player_id: i32, team: LobbyTeam| match &versus {
+            Some(versus) => player_team(versus, &members, player_id, team).is_some(),
+            None => {
+                team != LobbyTeam::None
+                    || team_ids
+                        .iter()
+                        .any(|&team_id| members.contains(&(team_id, player_id)))
+            }
+        };
+
+        let mut imported_games = 0;
+        let mut skipped_games = 0;
+        // If a map is played multiple times in a match, the last play counts.
+        // We also can't upsert the same row twice in a single statement.
+        let mut scores = HashMap::new();
+        for link in links {
+            let lobby = get_match(&self.0.osu, link.osu_mp_id as u32)
+                .await
+                .map_err(|error| {
+                    error!(%error, osu_mp_id = link.osu_mp_id, "could not get lobby from osu api");
+                    Status::internal(format!(
+                        "could not get lobby {} from osu api",
+                        link.osu_mp_id
+                    ))
+                })?;
+
+            let total_games = lobby.games().count();
+            let mut imported_link_games = 0;
+            for game in played_games(&lobby, link.warmups as usize) {
+                let Some(pool_map) = game
+                    .map
+                    .as_ref()
+                    .and_then(|map| pool.get(&(map.map_id as i64)))
+                else {
+                    continue;
+                };
+                imported_link_games += 1;
+
+                for match_score in game.scores.iter().filter(|s| s.score > 0) {
+                    let team = lobby_team(match_score);
+                    if !plays_for_team(match_score.user_id as i32, team) {
+                        continue;
+                    }
+                    let key = (
+                        match_score.user_id,
+                        pool_map.bracket_order,
+                        pool_map.map_order,
+                    );
+                    let score = score::ActiveModel {
+                        player_id: A::Set(match_score.user_id as i32),
+                        tournament_id: A::Set(pool_map.tournament_id),
+                        stage_order: A::Set(pool_map.stage_order),
+                        bracket_order: A::Set(pool_map.bracket_order),
+                        map_order: A::Set(pool_map.map_order),
+                        match_id: A::Set(found_match.id),
+                        score: A::Set(match_score.score as i64),
+                        accuracy: A::Set(Some(match_score.accuracy)),
+                        max_combo: A::Set(Some(match_score.max_combo as i32)),
+                        count_300: A::Set(Some(match_score.statistics.count_300 as i32)),
+                        count_100: A::Set(Some(match_score.statistics.count_100 as i32)),
+                        count_50: A::Set(Some(match_score.statistics.count_50 as i32)),
+                        count_miss: A::Set(Some(match_score.statistics.count_miss as i32)),
+                        mods: A::Set(Some(score_mods(game, match_score) as i32)),
+                        pass: A::Set(Some(match_score.pass)),
+                        perfect: A::Set(Some(match_score.perfect)),
+                        scoring_type: A::Set(Some(scoring_type(game))),
+                        team: A::Set(Some(team)),
+                        slot: A::Set(Some(match_score.slot.into())),
+                    };
+                    scores.insert(key, score);
+                }
+            }
+            imported_games += imported_link_games;
+            skipped_games += total_games - imported_link_games;
+        }
+
+        let imported_scores = scores.len();
+        replace_scores(db, found_match.id, scores.into_values()).await?;
+
+        spawn_rating_replay(self.0.clone());
+
+        Ok(Response::new(ImportScoresResponse {
+            imported_games: imported_games as u32,
+            skipped_games: skipped_games as u32,
+            imported_scores: imported_scores as u32,
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key))]
+    async fn get_pick_bans(
+        &self,
+        request: Request<GetPickBansRequest>,
+    ) -> Result<Response<GetPickBansResponse>, Status> {
+        let db = &self.0.db;
+        let match_key = request
+            .get_ref()
+            .key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing match key"))?;
+        let (stage, versus_match) = find_versus_match(match_key, db).await?;
+
+        let pick_bans = versus_match
+            .find_related(pick_ban::Entity)
+            .order_by_asc(pick_ban::Column::ActionOrder)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get pick bans from database");
+                Status::internal("failed to get pick bans")
+            })?;
+
+        let stage_key = StageKey {
+            tournament_key: Some(TournamentKey {
+                id: stage.tournament_id,
+            }),
+            stage_order: stage.stage_order as u32,
+        };
+        Ok(Response::new(GetPickBansResponse {
+            pick_bans: pick_bans
+                .iter()
+                .map(|pick_ban| pick_ban_to_proto(stage_key, pick_ban))
+                .collect(),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key))]
+    async fn set_pick_bans(
+        &self,
+        request: Request<SetPickBansRequest>,
+    ) -> Result<Response<SetPickBansResponse>, Status> {
+        let db = &self.0.db;
+        let match_key = request
+            .get_ref()
+            .key
+            .ok_or_else(|| Status::invalid_argument("missing match key"))?;
+        let (stage, versus_match) = find_versus_match(&match_key, db).await?;
+        require_permission(
+            &request,
+            stage.tournament_id,
+            Permission::RefereeMatches,
+            db,
+        )
+        .await?;
+        let request = request.into_inner();
+        let pool = find_stage_pool(&stage, db).await?;
+
+        let pick_bans = request
+            .pick_bans
+            .iter()
+            .enumerate()
+            .map(|(i, pick_ban)| validate_pick_ban(&stage, &versus_match, &pool, i, pick_ban))
+            .collect::<tonic::Result<Vec<_>>>()?;
+
+        // The old log must not be deleted if the new one can't be inserted
+        let txn = db.begin().await.map_err(|error| {
+            error!(%error, "failed to start transaction");
+            Status::internal("failed to set pick bans")
+        })?;
+        pick_ban::Entity::delete_many()
+            .filter(pick_ban::Column::MatchId.eq(versus_match.match_id))
+            .exec(&txn)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to delete pick bans");
+                Status::internal("failed to set pick bans")
+            })?;
+        if !pick_bans.is_empty() {
+            pick_ban::Entity::insert_many(
+                pick_bans
+                    .iter()
+                    .cloned()
+                    .map(IntoActiveModel::into_active_model),
+            )
+            .exec_without_returning(&txn)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to insert pick bans");
+                Status::internal("failed to set pick bans")
+            })?;
+        }
+        txn.commit().await.map_err(|error| {
+            error!(%error, "failed to commit transaction");
+            Status::internal("failed to set pick bans")
+        })?;
+
+        let stage_key = StageKey {
+            tournament_key: Some(TournamentKey {
+                id: stage.tournament_id,
+            }),
+            stage_order: stage.stage_order as u32,
+        };
+        Ok(Response::new(SetPickBansResponse {
+            pick_bans: pick_bans
+                .iter()
+                .map(|pick_ban| pick_ban_to_proto(stage_key, pick_ban))
+                .collect(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use model::sea_orm_active_enums::ScoringType;
+    use sea_orm::{DatabaseBackend, DbErr, Iterable, MockDatabase, Value};
+    use sqlx::types::chrono::NaiveDate;
+
+    use super::*;
+    use crate::routes::test_util::{exec_ok, rolled_back, statement};
+
+    fn stage(best_of: i16) -> stage::Model {
+        stage::Model {
+            tournament_id: 1,
+            stage_order: 1,
+            name: "Quarterfinals".to_owned(),
+            best_of,
+            start_date: None,
+            end_date: None,
+        }
+    }
+
+    fn versus(score_red: Option<u32>, score_blue: Option<u32>) -> VersusMatch {
+        VersusMatch {
+            team_red: 1,
+            team_blue: 2,
+            score_red,
+            score_blue,
+        }
+    }
+
+    #[test]
+    fn validates_versus_scores() {
+        let stage = stage(9);
+
+        assert!(validate_versus_match(&stage, &versus(None, None)).is_ok());
+        assert!(validate_versus_match(&stage, &versus(Some(5), Some(4))).is_ok());
+        // A match can't continue after a team has won it
+        assert!(validate_versus_match(&stage, &versus(Some(5), Some(5))).is_err());
+        assert!(validate_versus_match(&stage, &versus(Some(6), Some(2))).is_err());
+        assert!(validate_versus_match(&stage, &versus(Some(1), Some(6))).is_err());
+    }
+
+    fn date() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(18, 0, 0)
+            .unwrap()
+    }
+
+    fn versus_match() -> r#match::Model {
+        r#match::Model {
+            id: 1,
+            tournament_id: 1,
+            stage_order: 1,
+            date: date(),
+            match_type: MatchType::VersusMatch,
+        }
+    }
+
+    fn score(player_id: i32) -> score::Model {
+        score::Model {
+            player_id,
+            tournament_id: 1,
+            stage_order: 1,
+            bracket_order: 0,
+            map_order: 0,
+            match_id: 1,
+            score: 500_000,
+            accuracy: Some(0.98),
+            max_combo: Some(1000),
+            count_300: Some(900),
+            count_100: Some(20),
+            count_50: Some(0),
+            count_miss: Some(1),
+            mods: Some(0),
+            pass: Some(true),
+            perfect: Some(false),
+            scoring_type: Some(ScoringType::ScoreV2),
+            team: Some(LobbyTeam::Red),
+            slot: Some(0),
+        }
+    }
+
+    #[tokio::test]
+    async fn create_rolls_back_on_failure() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![versus_match()]])
+            .append_query_results([vec![versus_match::Model {
+                match_id: 1,
+                team_red: 1,
+                team_blue: 2,
+                score_red: None,
+                score_blue: None,
+                match_type: MatchType::VersusMatch,
+            }]])
+            // Inserting the links fails
+            .append_query_errors([DbErr::Custom("injected failure".to_owned())])
+            .into_connection();
+
+        let mut new_match = versus_match().into_active_model().reset_all();
+        new_match.id = ActiveValue::NotSet;
+        let details = create_match_request::Details::VersusMatch(versus(None, None));
+        let created = insert_match(&db, new_match, details, &[111, 222]).await;
+
+        assert!(created.is_err());
+        assert_eq!(
+            db.into_transaction_log(),
+            [rolled_back([
+                statement(
+                    r#"INSERT INTO "match" ("tournament_id", "stage_order", "date", "match_type") VALUES ($1, $2, $3, CAST($4 AS match_type)) RETURNING "id", "tournament_id", "stage_order", "date", CAST("match_type" AS text)"#,
+                    [
+                        1i32.into(),
+                        1i16.into(),
+                        date().into(),
+                        "versus_match".into(),
+                    ],
+                ),
+                statement(
+                    r#"INSERT INTO "versus_match" ("match_id", "team_red", "team_blue", "score_red", "score_blue", "match_type") VALUES ($1, $2, $3, $4, $5, CAST($6 AS match_type)) RETURNING "match_id", "team_red", "team_blue", "score_red", "score_blue", CAST("match_type" AS text)"#,
+                    [
+                        1i32.into(),
+                        1i32.into(),
+                        2i32.into(),
+                        Value::SmallInt(None),
+                        Value::SmallInt(None),
+                        "versus_match".into(),
+                    ],
+                ),
+                statement(
+                    r#"INSERT INTO "match_link" ("match_id", "link_order", "osu_mp_id", "warmups") VALUES ($1, $2, $3, $4), ($5, $6, $7, $8) RETURNING "match_id", "link_order""#,
+                    [
+                        1i32.into(),
+                        0i16.into(),
+                        111i32.into(),
+                        0i16.into(),
+                        1i32.into(),
+                        1i16.into(),
+                        222i32.into(),
+                        0i16.into(),
+                    ],
+                ),
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn update_rolls_back_on_failure() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![versus_match::Model {
+                match_id: 1,
+                team_red: 1,
+                team_blue: 2,
+                score_red: Some(5),
+                score_blue: Some(4),
+                match_type: MatchType::VersusMatch,
+            }]])
+            // Rescheduling fails after the result was updated
+            .append_query_errors([DbErr::Custom("injected failure".to_owned())])
+            .into_connection();
+
+        let details = update_match_request::Details::VersusMatch(versus(Some(5), Some(4)));
+        let new_date = NaiveDate::from_ymd_opt(2024, 6, 2)
+            .unwrap()
+            .and_hms_opt(18, 0, 0)
+            .unwrap();
+        let updated = update_match(&db, versus_match(), Some(details), Some(new_date)).await;
+
+        assert!(updated.is_err());
+        assert_eq!(
+            db.into_transaction_log(),
+            [rolled_back([
+                statement(
+                    r#"UPDATE "versus_match" SET "team_red" = $1, "team_blue" = $2, "score_red" = $3, "score_blue" = $4 WHERE "versus_match"."match_id" = $5 RETURNING "match_id", "team_red", "team_blue", "score_red", "score_blue", CAST("match_type" AS text)"#,
+                    [
+                        1i32.into(),
+                        2i32.into(),
+                        5i16.into(),
+                        4i16.into(),
+                        1i32.into(),
+                    ],
+                ),
+                statement(
+                    r#"UPDATE "match" SET "date" = $1 WHERE "match"."id" = $2 RETURNING "id", "tournament_id", "stage_order", "date", CAST("match_type" AS text)"#,
+                    [new_date.into(), 1i32.into()],
+                ),
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_rolls_back_on_failure() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([exec_ok(), exec_ok(), exec_ok(), exec_ok()])
+            // Deleting the versus match fails after everything referencing it was deleted
+            .append_exec_errors([DbErr::Custom("injected failure".to_owned())])
+            .into_connection();
+
+        let deleted = delete_match(&db, versus_match()).await;
+
+        assert!(deleted.is_err());
+        assert_eq!(
+            db.into_transaction_log(),
+            [rolled_back([
+                statement(
+                    r#"DELETE FROM "pick_ban" WHERE "pick_ban"."match_id" = $1"#,
+                    [1i32.into()],
+                ),
+                statement(
+                    r#"DELETE FROM "score" WHERE "score"."match_id" = $1"#,
+                    [1i32.into()],
+                ),
+                statement(
+                    r#"DELETE FROM "match_link" WHERE "match_link"."match_id" = $1"#,
+                    [1i32.into()],
+                ),
+                statement(
+                    r#"DELETE FROM "qualifier_run" WHERE "qualifier_run"."match_id" = $1"#,
+                    [1i32.into()],
+                ),
+                statement(
+                    r#"DELETE FROM "versus_match" WHERE "versus_match"."match_id" = $1"#,
+                    [1i32.into()],
+                ),
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn replacing_scores_rolls_back_on_failure() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([exec_ok()])
+            // Inserting the new scores fails after the old ones were deleted
+            .append_exec_errors([DbErr::Custom("injected failure".to_owned())])
+            .into_connection();
+
+        let scores = [score(2), score(3)].map(|score| score.into_active_model().reset_all());
+        let replaced = replace_scores(&db, 1, scores).await;
+
+        assert!(replaced.is_err());
+        assert_eq!(
+            db.into_transaction_log(),
+            [rolled_back([
+                statement(
+                    r#"DELETE FROM "score" WHERE "score"."match_id" = $1"#,
+                    [1i32.into()],
+                ),
+                statement(
+                    r#"INSERT INTO "score" ("player_id", "tournament_id", "stage_order", "bracket_order", "map_order", "match_id", "score", "accuracy", "max_combo", "count_300", "count_100", "count_50", "count_miss", "mods", "pass", "perfect", "scoring_type", "team", "slot") VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, CAST($17 AS scoring_type), CAST($18 AS lobby_team), $19), ($20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, CAST($36 AS scoring_type), CAST($37 AS lobby_team), $38)"#,
+                    [score(2), score(3)]
+                        .iter()
+                        .flat_map(|score| score::Column::iter().map(|column| score.get(column)))
+                        .collect::<Vec<_>>(),
+                ),
+            ])]
+        );
+    }
+}
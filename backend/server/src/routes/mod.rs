@@ -5,12 +5,18 @@ use tracing::error;
 
 #[allow(unused)]
 pub mod debug;
+pub mod matches;
 pub mod osu_user;
 pub mod pool;
+pub mod rating;
 pub mod stage;
+pub mod stats;
 pub mod tournament;
 pub mod osu_auth;
 pub mod score;
+pub mod team;
+#[cfg(test)]
+mod test_util;
 
 
 fn convert_start_end(
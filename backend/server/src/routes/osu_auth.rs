@@ -1,18 +1,25 @@
 use std::str::FromStr;
 
 use futures::TryFutureExt;
-use oauth2::{basic::BasicClient, reqwest::async_http_client, AuthorizationCode, TokenResponse};
+use oauth2::{
+    basic::{BasicClient, BasicTokenResponse},
+    reqwest::async_http_client,
+    AuthorizationCode, RefreshToken, TokenResponse,
+};
 use proto::osu_auth::{
     osu_auth_service_server::OsuAuthService, DeliverAuthCodeRequest, DeliverAuthCodeResponse,
-    RequestAuthCodeRequest, RequestAuthCodeResponse,
+    GetSessionsRequest, GetSessionsResponse, LogoutRequest, LogoutResponse, RefreshSessionRequest,
+    RefreshSessionResponse, RequestAuthCodeRequest, RequestAuthCodeResponse, RevokeSessionRequest,
+    RevokeSessionResponse, SessionInfo,
 };
 use tonic::{metadata::MetadataValue, Request, Response, Status};
 use tracing::error;
 use url::Url;
-use utils::{crypt::EncryptedToken, Cacheable};
+use utils::{cache::Cache, crypt::EncryptedToken, Cacheable};
 
 use crate::{
-    osu::auth::{OsuApiTokens, OsuAuthCode, OsuCsrfToken, Session},
+    osu::auth::{OsuApiTokens, OsuAuthCode, OsuCsrfToken, Session, API_TOKENS_LIFETIME},
+    permission::require_login,
     AppState,
 };
 
@@ -25,7 +32,7 @@ impl OsuAuthService for OsuAuthServiceImpl {
         &self,
         _request: Request<RequestAuthCodeRequest>,
     ) -> Result<Response<RequestAuthCodeResponse>, Status> {
-        let url = OsuAuthCode::request(&self.1, &self.0.redis)
+        let url = OsuAuthCode::request(&self.1, &self.0.cache)
             .map_err(|error| {
                 tracing::error!(%error, "error requesting auth code");
                 Status::internal(format!("error requesting auth code: {error}"))
@@ -46,12 +53,12 @@ impl OsuAuthService for OsuAuthServiceImpl {
 
         let auth_code = AuthorizationCode::new(request.auth_code.to_string());
         let csrf_token = request.state;
-        let redis = &self.0.redis;
+        let cache = &self.0.cache;
 
         let client = &self.1;
 
         // Check whether the CSRF token received from the server matches the one from the cache
-        let cached_csrf_token = OsuCsrfToken::uncache(redis, csrf_token.as_str())
+        let cached_csrf_token = OsuCsrfToken::uncache(cache, csrf_token.as_str())
             .map_err(|error| {
                 tracing::error!(%error, "error fetching CSRF token");
                 Status::internal(format!("error fetching CSRF token: {error}"))
@@ -78,39 +85,17 @@ impl OsuAuthService for OsuAuthServiceImpl {
             .await?;
 
         let access_token = token.access_token();
-        let refresh_token = token.refresh_token().ok_or_else(|| {
-            tracing::error!(error = "osu API did not send refresh token");
-            Status::internal("osu API did not send refresh token")
-        })?;
-        let expiry = token.expires_in().ok_or_else(|| {
-            tracing::error!(error = "osu API did not send token expiry");
-            Status::internal("osu API did not send token expiry")
-        })?;
-
         let user = request_user_data(access_token.secret().as_str()).await?;
         let user_id = user.user_id;
 
         tracing::info!(user_id, "successfully authenticated user");
 
         // All is well, so we save the accesss token and refresh token
-        OsuApiTokens {
-            user_id,
-            access_token: EncryptedToken::new(access_token.secret()).map_err(|error| {
-                error!("error caching access token: {error}");
-                Status::internal("error caching access token")
-            })?,
-            refresh_token: EncryptedToken::new(refresh_token.secret()).map_err(|error| {
-                error!("error caching refresh token: {error}");
-                Status::internal("error caching refresh token")
-            })?,
-        }
-        .cache(redis, Some(expiry.as_secs() as usize - 30))
-        .map_err(|error| Status::internal(format!("error caching access tokens: {error}")))
-        .await?;
+        cache_api_tokens(user_id, &token, None, cache).await?;
         let session = Session::new(user_id);
 
         session
-            .cache(redis, Some(600))
+            .store(cache)
             .await
             .map_err(|e| Status::internal(format!("error caching session token: {e}")))?;
 
@@ -125,6 +110,150 @@ impl OsuAuthService for OsuAuthServiceImpl {
         );
         Ok(resp)
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn refresh_session(
+        &self,
+        request: Request<RefreshSessionRequest>,
+    ) -> Result<Response<RefreshSessionResponse>, Status> {
+        let cache = &self.0.cache;
+        let mut session = require_login(&request)?.clone();
+        let user_id = session.osu_user_id;
+
+        let tokens = OsuApiTokens::get_cached(&user_id, cache)
+            .await
+            .map_err(|error| {
+                error!(%error, "error fetching osu api tokens");
+                Status::internal("error fetching osu api tokens")
+            })?
+            .ok_or_else(|| Status::unauthenticated("osu authorization expired"))?;
+        let refresh_token = tokens.refresh_token.decrypt_string().map_err(|error| {
+            error!(%error, "error decrypting refresh token");
+            Status::internal("error decrypting refresh token")
+        })?;
+        let refresh_token = RefreshToken::new(refresh_token);
+
+        // Rotate the access token
+        let token = self
+            .1
+            .exchange_refresh_token(&refresh_token)
+            .request_async(async_http_client)
+            .await
+            .map_err(|error| {
+                tracing::warn!(%error, user_id, "could not refresh token with osu API");
+                Status::unauthenticated("could not refresh osu authorization")
+            })?;
+        cache_api_tokens(user_id, &token, Some(&refresh_token), cache).await?;
+
+        session.refresh(cache).await.map_err(|error| {
+            error!(%error, "error caching session");
+            Status::internal("error refreshing session")
+        })?;
+
+        Ok(Response::new(RefreshSessionResponse {
+            expires_at: session.expires_at,
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn logout(
+        &self,
+        request: Request<LogoutRequest>,
+    ) -> Result<Response<LogoutResponse>, Status> {
+        let session = require_login(&request)?;
+        session.end(&self.0.cache).await.map_err(|error| {
+            error!(%error, "error deleting session");
+            Status::internal("error ending session")
+        })?;
+
+        Ok(Response::new(LogoutResponse {}))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_sessions(
+        &self,
+        request: Request<GetSessionsRequest>,
+    ) -> Result<Response<GetSessionsResponse>, Status> {
+        let current = require_login(&request)?;
+        let sessions = Session::all_of_user(current.osu_user_id, &self.0.cache)
+            .await
+            .map_err(|error| {
+                error!(%error, "error fetching sessions");
+                Status::internal("error fetching sessions")
+            })?;
+
+        Ok(Response::new(GetSessionsResponse {
+            sessions: sessions
+                .into_iter()
+                .map(|session| SessionInfo {
+                    current: session.session_id == current.session_id,
+                    id: session.public_id,
+                    created_at: session.created_at,
+                    expires_at: session.expires_at,
+                })
+                .collect(),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(id = request.get_ref().id))]
+    async fn revoke_session(
+        &self,
+        request: Request<RevokeSessionRequest>,
+    ) -> Result<Response<RevokeSessionResponse>, Status> {
+        let cache = &self.0.cache;
+        let user_id = require_login(&request)?.osu_user_id;
+        let id = &request.get_ref().id;
+
+        let sessions = Session::all_of_user(user_id, cache)
+            .await
+            .map_err(|error| {
+                error!(%error, "error fetching sessions");
+                Status::internal("error fetching sessions")
+            })?;
+        let session = sessions
+            .iter()
+            .find(|session| &session.public_id == id)
+            .ok_or_else(|| Status::not_found(format!("session {id} not found")))?;
+        session.end(cache).await.map_err(|error| {
+            error!(%error, "error deleting session");
+            Status::internal("error ending session")
+        })?;
+
+        Ok(Response::new(RevokeSessionResponse {}))
+    }
+}
+
+/// Caches the osu api tokens of a user.
+/// If the token response does not contain a new refresh token, the previous one is kept.
+async fn cache_api_tokens(
+    user_id: u32,
+    token: &BasicTokenResponse,
+    previous_refresh_token: Option<&RefreshToken>,
+    cache: &Cache,
+) -> tonic::Result<()> {
+    let refresh_token = token
+        .refresh_token()
+        .or(previous_refresh_token)
+        .ok_or_else(|| {
+            tracing::error!(error = "osu API did not send refresh token");
+            Status::internal("osu API did not send refresh token")
+        })?;
+
+    OsuApiTokens {
+        user_id,
+        access_token: EncryptedToken::new(token.access_token().secret()).map_err(|error| {
+            error!("error caching access token: {error}");
+            Status::internal("error caching access token")
+        })?,
+        refresh_token: EncryptedToken::new(refresh_token.secret()).map_err(|error| {
+            error!("error caching refresh token: {error}");
+            Status::internal("error caching refresh token")
+        })?,
+    }
+    // The refresh token outlives the access token, so the tokens are kept longer
+    .cache(cache, Some(API_TOKENS_LIFETIME))
+    .map_err(|error| Status::internal(format!("error caching access tokens: {error}")))
+    .await
 }
 
 #[tracing::instrument(skip_all)]
@@ -1,16 +1,18 @@
 use futures::TryFutureExt;
-use proto::osu::{api::get_user, osu_user_service_server::OsuUserService, GetUserRequest, GetUserResponse};
+use proto::osu::{
+    api::get_user, osu_user_service_server::OsuUserService, GetUserRequest, GetUserResponse,
+};
 use tonic::{async_trait, Request, Response, Status};
-use utils::Cacheable;
+use utils::{cache::Cache, Cacheable};
 
-use crate::{osu::auth::Session, AppState, RedisConnectionPool};
+use crate::{osu::auth::Session, AppState};
 
 pub struct OsuUserServiceImpl(pub AppState);
 
 #[tracing::instrument(skip_all)]
 pub async fn get_authenticated_user<T: std::fmt::Debug>(
     request: &Request<T>,
-    redis: &RedisConnectionPool,
+    cache: &Cache,
 ) -> tonic::Result<Option<Session>> {
     let Some(tok) = request.metadata().get("authorization") else {
         tracing::debug!(?request, "no user logged in");
@@ -20,7 +22,7 @@ pub async fn get_authenticated_user<T: std::fmt::Debug>(
     let token_string = String::from_utf8_lossy(tok.as_bytes());
     // Remove "Bearer " from the token
     let token_string = &token_string[7..];
-    let Some(session) = Session::get_cached(token_string, redis)
+    let Some(session) = Session::get_cached(token_string, cache)
         .map_err(|e| {
             tracing::error!(error = %e, "could not get session");
             Status::internal("error getting session")
@@ -42,16 +44,14 @@ impl OsuUserService for OsuUserServiceImpl {
         request: Request<GetUserRequest>,
     ) -> Result<Response<GetUserResponse>, Status> {
         let Some(Session { osu_user_id, .. }) =
-            get_authenticated_user(&request, &self.0.redis).await?
+            get_authenticated_user(&request, &self.0.cache).await?
         else {
             tracing::debug!("no user logged in");
             return Ok(Response::new(GetUserResponse { user: None }));
         };
 
-        let user = get_user(&self.0.redis, self.0.osu.as_ref(), osu_user_id)
+        let user = get_user(&self.0.cache, &self.0.osu, osu_user_id)
             .await
-            .map(proto::osu::User::from)
-            .map(Option::Some)
             .map_err(|e| {
                 tracing::error!(error = %e, "could not get osu user");
                 Status::internal("could not get osu user")
@@ -1,28 +1,390 @@
+use std::collections::{HashMap, VecDeque};
+
 use super::tournament::find_stage;
-use crate::AppState;
-use futures::TryStreamExt;
-use futures::{stream::FuturesOrdered, TryFutureExt};
-use model::{pool_bracket, pool_map};
-use proto::osu::api::get_map;
+use crate::{
+    permission::{require_permission, Permission},
+    AppState,
+};
+use futures::TryFutureExt;
+use model::{pick_ban, pool_bracket, pool_map, score, stage, tournament};
+use proto::osu::api::get_maps;
 use proto::{
-    keys::StageKey,
+    keys::{PoolBracketKey, PoolMapKey, StageKey},
     pool::{
         pool_service_server::PoolService, update_pool_bracket_request::MapIds,
         CreatePoolBracketRequest, CreatePoolBracketResponse, DeletePoolBracketRequest,
         DeletePoolBracketResponse, DeletePoolRequest, DeletePoolResponse, GetPoolBracketRequest,
-        GetPoolBracketResponse, GetPoolRequest, GetPoolResponse, Pool, PoolBracketMaps,
+        GetPoolBracketResponse, GetPoolRequest, GetPoolResponse, MovePoolMapRequest,
+        MovePoolMapResponse, Pool, PoolBracketMaps, SwapPoolMapsRequest, SwapPoolMapsResponse,
         UpdatePoolBracketRequest, UpdatePoolBracketResponse,
     },
 };
 use sea_orm::{
-    sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, FromQueryResult,
-    IntoActiveModel, ModelTrait, QueryFilter, QueryOrder, QuerySelect,
+    sea_query::Expr, ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait,
+    DatabaseConnection, DbErr, EntityTrait, FromQueryResult, IntoActiveModel, ModelTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
 };
 use tonic::{Request, Response, Status};
 use utils::LogStatus;
 
 pub struct PoolServiceImpl(pub AppState);
 
+/// The order a bracket or map is parked at while the others are moved out of its way
+const PARKED_ORDER: i16 = i16::MAX;
+
+/// Finds a map in a pool along with the tournament and stage it belongs to
+async fn find_pool_map(
+    pool_map_key: &PoolMapKey,
+    db: &DatabaseConnection,
+) -> tonic::Result<(tournament::Model, stage::Model, pool_map::Model)> {
+    let bracket_key = pool_map_key
+        .bracket_key
+        .as_ref()
+        .ok_or_else(|| Status::invalid_argument("missing bracket key in pool map key"))?;
+    let stage_key = bracket_key
+        .stage_key
+        .as_ref()
+        .ok_or_else(|| Status::invalid_argument("missing stage key in bracket key"))?;
+    let (tournament, stage) = find_stage(stage_key, db).await?;
+
+    let map = pool_map::Entity::find_by_id((
+        tournament.id,
+        stage.stage_order,
+        bracket_key.bracket_order as i16,
+        pool_map_key.map_order as i16,
+    ))
+    .one(db)
+    .await
+    .map_err(|e| Status::internal(format!("error fetching pool map: {e}")))?
+    .ok_or_else(|| {
+        Status::not_found(format!(
+            "map {} in bracket {} of stage {} in tournament {} does not exist",
+            pool_map_key.map_order, bracket_key.bracket_order, stage.stage_order, tournament.id
+        ))
+    })?;
+
+    Ok((tournament, stage, map))
+}
+
+/// Changes the order of a single bracket in a pool.
+/// Maps, scores and picks follow the bracket, since all keys referencing a bracket cascade on
+/// updates.
+async fn set_bracket_order(
+    db: &impl ConnectionTrait,
+    tournament_id: i32,
+    stage_order: i16,
+    from: i16,
+    to: i16,
+) -> Result<(), DbErr> {
+    pool_bracket::Entity::update_many()
+        .col_expr(pool_bracket::Column::BracketOrder, Expr::value(to))
+        .filter(pool_bracket::Column::TournamentId.eq(tournament_id))
+        .filter(pool_bracket::Column::StageOrder.eq(stage_order))
+        .filter(pool_bracket::Column::BracketOrder.eq(from))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Moves a bracket to a new position, shifting the brackets in between by one place.
+/// This should be run in a transaction, so that no one sees the intermediate states.
+async fn move_bracket(
+    db: &impl ConnectionTrait,
+    tournament_id: i32,
+    stage_order: i16,
+    from: i16,
+    to: i16,
+) -> Result<(), DbErr> {
+    if from == to {
+        return Ok(());
+    }
+
+    set_bracket_order(db, tournament_id, stage_order, from, PARKED_ORDER).await?;
+    if from < to {
+        for order in from + 1..=to {
+            set_bracket_order(db, tournament_id, stage_order, order, order - 1).await?;
+        }
+    } else {
+        for order in (to..from).rev() {
+            set_bracket_order(db, tournament_id, stage_order, order, order + 1).await?;
+        }
+    }
+    set_bracket_order(db, tournament_id, stage_order, PARKED_ORDER, to).await
+}
+
+/// Changes the bracket and order of a single map in a pool. The positions are given as pairs of
+/// bracket order and map order.
+/// Scores and picks follow the map, since all keys referencing a map cascade on updates.
+async fn set_map_position(
+    db: &impl ConnectionTrait,
+    tournament_id: i32,
+    stage_order: i16,
+    (from_bracket, from_map): (i16, i16),
+    (to_bracket, to_map): (i16, i16),
+) -> Result<(), DbErr> {
+    pool_map::Entity::update_many()
+        .col_expr(pool_map::Column::BracketOrder, Expr::value(to_bracket))
+        .col_expr(pool_map::Column::MapOrder, Expr::value(to_map))
+        .filter(pool_map::Column::TournamentId.eq(tournament_id))
+        .filter(pool_map::Column::StageOrder.eq(stage_order))
+        .filter(pool_map::Column::BracketOrder.eq(from_bracket))
+        .filter(pool_map::Column::MapOrder.eq(from_map))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Counts the maps in a bracket, not including a parked map
+async fn count_maps(
+    db: &impl ConnectionTrait,
+    tournament_id: i32,
+    stage_order: i16,
+    bracket_order: i16,
+) -> Result<u64, DbErr> {
+    pool_map::Entity::find()
+        .filter(pool_map::Column::TournamentId.eq(tournament_id))
+        .filter(pool_map::Column::StageOrder.eq(stage_order))
+        .filter(pool_map::Column::BracketOrder.eq(bracket_order))
+        .filter(pool_map::Column::MapOrder.ne(PARKED_ORDER))
+        .count(db)
+        .await
+}
+
+/// Moves a map to a new position in the pool. The maps after its old position move up by one
+/// place, and the maps from its new position on move down by one place.
+/// This should be run in a transaction, so that no one sees the intermediate states.
+async fn move_pool_map(
+    db: &impl ConnectionTrait,
+    tournament_id: i32,
+    stage_order: i16,
+    from: (i16, i16),
+    to: (i16, i16),
+) -> Result<(), DbErr> {
+    if from == to {
+        return Ok(());
+    }
+    let (from_bracket, from_map) = from;
+    let (to_bracket, to_map) = to;
+
+    let parked = (from_bracket, PARKED_ORDER);
+    set_map_position(db, tournament_id, stage_order, from, parked).await?;
+
+    // Close the gap in the old bracket
+    let remaining = count_maps(db, tournament_id, stage_order, from_bracket).await? as i16;
+    for order in from_map + 1..=remaining {
+        let (old, new) = ((from_bracket, order), (from_bracket, order - 1));
+        set_map_position(db, tournament_id, stage_order, old, new).await?;
+    }
+
+    // Make room in the new bracket
+    let existing = count_maps(db, tournament_id, stage_order, to_bracket).await? as i16;
+    for order in (to_map..existing).rev() {
+        let (old, new) = ((to_bracket, order), (to_bracket, order + 1));
+        set_map_position(db, tournament_id, stage_order, old, new).await?;
+    }
+
+    set_map_position(db, tournament_id, stage_order, parked, to).await
+}
+
+/// Swaps the positions of two maps in a pool.
+/// This should be run in a transaction, so that no one sees the intermediate states.
+async fn swap_pool_maps(
+    db: &impl ConnectionTrait,
+    tournament_id: i32,
+    stage_order: i16,
+    first: (i16, i16),
+    second: (i16, i16),
+) -> Result<(), DbErr> {
+    if first == second {
+        return Ok(());
+    }
+
+    let parked = (first.0, PARKED_ORDER);
+    set_map_position(db, tournament_id, stage_order, first, parked).await?;
+    set_map_position(db, tournament_id, stage_order, second, first).await?;
+    set_map_position(db, tournament_id, stage_order, parked, second).await
+}
+
+/// Replaces the maps in a bracket with the given maps.
+/// Maps that stay in the bracket are moved to their new position, so they keep their scores and
+/// picks. Maps that are removed from the bracket may not have any scores or picks.
+/// This should be run in a transaction, so that a failure leaves the bracket untouched.
+async fn replace_maps(
+    db: &impl ConnectionTrait,
+    tournament_id: i32,
+    stage_order: i16,
+    bracket_order: i16,
+    map_ids: &[u32],
+) -> tonic::Result<()> {
+    use ActiveValue as A;
+
+    let old_maps = pool_map::Entity::find()
+        .filter(pool_map::Column::TournamentId.eq(tournament_id))
+        .filter(pool_map::Column::StageOrder.eq(stage_order))
+        .filter(pool_map::Column::BracketOrder.eq(bracket_order))
+        .order_by_asc(pool_map::Column::MapOrder)
+        .all(db)
+        .await
+        .map_err(|e| Status::internal(format!("error fetching old pool maps: {e}")))?;
+
+    // Park the old maps at the end so they don't collide with the new map orders
+    let mut parked_maps = HashMap::<i64, VecDeque<i16>>::new();
+    for map in old_maps {
+        let parked = PARKED_ORDER - map.map_order;
+        set_map_position(
+            db,
+            tournament_id,
+            stage_order,
+            (bracket_order, map.map_order),
+            (bracket_order, parked),
+        )
+        .await
+        .map_err(|e| Status::internal(format!("error moving pool map: {e}")))?;
+        parked_maps.entry(map.map_id).or_default().push_back(parked);
+    }
+
+    // Move maps that are still in the bracket to their new place and insert the others
+    for (map_order, &map_id) in map_ids.iter().enumerate() {
+        let map_order = map_order as i16;
+        match parked_maps
+            .get_mut(&(map_id as i64))
+            .and_then(VecDeque::pop_front)
+        {
+            Some(parked) => set_map_position(
+                db,
+                tournament_id,
+                stage_order,
+                (bracket_order, parked),
+                (bracket_order, map_order),
+            )
+            .await
+            .map_err(|e| Status::internal(format!("error moving pool map: {e}")))?,
+            None => {
+                pool_map::ActiveModel {
+                    tournament_id: A::Set(tournament_id),
+                    stage_order: A::Set(stage_order),
+                    bracket_order: A::Set(bracket_order),
+                    map_order: A::Set(map_order),
+                    map_id: A::Set(map_id as i64),
+                }
+                .insert(db)
+                .await
+                .map_err(|e| Status::internal(format!("error inserting pool map: {e}")))?;
+            }
+        }
+    }
+
+    // Delete the maps that were removed, unless results were already recorded on them
+    for (map_id, parked) in parked_maps {
+        for map_order in parked {
+            let key = (tournament_id, stage_order, bracket_order, map_order);
+            let scores = score::Entity::find()
+                .filter(score::Column::TournamentId.eq(tournament_id))
+                .filter(score::Column::StageOrder.eq(stage_order))
+                .filter(score::Column::BracketOrder.eq(bracket_order))
+                .filter(score::Column::MapOrder.eq(map_order))
+                .count(db)
+                .await
+                .map_err(|e| Status::internal(format!("error fetching scores: {e}")))?;
+            let pick_bans = pick_ban::Entity::find()
+                .filter(pick_ban::Column::TournamentId.eq(tournament_id))
+                .filter(pick_ban::Column::StageOrder.eq(stage_order))
+                .filter(pick_ban::Column::BracketOrder.eq(bracket_order))
+                .filter(pick_ban::Column::MapOrder.eq(map_order))
+                .count(db)
+                .await
+                .map_err(|e| Status::internal(format!("error fetching picks and bans: {e}")))?;
+            if scores > 0 || pick_bans > 0 {
+                return Err(Status::failed_precondition(format!(
+                    "map {map_id} can't be removed from the bracket, since it already has \
+                     {scores} scores and {pick_bans} picks or bans"
+                )));
+            }
+
+            pool_map::Entity::delete_by_id(key)
+                .exec(db)
+                .await
+                .map_err(|e| Status::internal(format!("error deleting pool map: {e}")))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the changes of a bracket update request to the bracket with the given key.
+/// This happens in a single transaction, so either all of the changes are applied or none of them.
+/// Returns the bracket's order after the update.
+async fn apply_bracket_update(
+    db: &DatabaseConnection,
+    (tournament_id, stage_order, bracket_order): (i32, i16, i16),
+    request: UpdatePoolBracketRequest,
+) -> tonic::Result<u32> {
+    use ActiveValue as A;
+    let txn = db
+        .begin()
+        .map_err(|e| Status::internal(format!("could not start transaction: {e}")))
+        .await?;
+
+    let mut bracket = pool_bracket::Entity::find_by_id((tournament_id, stage_order, bracket_order))
+        .one(&txn)
+        .map_err(|e| Status::not_found(format!("error fetching pool bracket: {e}")))
+        .await?
+        .ok_or_else(|| {
+            Status::not_found(format!(
+                "bracket {bracket_order} in stage {stage_order} of tournament {tournament_id} \
+                 does not exist"
+            ))
+        })?
+        .into_active_model();
+
+    // Update bracket name
+    if let Some(name) = request.name {
+        bracket.name = A::Set(name);
+        bracket
+            .update(&txn)
+            .map_err(|e| Status::internal(format!("error updating pool bracket: {e}")))
+            .await?;
+    }
+
+    // Update maps
+    if let Some(MapIds { maps }) = request.maps {
+        replace_maps(&txn, tournament_id, stage_order, bracket_order, &maps).await?;
+    }
+
+    // Update bracket order
+    let mut bracket_order_after = bracket_order as u32;
+    if let Some(new_bracket_order) = request.bracket_order {
+        let bracket_count = pool_bracket::Entity::find()
+            .filter(pool_bracket::Column::TournamentId.eq(tournament_id))
+            .filter(pool_bracket::Column::StageOrder.eq(stage_order))
+            .count(&txn)
+            .map_err(|e| Status::internal(format!("error counting pool brackets: {e}")))
+            .await?;
+        if new_bracket_order as u64 >= bracket_count {
+            return Err(Status::invalid_argument(format!(
+                "bracket order {new_bracket_order} is out of range for {bracket_count} brackets"
+            )));
+        }
+
+        move_bracket(
+            &txn,
+            tournament_id,
+            stage_order,
+            bracket_order,
+            new_bracket_order as i16,
+        )
+        .map_err(|e| Status::internal(format!("error reordering pool brackets: {e}")))
+        .await?;
+        bracket_order_after = new_bracket_order;
+    }
+
+    txn.commit()
+        .map_err(|e| Status::internal(format!("could not commit bracket update: {e}")))
+        .await?;
+
+    Ok(bracket_order_after)
+}
+
 #[tonic::async_trait]
 impl PoolService for PoolServiceImpl {
     async fn get(
@@ -48,29 +410,28 @@ impl PoolService for PoolServiceImpl {
             .map_err(|e| Status::internal(format!("error fetching pool: {e}")))
             .error_status()?;
 
+        // Get the map data for the whole pool at once
+        let map_ids = pool
+            .iter()
+            .flat_map(|(_, maps)| maps.iter().map(|map| map.map_id as u32))
+            .collect::<Vec<_>>();
+        let mut map_data = get_maps(&self.0.cache, &self.0.sqlx, &self.0.osu, &map_ids)
+            .map_err(|e| Status::internal(format!("error fetching map info: {e}")))
+            .await
+            .error_status()?
+            .into_iter();
+
+        // Transform the brackets into the on-the-wire format
         let brackets = pool
             .into_iter()
-            .map(|(bracket, maps)| {
-                // Get the map data from the osu api for each map
-                maps.into_iter()
-                    .map(|map| get_map(&self.0.redis, self.0.osu.as_ref(), map.map_id as u32))
-                    .collect::<FuturesOrdered<_>>()
-                    // Transform the beatmaps into the on-the-wire format
-                    .try_collect::<Vec<_>>()
-                    .map_ok(|maps| (bracket, maps))
-                    // Transform the brackets into the on-the-wire format
-                    .map_ok(|(bracket, maps)| proto::pool::PoolBracket {
-                        bracket_order: bracket.bracket_order as u32,
-                        name: bracket.name,
-                        maps: Some(PoolBracketMaps { maps }),
-                    })
+            .map(|(bracket, maps)| proto::pool::PoolBracket {
+                bracket_order: bracket.bracket_order as u32,
+                name: bracket.name,
+                maps: Some(PoolBracketMaps {
+                    maps: map_data.by_ref().take(maps.len()).collect(),
+                }),
             })
-            // Collect each fetched bracket
-            .collect::<FuturesOrdered<_>>()
-            .try_collect::<Vec<_>>()
-            .map_err(|e| Status::internal(format!("error fetching map info: {e}")))
-            .await
-            .error_status()?;
+            .collect();
 
         Ok(Response::new(GetPoolResponse {
             pool: Some(Pool { brackets }),
@@ -82,13 +443,14 @@ impl PoolService for PoolServiceImpl {
         request: Request<DeletePoolRequest>,
     ) -> Result<Response<DeletePoolResponse>, Status> {
         let db = &self.0.db;
-        let request = request.into_inner();
         let stage_key = request
+            .get_ref()
             .stage_key
             .ok_or_else(|| Status::invalid_argument("missing stage key"))?;
 
         // Test if the tournament and stage exist
         let (tournament, stage) = find_stage(&stage_key, db).await?;
+        require_permission(&request, tournament.id, Permission::EditPool, db).await?;
 
         pool_bracket::Entity::delete_many()
             .filter(pool_bracket::Column::TournamentId.eq(tournament.id))
@@ -107,19 +469,21 @@ impl PoolService for PoolServiceImpl {
     ) -> Result<Response<CreatePoolBracketResponse>, Status> {
         use ActiveValue as A;
         let db = &self.0.db;
-        let request = request.into_inner();
         let stage_key = request
+            .get_ref()
             .stage_key
             .ok_or_else(|| Status::invalid_argument("missing stage key"))?;
 
+        // Test if the tournament and stage exist
+        let (tournament, stage) = find_stage(&stage_key, db).await.error_status()?;
+        require_permission(&request, tournament.id, Permission::EditPool, db).await?;
+        let request = request.into_inner();
+
         // We don't allow empty bracket names
         if request.name.trim().is_empty() {
             return Err(Status::invalid_argument("empty bracket name"));
         }
 
-        // Test if the tournament and stage exist
-        let (tournament, stage) = find_stage(&stage_key, db).await.error_status()?;
-
         #[allow(unused)]
         #[derive(FromQueryResult, Debug)]
         struct MaxBracket {
@@ -219,12 +583,9 @@ impl PoolService for PoolServiceImpl {
             .await
             .error_status()?;
 
-        let maps = maps
-            .into_iter()
-            .map(|map| get_map(&self.0.redis, self.0.osu.as_ref(), map.map_id as u32))
-            .collect::<FuturesOrdered<_>>()
+        let map_ids = maps.iter().map(|map| map.map_id as u32).collect::<Vec<_>>();
+        let maps = get_maps(&self.0.cache, &self.0.sqlx, &self.0.osu, &map_ids)
             .map_err(|e| Status::internal(format!("error fetching map info: {e}")))
-            .try_collect::<Vec<_>>()
             .await
             .error_status()?;
 
@@ -241,10 +602,9 @@ impl PoolService for PoolServiceImpl {
         &self,
         request: Request<UpdatePoolBracketRequest>,
     ) -> Result<Response<UpdatePoolBracketResponse>, Status> {
-        use ActiveValue as A;
         let db = &self.0.db;
-        let request = request.into_inner();
         let pool_bracket_key = request
+            .get_ref()
             .key
             .ok_or_else(|| Status::invalid_argument("missing pool bracket key"))?;
         let stage_key = pool_bracket_key
@@ -253,69 +613,40 @@ impl PoolService for PoolServiceImpl {
             .ok_or_else(|| Status::invalid_argument("missing stage key in pool bracket key"))?;
         // Test if the tournament and stage exist
         let (tournament, stage) = find_stage(stage_key, db).await?;
+        require_permission(&request, tournament.id, Permission::EditPool, db).await?;
+        let request = request.into_inner();
 
-        let mut bracket = pool_bracket::Entity::find_by_id((
-            tournament.id,
-            stage.stage_order,
-            pool_bracket_key.bracket_order as i16,
-        ))
-        .one(db)
-        .map_err(|e| Status::not_found(format!("error fetching pool bracket: {e}")))
-        .await?
-        .ok_or_else(|| {
-            Status::not_found(format!(
-                "bracket {} in stage {} of tournament {} does not exist",
-                pool_bracket_key.bracket_order, stage.stage_order, tournament.id
-            ))
-        })?
-        .into_active_model();
-
-        // Update bracket name
-        if let Some(name) = request.name {
-            // We don't allow empty bracket names
-            if name.trim().is_empty() {
-                return Err(Status::invalid_argument("empty bracket name"));
-            }
-            bracket.name = A::Set(name);
-        }
-
-        // Update bracket order
-        if let Some(bracket_order) = request.bracket_order {
-            bracket.bracket_order = A::Set(bracket_order as i16);
+        // We don't allow empty bracket names
+        if request
+            .name
+            .as_ref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return Err(Status::invalid_argument("empty bracket name"));
         }
 
-        // Update maps
-        if let Some(MapIds { maps }) = request.maps {
-            let tournament_id = tournament.id;
-            let stage_order = stage_key.stage_order as i16;
-            let bracket_order = pool_bracket_key.bracket_order as i16;
-
-            // Delete all old maps
-            pool_map::Entity::delete_many()
-                .filter(pool_map::Column::TournamentId.eq(tournament_id))
-                .filter(pool_map::Column::StageOrder.eq(stage_order))
-                .filter(pool_map::Column::BracketOrder.eq(bracket_order))
-                .exec(db)
-                .map_err(|e| Status::internal(format!("error deleting old pool maps: {e}")))
+        // Make sure that the new maps exist and are stored in the database, so that the pool can
+        // always be joined with their data
+        if let Some(MapIds { ref maps }) = request.maps {
+            get_maps(&self.0.cache, &self.0.sqlx, &self.0.osu, maps)
+                .map_err(|e| Status::internal(format!("error fetching map info: {e}")))
                 .await
                 .error_status()?;
-
-            // Insert the new maps
-            pool_map::Entity::insert_many(maps.into_iter().enumerate().map(
-                |(map_order, map_id)| pool_map::ActiveModel {
-                    tournament_id: A::Set(tournament_id),
-                    stage_order: A::Set(stage_order),
-                    bracket_order: A::Set(bracket_order),
-                    map_order: A::Set(map_order as i16),
-                    map_id: A::Set(map_id as i64),
-                },
-            ))
-            .exec(db)
-            .map_err(|e| Status::internal(format!("error inserting pool maps: {e}")))
-            .await
-            .error_status()?;
         }
 
+        let mut pool_bracket_key = pool_bracket_key;
+        pool_bracket_key.bracket_order = apply_bracket_update(
+            db,
+            (
+                tournament.id,
+                stage.stage_order,
+                pool_bracket_key.bracket_order as i16,
+            ),
+            request,
+        )
+        .await
+        .error_status()?;
+
         // We want to get the update bracket back
         let GetPoolBracketResponse { bracket } = self
             .get_bracket(Request::new(GetPoolBracketRequest {
@@ -333,8 +664,8 @@ impl PoolService for PoolServiceImpl {
         request: Request<DeletePoolBracketRequest>,
     ) -> Result<Response<DeletePoolBracketResponse>, Status> {
         let db = &self.0.db;
-        let request = request.into_inner();
         let pool_bracket_key = request
+            .get_ref()
             .key
             .ok_or_else(|| Status::invalid_argument("missing pool bracket key"))?;
         let stage_key = pool_bracket_key
@@ -343,16 +674,21 @@ impl PoolService for PoolServiceImpl {
             .ok_or_else(|| Status::invalid_argument("missing stage key in pool bracket key"))?;
         // Test if the tournament and stage exist
         let (tournament, stage) = find_stage(stage_key, db).await.error_status()?;
+        require_permission(&request, tournament.id, Permission::EditPool, db).await?;
 
-        let delete_res = pool_bracket::Entity::delete_by_id((
-            tournament.id,
-            stage.stage_order,
-            pool_bracket_key.bracket_order as i16,
-        ))
-        .exec(db)
-        .map_err(|e| Status::not_found(format!("error fetching pool bracket: {e}")))
-        .await
-        .error_status()?;
+        let txn = db
+            .begin()
+            .map_err(|e| Status::internal(format!("could not start transaction: {e}")))
+            .await
+            .error_status()?;
+
+        let bracket_order = pool_bracket_key.bracket_order as i16;
+        let delete_res =
+            pool_bracket::Entity::delete_by_id((tournament.id, stage.stage_order, bracket_order))
+                .exec(&txn)
+                .map_err(|e| Status::not_found(format!("error fetching pool bracket: {e}")))
+                .await
+                .error_status()?;
 
         if delete_res.rows_affected == 0 {
             return Err(Status::not_found(format!(
@@ -361,6 +697,280 @@ impl PoolService for PoolServiceImpl {
             )));
         }
 
+        // Close the gap left by the bracket
+        let later_brackets = pool_bracket::Entity::find()
+            .filter(pool_bracket::Column::TournamentId.eq(tournament.id))
+            .filter(pool_bracket::Column::StageOrder.eq(stage.stage_order))
+            .filter(pool_bracket::Column::BracketOrder.gt(bracket_order))
+            .order_by_asc(pool_bracket::Column::BracketOrder)
+            .all(&txn)
+            .map_err(|e| Status::internal(format!("error fetching pool brackets: {e}")))
+            .await
+            .error_status()?;
+        for bracket in later_brackets {
+            let order = bracket.bracket_order;
+            set_bracket_order(&txn, tournament.id, stage.stage_order, order, order - 1)
+                .map_err(|e| Status::internal(format!("error reordering pool brackets: {e}")))
+                .await
+                .error_status()?;
+        }
+
+        txn.commit()
+            .map_err(|e| Status::internal(format!("could not commit bracket deletion: {e}")))
+            .await
+            .error_status()?;
+
         Ok(Response::new(DeletePoolBracketResponse {}))
     }
+
+    async fn move_map(
+        &self,
+        request: Request<MovePoolMapRequest>,
+    ) -> Result<Response<MovePoolMapResponse>, Status> {
+        let db = &self.0.db;
+        let pool_map_key = request
+            .get_ref()
+            .key
+            .ok_or_else(|| Status::invalid_argument("missing pool map key"))?;
+        // Test if the map exists
+        let (tournament, stage, map) = find_pool_map(&pool_map_key, db).await.error_status()?;
+        require_permission(&request, tournament.id, Permission::EditPool, db).await?;
+        let request = request.into_inner();
+
+        let to_bracket = request.bracket_order as i16;
+        let to_map = request.map_order as i16;
+
+        // The bracket and its maps are checked inside the transaction, so that maps that are
+        // added or removed concurrently can't make the range check stale
+        let txn = db
+            .begin()
+            .map_err(|e| Status::internal(format!("could not start transaction: {e}")))
+            .await
+            .error_status()?;
+
+        // Test if the bracket to move the map into exists
+        pool_bracket::Entity::find_by_id((tournament.id, stage.stage_order, to_bracket))
+            .lock_exclusive()
+            .one(&txn)
+            .map_err(|e| Status::internal(format!("error fetching pool bracket: {e}")))
+            .await?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "bracket {} in stage {} of tournament {} does not exist",
+                    to_bracket, stage.stage_order, tournament.id
+                ))
+            })
+            .error_status()?;
+
+        // The map can be appended to another bracket, but within its own bracket, the last place
+        // is the one of the last map
+        let map_count = count_maps(&txn, tournament.id, stage.stage_order, to_bracket)
+            .map_err(|e| Status::internal(format!("error counting pool maps: {e}")))
+            .await
+            .error_status()?;
+        let max_map_order = if to_bracket == map.bracket_order {
+            map_count.saturating_sub(1)
+        } else {
+            map_count
+        };
+        if to_map as u64 > max_map_order {
+            return Err(Status::invalid_argument(format!(
+                "map order {to_map} is out of range for bracket {to_bracket}"
+            )));
+        }
+
+        move_pool_map(
+            &txn,
+            tournament.id,
+            stage.stage_order,
+            (map.bracket_order, map.map_order),
+            (to_bracket, to_map),
+        )
+        .map_err(|e| Status::internal(format!("error moving pool map: {e}")))
+        .await
+        .error_status()?;
+        txn.commit()
+            .map_err(|e| Status::internal(format!("could not commit map move: {e}")))
+            .await
+            .error_status()?;
+
+        Ok(Response::new(MovePoolMapResponse {
+            key: Some(PoolMapKey {
+                bracket_key: Some(PoolBracketKey {
+                    stage_key: pool_map_key.bracket_key.and_then(|key| key.stage_key),
+                    bracket_order: to_bracket as u32,
+                }),
+                map_order: to_map as u32,
+            }),
+        }))
+    }
+
+    async fn swap_maps(
+        &self,
+        request: Request<SwapPoolMapsRequest>,
+    ) -> Result<Response<SwapPoolMapsResponse>, Status> {
+        let db = &self.0.db;
+        let first_key = request
+            .get_ref()
+            .first
+            .ok_or_else(|| Status::invalid_argument("missing first pool map key"))?;
+        let second_key = request
+            .get_ref()
+            .second
+            .ok_or_else(|| Status::invalid_argument("missing second pool map key"))?;
+        // Test if the maps exist
+        let (tournament, stage, first) = find_pool_map(&first_key, db).await.error_status()?;
+        let (_, _, second) = find_pool_map(&second_key, db).await.error_status()?;
+        if (first.tournament_id, first.stage_order) != (second.tournament_id, second.stage_order) {
+            return Err(Status::invalid_argument(
+                "only maps in the same pool can be swapped",
+            ));
+        }
+        require_permission(&request, tournament.id, Permission::EditPool, db).await?;
+
+        let txn = db
+            .begin()
+            .map_err(|e| Status::internal(format!("could not start transaction: {e}")))
+            .await
+            .error_status()?;
+        swap_pool_maps(
+            &txn,
+            tournament.id,
+            stage.stage_order,
+            (first.bracket_order, first.map_order),
+            (second.bracket_order, second.map_order),
+        )
+        .map_err(|e| Status::internal(format!("error swapping pool maps: {e}")))
+        .await
+        .error_status()?;
+        txn.commit()
+            .map_err(|e| Status::internal(format!("could not commit map swap: {e}")))
+            .await
+            .error_status()?;
+
+        Ok(Response::new(SwapPoolMapsResponse {}))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sea_orm::{DatabaseBackend, DbErr, MockDatabase, Statement};
+    use tonic::Code;
+
+    use super::*;
+    use crate::routes::test_util::{count, exec_ok, rolled_back, statement};
+
+    fn bracket() -> pool_bracket::Model {
+        pool_bracket::Model {
+            tournament_id: 1,
+            stage_order: 0,
+            bracket_order: 0,
+            name: "NM".to_owned(),
+        }
+    }
+
+    fn pool_map(map_order: i16, map_id: i64) -> pool_map::Model {
+        pool_map::Model {
+            tournament_id: 1,
+            stage_order: 0,
+            bracket_order: 0,
+            map_order,
+            map_id,
+        }
+    }
+
+    fn update(maps: Vec<u32>) -> UpdatePoolBracketRequest {
+        UpdatePoolBracketRequest {
+            key: None,
+            name: None,
+            bracket_order: None,
+            maps: Some(MapIds { maps }),
+        }
+    }
+
+    /// The statements that load the test bracket with one map and park that map
+    fn park_first_map() -> Vec<Statement> {
+        vec![
+            statement(
+                r#"SELECT "pool_bracket"."tournament_id", "pool_bracket"."stage_order", "pool_bracket"."bracket_order", "pool_bracket"."name" FROM "pool_bracket" WHERE "pool_bracket"."tournament_id" = $1 AND "pool_bracket"."stage_order" = $2 AND "pool_bracket"."bracket_order" = $3 LIMIT $4"#,
+                [1i32.into(), 0i16.into(), 0i16.into(), 1u64.into()],
+            ),
+            statement(
+                r#"SELECT "pool_map"."tournament_id", "pool_map"."stage_order", "pool_map"."bracket_order", "pool_map"."map_order", "pool_map"."map_id" FROM "pool_map" WHERE "pool_map"."tournament_id" = $1 AND "pool_map"."stage_order" = $2 AND "pool_map"."bracket_order" = $3 ORDER BY "pool_map"."map_order" ASC"#,
+                [1i32.into(), 0i16.into(), 0i16.into()],
+            ),
+            statement(
+                r#"UPDATE "pool_map" SET "bracket_order" = $1, "map_order" = $2 WHERE "pool_map"."tournament_id" = $3 AND "pool_map"."stage_order" = $4 AND "pool_map"."bracket_order" = $5 AND "pool_map"."map_order" = $6"#,
+                [
+                    0i16.into(),
+                    PARKED_ORDER.into(),
+                    1i32.into(),
+                    0i16.into(),
+                    0i16.into(),
+                    0i16.into(),
+                ],
+            ),
+        ]
+    }
+
+    #[tokio::test]
+    async fn bracket_update_rolls_back_on_failure() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![bracket()]])
+            .append_query_results([vec![pool_map(0, 100)]])
+            // Parking the old map works, but inserting the new map fails
+            .append_exec_results([exec_ok()])
+            .append_query_errors([DbErr::Custom("injected failure".to_owned())])
+            .into_connection();
+
+        let updated = apply_bracket_update(&db, (1, 0, 0), update(vec![200, 100])).await;
+
+        assert!(updated.is_err());
+        let insert_map = statement(
+            r#"INSERT INTO "pool_map" ("tournament_id", "stage_order", "bracket_order", "map_order", "map_id") VALUES ($1, $2, $3, $4, $5) RETURNING "tournament_id", "stage_order", "bracket_order", "map_order", "map_id""#,
+            [
+                1i32.into(),
+                0i16.into(),
+                0i16.into(),
+                0i16.into(),
+                200i64.into(),
+            ],
+        );
+        assert_eq!(
+            db.into_transaction_log(),
+            [rolled_back([park_first_map(), vec![insert_map]].concat())]
+        );
+    }
+
+    #[tokio::test]
+    async fn removing_maps_with_scores_fails() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![bracket()]])
+            .append_query_results([vec![pool_map(0, 100)]])
+            .append_exec_results([exec_ok()])
+            // The removed map has scores, but no picks or bans
+            .append_query_results([vec![count(3)], vec![count(0)]])
+            .into_connection();
+
+        let updated = apply_bracket_update(&db, (1, 0, 0), update(vec![])).await;
+
+        assert_eq!(updated.unwrap_err().code(), Code::FailedPrecondition);
+        // The parked map is checked for scores, picks and bans, but never deleted
+        let count_references = [
+            statement(
+                r#"SELECT COUNT(*) AS num_items FROM (SELECT "score"."player_id", "score"."tournament_id", "score"."stage_order", "score"."bracket_order", "score"."map_order", "score"."match_id", "score"."score", "score"."accuracy", "score"."max_combo", "score"."count_300", "score"."count_100", "score"."count_50", "score"."count_miss", "score"."mods", "score"."pass", "score"."perfect", CAST("score"."scoring_type" AS text), CAST("score"."team" AS text), "score"."slot" FROM "score" WHERE "score"."tournament_id" = $1 AND "score"."stage_order" = $2 AND "score"."bracket_order" = $3 AND "score"."map_order" = $4) AS "sub_query""#,
+                [1i32.into(), 0i16.into(), 0i16.into(), PARKED_ORDER.into()],
+            ),
+            statement(
+                r#"SELECT COUNT(*) AS num_items FROM (SELECT "pick_ban"."match_id", "pick_ban"."action_order", CAST("pick_ban"."kind" AS text), "pick_ban"."team_id", "pick_ban"."tournament_id", "pick_ban"."stage_order", "pick_ban"."bracket_order", "pick_ban"."map_order" FROM "pick_ban" WHERE "pick_ban"."tournament_id" = $1 AND "pick_ban"."stage_order" = $2 AND "pick_ban"."bracket_order" = $3 AND "pick_ban"."map_order" = $4) AS "sub_query""#,
+                [1i32.into(), 0i16.into(), 0i16.into(), PARKED_ORDER.into()],
+            ),
+        ];
+        assert_eq!(
+            db.into_transaction_log(),
+            [rolled_back(
+                [park_first_map(), count_references.to_vec()].concat()
+            )]
+        );
+    }
 }
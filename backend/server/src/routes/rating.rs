@@ -0,0 +1,340 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use model::{
+    player_rating, player_rating_change, r#match, score, sea_orm_active_enums::LobbyTeam,
+    team_member, tournament, versus_match,
+};
+use proto::{
+    keys::{MatchKey, StageKey, TournamentKey},
+    osu::{api::get_users, User},
+    ratings::{
+        rating_service_server::RatingService, GetRatingHistoryRequest, GetRatingHistoryResponse,
+        GetRatingLeaderboardRequest, GetRatingLeaderboardResponse, RatedPlayer, RatingChange,
+    },
+};
+use sea_orm::{
+    ActiveValue, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, TransactionTrait,
+};
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+
+use crate::{
+    stats::rating::{replay_ratings, LineupPlayer, RatedMatch},
+    AppState,
+};
+
+pub struct RatingServiceImpl(pub AppState);
+
+/// The number of players returned by a leaderboard request that doesn't set a limit
+const DEFAULT_LEADERBOARD_LIMIT: u32 = 50;
+/// The maximum number of players returned by a single leaderboard request
+const MAX_LEADERBOARD_LIMIT: u32 = 100;
+
+/// The number of rows inserted by a single statement, so that the number of bind parameters stays
+/// below the limit of postgres
+const INSERT_CHUNK_SIZE: usize = 1000;
+
+/// How long a requested replay waits for further changes before it starts, so that e.g. a referee
+/// who edits a match and imports its scores right after only causes a single replay
+const REPLAY_DELAY: Duration = Duration::from_secs(10);
+
+/// Makes sure that only one replay writes the ratings at a time, so that the last replay always
+/// sees the latest matches
+static REPLAY_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+/// Whether a replay has been requested that hasn't started reading the matches yet
+static REPLAY_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Recalculates the ratings of all players in the background. This should be called whenever
+/// versus matches, their scores or the tournaments they belong to change.
+///
+/// Replays are debounced: changes made while a replay is still waiting to start are covered by
+/// that replay instead of starting another one.
+pub fn spawn_rating_replay(state: AppState) {
+    if REPLAY_PENDING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    tokio::spawn(async move {
+        tokio::time::sleep(REPLAY_DELAY).await;
+        if let Err(error) = replay_all_ratings(&state.db).await {
+            error!(%error, "failed to recalculate player ratings");
+        }
+    });
+}
+
+/// Finds the team a player played for in a versus match.
+///
+/// This is the team they are a member of. Players who aren't a member of either team are assigned
+/// to the team whose lobby color they played with. `members` contains the `(team_id, user_id)`
+/// pairs of both teams.
+pub fn player_team(
+    versus: &versus_match::Model,
+    members: &HashSet<(i32, i32)>,
+    player_id: i32,
+    lobby_team: LobbyTeam,
+) -> Option<i32> {
+    [versus.team_red, versus.team_blue]
+        .into_iter()
+        .find(|&team_id| members.contains(&(team_id, player_id)))
+        .or(match lobby_team {
+            LobbyTeam::Red => Some(versus.team_red),
+            LobbyTeam::Blue => Some(versus.team_blue),
+            LobbyTeam::None => None,
+        })
+}
+
+/// Replays all versus matches of active tournaments from scratch and replaces the stored ratings.
+/// A player's side in a match is determined by [`player_team`].
+pub async fn replay_all_ratings(db: &DatabaseConnection) -> Result<(), DbErr> {
+    let _guard = REPLAY_LOCK.lock().await;
+    // Changes from here on aren't guaranteed to be seen by this replay, so they need another one
+    REPLAY_PENDING.store(false, Ordering::Release);
+
+    let tournament_ids = tournament::Entity::find_active()
+        .select_only()
+        .column(tournament::Column::Id)
+        .into_tuple::<i32>()
+        .all(db)
+        .await?;
+    let matches = versus_match::Entity::find()
+        .find_also_related(r#match::Entity)
+        .filter(r#match::Column::TournamentId.is_in(tournament_ids))
+        .filter(versus_match::Column::ScoreRed.is_not_null())
+        .filter(versus_match::Column::ScoreBlue.is_not_null())
+        .order_by_asc(r#match::Column::Date)
+        .order_by_asc(r#match::Column::Id)
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|(versus, found_match)| found_match.map(|_| versus))
+        .collect::<Vec<_>>();
+    let match_ids = matches
+        .iter()
+        .map(|versus| versus.match_id)
+        .collect::<Vec<_>>();
+
+    let scores = score::Entity::find()
+        .filter(score::Column::MatchId.is_in(match_ids.iter().copied()))
+        .all(db)
+        .await?;
+    let members = team_member::Entity::find()
+        .filter(
+            team_member::Column::TeamId.is_in(
+                matches
+                    .iter()
+                    .flat_map(|versus| [versus.team_red, versus.team_blue]),
+            ),
+        )
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|member| (member.team_id, member.user_id))
+        .collect::<HashSet<_>>();
+
+    // The maps played by each player in each match and the lobby color they played with
+    let mut lineups = HashMap::<_, BTreeMap<_, (u32, LobbyTeam)>>::new();
+    for score in &scores {
+        let (maps_played, team) = lineups
+            .entry(score.match_id)
+            .or_default()
+            .entry(score.player_id)
+            .or_insert((0, LobbyTeam::None));
+        *maps_played += 1;
+        if let Some(score_team) = score.team {
+            *team = score_team;
+        }
+    }
+
+    let rated_matches = matches
+        .iter()
+        .map(|versus| {
+            let mut red = vec![];
+            let mut blue = vec![];
+            for (&player_id, &(maps_played, team)) in
+                lineups.get(&versus.match_id).into_iter().flatten()
+            {
+                let player = LineupPlayer {
+                    player_id,
+                    maps_played,
+                };
+                match player_team(versus, &members, player_id, team) {
+                    Some(team_id) if team_id == versus.team_red => red.push(player),
+                    Some(_) => blue.push(player),
+                    None => {}
+                }
+            }
+            RatedMatch {
+                match_id: versus.match_id,
+                red,
+                blue,
+                score_red: versus.score_red.unwrap_or_default() as u32,
+                score_blue: versus.score_blue.unwrap_or_default() as u32,
+            }
+        })
+        .collect::<Vec<_>>();
+    let ratings = replay_ratings(&rated_matches);
+
+    let txn = db.begin().await?;
+    player_rating_change::Entity::delete_many()
+        .exec(&txn)
+        .await?;
+    player_rating::Entity::delete_many().exec(&txn).await?;
+    for chunk in ratings.players.chunks(INSERT_CHUNK_SIZE) {
+        player_rating::Entity::insert_many(chunk.iter().map(|player| player_rating::ActiveModel {
+            user_id: ActiveValue::Set(player.player_id),
+            rating: ActiveValue::Set(player.rating),
+            matches_played: ActiveValue::Set(player.matches_played as i32),
+        }))
+        .exec_without_returning(&txn)
+        .await?;
+    }
+    for chunk in ratings.changes.chunks(INSERT_CHUNK_SIZE) {
+        player_rating_change::Entity::insert_many(chunk.iter().map(|change| {
+            player_rating_change::ActiveModel {
+                user_id: ActiveValue::Set(change.player_id),
+                match_id: ActiveValue::Set(change.match_id),
+                rating_before: ActiveValue::Set(change.rating_before),
+                rating_after: ActiveValue::Set(change.rating_after),
+            }
+        }))
+        .exec_without_returning(&txn)
+        .await?;
+    }
+    txn.commit().await?;
+
+    info!(
+        matches = rated_matches.len(),
+        players = ratings.players.len(),
+        "recalculated player ratings"
+    );
+    Ok(())
+}
+
+#[tonic::async_trait]
+impl RatingService for RatingServiceImpl {
+    #[tracing::instrument(skip_all, fields(offset = request.get_ref().offset, limit = request.get_ref().limit))]
+    async fn get_leaderboard(
+        &self,
+        request: Request<GetRatingLeaderboardRequest>,
+    ) -> Result<Response<GetRatingLeaderboardResponse>, Status> {
+        let db = &self.0.db;
+        let request = request.into_inner();
+        let limit = match request.limit {
+            0 => DEFAULT_LEADERBOARD_LIMIT,
+            limit => limit.min(MAX_LEADERBOARD_LIMIT),
+        };
+
+        let total_players = player_rating::Entity::find()
+            .count(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to count player ratings");
+                Status::internal("failed to get rating leaderboard")
+            })?;
+        let page = player_rating::Entity::find()
+            .order_by_desc(player_rating::Column::Rating)
+            .order_by_asc(player_rating::Column::UserId)
+            .offset(request.offset as u64)
+            .limit(limit as u64)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get player ratings from database");
+                Status::internal("failed to get rating leaderboard")
+            })?;
+
+        let player_ids = page
+            .iter()
+            .map(|player| player.user_id as u32)
+            .collect::<Vec<_>>();
+        let users = get_users(&self.0.cache, &self.0.osu, &player_ids)
+            .await
+            .map_err(|error| {
+                error!(%error, "error getting users");
+                Status::internal("error getting users")
+            })?;
+
+        Ok(Response::new(GetRatingLeaderboardResponse {
+            players: page
+                .into_iter()
+                .zip(users)
+                .enumerate()
+                .map(|(i, (player, user))| RatedPlayer {
+                    user: Some(user.unwrap_or_else(|| User::missing(player.user_id as u32))),
+                    rank: request.offset + i as u32 + 1,
+                    rating: player.rating,
+                    matches_played: player.matches_played as u32,
+                })
+                .collect(),
+            total_players: total_players as u32,
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = request.get_ref().user_id))]
+    async fn get_history(
+        &self,
+        request: Request<GetRatingHistoryRequest>,
+    ) -> Result<Response<GetRatingHistoryResponse>, Status> {
+        let db = &self.0.db;
+        let user_id = request.into_inner().user_id as i32;
+
+        let rating = player_rating::Entity::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get player rating from database");
+                Status::internal("failed to get player rating")
+            })?
+            .ok_or_else(|| Status::not_found(format!("user {user_id} has no rating")))?;
+        let changes = player_rating_change::Entity::find()
+            .filter(player_rating_change::Column::UserId.eq(user_id))
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get rating changes from database");
+                Status::internal("failed to get rating history")
+            })?
+            .into_iter()
+            .map(|change| (change.match_id, change))
+            .collect::<HashMap<_, _>>();
+        let matches = r#match::Entity::find()
+            .filter(r#match::Column::Id.is_in(changes.keys().copied()))
+            .order_by_asc(r#match::Column::Date)
+            .order_by_asc(r#match::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get matches from database");
+                Status::internal("failed to get rating history")
+            })?;
+
+        Ok(Response::new(GetRatingHistoryResponse {
+            rating: rating.rating,
+            matches_played: rating.matches_played as u32,
+            changes: matches
+                .into_iter()
+                .filter_map(|found_match| {
+                    let change = changes.get(&found_match.id)?;
+                    Some(RatingChange {
+                        match_key: Some(MatchKey {
+                            stage_key: Some(StageKey {
+                                tournament_key: Some(TournamentKey {
+                                    id: found_match.tournament_id,
+                                }),
+                                stage_order: found_match.stage_order as u32,
+                            }),
+                            match_id: found_match.id as u32,
+                        }),
+                        date: Some(found_match.date.into()),
+                        rating_before: change.rating_before,
+                        rating_after: change.rating_after,
+                    })
+                })
+                .collect(),
+        }))
+    }
+}
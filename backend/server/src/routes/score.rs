@@ -1,17 +1,44 @@
-use futures::{StreamExt, TryStreamExt};
+use std::collections::HashMap;
+
+use model::score;
 use proto::{
     keys::PoolMapKey,
-    osu::api::get_user,
-    scores::{score_service_server::ScoreService, GetScoresRequest, GetScoresResponse, Score},
+    osu::{api::get_users, User},
+    scores::{
+        self, score_service_server::ScoreService, GetScoresRequest, GetScoresResponse,
+        GetStageLeaderboardRequest, GetStageLeaderboardResponse, PlayerStanding, Score,
+        ScoreDetails,
+    },
 };
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
 use tonic::{Request, Response, Status};
 use tracing::error;
 
-use crate::AppState;
+use super::{stats::find_stage_pool, tournament::find_stage};
+use crate::{
+    stats::leaderboard::{rank_players, LeaderboardOrder, Play},
+    AppState,
+};
 use proto::osu::api::get_map;
 
 pub struct ScoreServiceImpl(pub AppState);
 
+/// The number of players returned by a leaderboard request that doesn't set a limit
+const DEFAULT_LEADERBOARD_LIMIT: u32 = 50;
+/// The maximum number of players returned by a single leaderboard request
+const MAX_LEADERBOARD_LIMIT: u32 = 100;
+
+impl From<scores::LeaderboardOrder> for LeaderboardOrder {
+    fn from(value: scores::LeaderboardOrder) -> Self {
+        match value {
+            scores::LeaderboardOrder::AverageScore => Self::AverageScore,
+            scores::LeaderboardOrder::AveragePlacement => Self::AveragePlacement,
+            scores::LeaderboardOrder::ZScore => Self::ZScore,
+            scores::LeaderboardOrder::MapsPlayed => Self::MapsPlayed,
+        }
+    }
+}
+
 #[tonic::async_trait]
 impl ScoreService for ScoreServiceImpl {
     #[tracing::instrument(skip_all)]
@@ -28,61 +55,199 @@ impl ScoreService for ScoreServiceImpl {
             map_order,
         } = extract_pool_map_key(request.pool_map_key)?;
 
-        let query_result = sqlx::query!(
+        let map_id = sqlx::query_scalar!(
             "
-            SELECT tournament_id, stage_order, bracket_order, map_order, map_id, player_id, score FROM pool_map
-            LEFT JOIN score USING (tournament_id, stage_order, bracket_order, map_order)
+            SELECT map_id FROM pool_map
             WHERE tournament_id = $1 AND stage_order = $2 AND bracket_order = $3 AND map_order = $4
-            ORDER BY score DESC
-            ", tournament_id, stage_order as i32, bracket_order as i32, map_order as i32
+            AND tournament_id IN (SELECT id FROM tournament WHERE deleted_at IS NULL)
+            ",
+            tournament_id,
+            stage_order as i32,
+            bracket_order as i32,
+            map_order as i32
         )
-        .fetch_all(&state.sqlx)
+        .fetch_optional(&state.sqlx)
         .await
         .map_err(|error| {
-            error!(%error, "could not query database for scores");
+            error!(%error, "could not query database for pool map");
             Status::internal("could not get scores")
         })?;
 
-        if query_result.is_empty() {
+        let Some(map_id) = map_id else {
             error!(
                 tournament_id,
                 stage_order, bracket_order, map_order, "map does not exist in pool"
             );
             return Err(Status::not_found("map does not exist in pool"));
-        }
+        };
+
+        let scores = score::Entity::find()
+            .filter(score::Column::TournamentId.eq(tournament_id))
+            .filter(score::Column::StageOrder.eq(stage_order as i16))
+            .filter(score::Column::BracketOrder.eq(bracket_order as i16))
+            .filter(score::Column::MapOrder.eq(map_order as i16))
+            .order_by_desc(score::Column::Score)
+            .all(&state.db)
+            .await
+            .map_err(|error| {
+                error!(%error, "could not query database for scores");
+                Status::internal("could not get scores")
+            })?;
 
-        let map_id = query_result[0].map_id;
-        let map = get_map(&self.0.redis, &self.0.osu, map_id as u32)
+        let map = get_map(&self.0.cache, &self.0.sqlx, &self.0.osu, map_id as u32)
             .await
             .map_err(|error| {
                 error!(%error, map_id, "error getting map from osu api");
                 Status::internal("could not get map from osu api")
             })?;
 
-        let scores = futures::stream::iter(&query_result)
-            .then(|v| async {
-                let user = match get_user(&self.0.redis, &self.0.osu, v.player_id as u32).await {
-                    Ok(user) => Some(user),
-                    Err(e) => return Err(e),
-                };
-
-                Ok(Score {
-                    user,
-                    score: v.score as u64,
-                })
-            })
-            .try_collect::<Vec<_>>()
+        let player_ids = scores
+            .iter()
+            .map(|score| score.player_id as u32)
+            .collect::<Vec<_>>();
+        let users = get_users(&self.0.cache, &self.0.osu, &player_ids)
             .await
             .map_err(|error| {
-                tracing::error!(%error, "error getting user");
+                tracing::error!(%error, "error getting users");
                 Status::internal("error getting user")
             })?;
 
+        let scores = scores
+            .iter()
+            .zip(users)
+            .map(|(score, user)| Score {
+                user: Some(user.unwrap_or_else(|| User::missing(score.player_id as u32))),
+                score: score.score as u64,
+                details: score_details(score),
+            })
+            .collect();
+
         Ok(Response::new(GetScoresResponse {
             beatmap: Some(map),
             scores,
         }))
     }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().stage_key))]
+    async fn get_stage_leaderboard(
+        &self,
+        request: Request<GetStageLeaderboardRequest>,
+    ) -> tonic::Result<Response<GetStageLeaderboardResponse>> {
+        let db = &self.0.db;
+        let request = request.into_inner();
+        let order = LeaderboardOrder::from(request.order());
+        let stage_key = request
+            .stage_key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing stage key"))?;
+        let (_tournament, stage) = find_stage(stage_key, db).await?;
+
+        let pool = find_stage_pool(&stage, db)
+            .await?
+            .into_iter()
+            .filter(|map| {
+                request
+                    .bracket_order
+                    .is_none_or(|bracket_order| map.bracket_order as u32 == bracket_order)
+            })
+            .collect::<Vec<_>>();
+        if let Some(bracket_order) = request.bracket_order {
+            if pool.is_empty() {
+                return Err(Status::not_found(format!(
+                    "bracket {bracket_order} of stage {} has no maps",
+                    stage.stage_order
+                )));
+            }
+        }
+        let map_index = pool
+            .iter()
+            .enumerate()
+            .map(|(i, map)| ((map.bracket_order, map.map_order), i))
+            .collect::<HashMap<_, _>>();
+
+        let mut query = score::Entity::find()
+            .filter(score::Column::TournamentId.eq(stage.tournament_id))
+            .filter(score::Column::StageOrder.eq(stage.stage_order));
+        if let Some(bracket_order) = request.bracket_order {
+            query = query.filter(score::Column::BracketOrder.eq(bracket_order as i16));
+        }
+        let plays = query
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get scores from database");
+                Status::internal("failed to get scores")
+            })?
+            .into_iter()
+            .filter_map(|score| {
+                Some(Play {
+                    player_id: score.player_id,
+                    map: *map_index.get(&(score.bracket_order, score.map_order))?,
+                    score: score.score as u64,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let standings = rank_players(order, &plays);
+        let total_players = standings.len() as u32;
+        let limit = match request.limit {
+            0 => DEFAULT_LEADERBOARD_LIMIT,
+            limit => limit.min(MAX_LEADERBOARD_LIMIT),
+        };
+        let page = standings
+            .into_iter()
+            .skip(request.offset as usize)
+            .take(limit as usize)
+            .collect::<Vec<_>>();
+
+        // Only the players on the requested page are looked up
+        let player_ids = page
+            .iter()
+            .map(|standing| standing.player_id as u32)
+            .collect::<Vec<_>>();
+        let users = get_users(&self.0.cache, &self.0.osu, &player_ids)
+            .await
+            .map_err(|error| {
+                error!(%error, "error getting users");
+                Status::internal("error getting users")
+            })?;
+
+        let players = page
+            .into_iter()
+            .zip(users)
+            .map(|(standing, user)| PlayerStanding {
+                user: Some(user.unwrap_or_else(|| User::missing(standing.player_id as u32))),
+                rank: standing.rank,
+                maps_played: standing.maps_played,
+                average_score: standing.average_score,
+                average_placement: standing.average_placement,
+                average_z_score: standing.average_z_score,
+            })
+            .collect();
+
+        Ok(Response::new(GetStageLeaderboardResponse {
+            players,
+            total_players,
+        }))
+    }
+}
+
+/// Returns the details of a score, unless it was imported before they were recorded
+fn score_details(score: &score::Model) -> Option<ScoreDetails> {
+    Some(ScoreDetails {
+        accuracy: score.accuracy?,
+        max_combo: score.max_combo? as u32,
+        count_300: score.count_300? as u32,
+        count_100: score.count_100? as u32,
+        count_50: score.count_50? as u32,
+        count_miss: score.count_miss? as u32,
+        mods: score.mods? as u32,
+        pass: score.pass?,
+        perfect: score.perfect?,
+        scoring_type: score.scoring_type?.into(),
+        team: score.team?.into(),
+        slot: score.slot? as u32,
+    })
 }
 
 struct ExtractedPoolMapKey {
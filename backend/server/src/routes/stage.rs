@@ -1,21 +1,74 @@
 use super::tournament::find_stage;
-use proto::osu::api::get_map;
-use crate::{ routes::convert_start_end, AppState};
-use futures::{stream::FuturesOrdered, TryFutureExt, TryStreamExt};
+use crate::{
+    permission::{require_permission, Permission},
+    routes::convert_start_end,
+    AppState,
+};
+use futures::TryFutureExt;
 use model::stage;
+use proto::osu::api::get_maps;
 use proto::stages::{
     stage_service_server::StageService, CreateStageRequest, CreateStageResponse,
     DeleteStageRequest, DeleteStageResponse, GetAllStagesRequest, GetAllStagesResponse,
     GetStageRequest, GetStageResponse, UpdateStageRequest, UpdateStageResponse,
 };
 use sea_orm::{
-    sea_query::Expr, ActiveModelTrait, ColumnTrait, EntityTrait, FromQueryResult, IntoActiveModel,
-    LoaderTrait, ModelTrait, QueryFilter, QueryOrder, QuerySelect,
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    FromQueryResult, IntoActiveModel, LoaderTrait, ModelTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, TransactionTrait,
 };
 use tonic::{Request, Response, Status};
 
 pub struct StageServiceImpl(pub AppState);
 
+/// The stage order a stage is parked at while the other stages are moved out of its way
+const PARKED_STAGE_ORDER: i16 = i16::MAX;
+
+/// Changes the order of a single stage.
+/// Pools, matches and scores follow the stage, since all keys referencing a stage cascade on
+/// updates.
+async fn set_stage_order(
+    db: &impl ConnectionTrait,
+    tournament_id: i32,
+    from: i16,
+    to: i16,
+) -> Result<(), DbErr> {
+    stage::Entity::update_many()
+        .col_expr(stage::Column::StageOrder, Expr::value(to))
+        .filter(stage::Column::TournamentId.eq(tournament_id))
+        .filter(stage::Column::StageOrder.eq(from))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Moves a stage to a new position, shifting the stages in between by one place.
+/// This should be run in a transaction, so that no one sees the intermediate states.
+async fn move_stage(
+    db: &impl ConnectionTrait,
+    tournament_id: i32,
+    from: i16,
+    to: i16,
+) -> Result<(), DbErr> {
+    if from == to {
+        return Ok(());
+    }
+
+    // Stage orders are unique, so the stages are moved one by one, each into the place that was
+    // freed last
+    set_stage_order(db, tournament_id, from, PARKED_STAGE_ORDER).await?;
+    if from < to {
+        for order in from + 1..=to {
+            set_stage_order(db, tournament_id, order, order - 1).await?;
+        }
+    } else {
+        for order in (to..from).rev() {
+            set_stage_order(db, tournament_id, order, order + 1).await?;
+        }
+    }
+    set_stage_order(db, tournament_id, PARKED_STAGE_ORDER, to).await
+}
+
 #[tonic::async_trait]
 impl StageService for StageServiceImpl {
     type GetAllStream =
@@ -32,7 +85,7 @@ impl StageService for StageServiceImpl {
             .ok_or_else(|| Status::invalid_argument("missing tournament key"))?;
 
         // find the tournament with its related stages
-        let res = model::tournament::Entity::find_by_id(tournament_key.id)
+        let res = model::tournament::Entity::find_active_by_id(tournament_key.id)
             .find_with_related(model::stage::Entity)
             .all(&self.0.db)
             .await
@@ -53,7 +106,7 @@ impl StageService for StageServiceImpl {
             .into_iter()
             .map(|stage| GetAllStagesResponse {
                 key: Some(proto::keys::StageKey {
-                    tournament_key: Some(tournament_key.clone()),
+                    tournament_key: Some(*tournament_key),
                     stage_order: stage.stage_order as u32,
                 }),
                 stage: Some(proto::stages::Stage {
@@ -100,25 +153,20 @@ impl StageService for StageServiceImpl {
             .await
             .map_err(|e| Status::internal(format!("could not load pool maps: {e}")))?;
 
-        // Fetch map info from the osu api and transform them to the on-the-wire format
-        let fetched_maps = maps
-            .into_iter()
-            .map(|bracket| {
-                bracket
-                    .into_iter()
-                    .map(|map| get_map(&self.0.redis, self.0.osu.as_ref(), map.map_id as u32))
-                    .collect::<FuturesOrdered<_>>()
-                    .map_ok(proto::osu::Beatmap::from)
-                    .map_err(|e| Status::internal(format!("error fetching map data: {e}")))
-                    .try_collect::<Vec<_>>()
-            })
-            .collect::<FuturesOrdered<_>>()
-            .try_collect::<Vec<_>>()
-            .await?;
+        // Fetch map info for the whole pool at once
+        let map_ids = maps
+            .iter()
+            .flatten()
+            .map(|map| map.map_id as u32)
+            .collect::<Vec<_>>();
+        let mut map_data = get_maps(&self.0.cache, &self.0.sqlx, &self.0.osu, &map_ids)
+            .map_err(|e| Status::internal(format!("error fetching map data: {e}")))
+            .await?
+            .into_iter();
 
         // Compose the response
         let response = GetStageResponse {
-            key: Some(stage_key.clone()),
+            key: Some(*stage_key),
             stage: Some(proto::stages::Stage {
                 name: stage.name.clone(),
                 best_of: stage.best_of as u32,
@@ -129,11 +177,13 @@ impl StageService for StageServiceImpl {
             pool: Some(proto::pool::Pool {
                 brackets: brackets
                     .into_iter()
-                    .zip(fetched_maps)
+                    .zip(maps)
                     .map(|(bracket, maps)| proto::pool::PoolBracket {
                         bracket_order: bracket.bracket_order as u32,
                         name: bracket.name.clone(),
-                        maps: Some(proto::pool::PoolBracketMaps { maps }),
+                        maps: Some(proto::pool::PoolBracketMaps {
+                            maps: map_data.by_ref().take(maps.len()).collect(),
+                        }),
                     })
                     .collect(),
             }),
@@ -147,11 +197,18 @@ impl StageService for StageServiceImpl {
         request: Request<CreateStageRequest>,
     ) -> Result<Response<CreateStageResponse>, Status> {
         use sea_orm::ActiveValue as A;
-        let request = request.into_inner();
         let tournament_key = request
+            .get_ref()
             .tournament_key
-            .as_ref()
             .ok_or_else(|| Status::invalid_argument("missing tournament key"))?;
+        require_permission(
+            &request,
+            tournament_key.id,
+            Permission::EditStages,
+            &self.0.db,
+        )
+        .await?;
+        let request = request.into_inner();
 
         #[allow(unused)]
         #[derive(FromQueryResult, Debug)]
@@ -202,14 +259,21 @@ impl StageService for StageServiceImpl {
     ) -> Result<Response<UpdateStageResponse>, Status> {
         use sea_orm::ActiveValue as A;
         let db = &self.0.db;
-        let req = request.into_inner();
-        let stage_key = req
+        let stage_key = request
+            .get_ref()
             .key
-            .as_ref()
             .ok_or_else(|| Status::invalid_argument("missing stage key"))?;
-        let (_tournament, stage) = find_stage(stage_key, db).await?;
+        let (tournament, stage) = find_stage(&stage_key, db).await?;
+        require_permission(&request, tournament.id, Permission::EditStages, db).await?;
+        let req = request.into_inner();
+
+        let txn = db
+            .begin()
+            .await
+            .map_err(|e| Status::internal(format!("could not start transaction: {e}")))?;
 
         // Update values
+        let stage_order = stage.stage_order;
         let mut stage = stage.into_active_model();
         if let Some(name) = req.name {
             stage.name = A::Set(name);
@@ -219,11 +283,34 @@ impl StageService for StageServiceImpl {
         }
 
         // Update in database
-        let stage = stage
-            .update(&self.0.db)
+        let mut stage = stage
+            .update(&txn)
             .await
             .map_err(|e| Status::internal(format!("could not update stage: {e}")))?;
-        
+
+        // Move the stage to its new place
+        if let Some(new_stage_order) = req.stage_order {
+            let stage_count = stage::Entity::find()
+                .filter(stage::Column::TournamentId.eq(tournament.id))
+                .count(&txn)
+                .await
+                .map_err(|e| Status::internal(format!("could not count stages: {e}")))?;
+            if new_stage_order < 0 || new_stage_order as u64 >= stage_count {
+                return Err(Status::invalid_argument(format!(
+                    "stage order {new_stage_order} is out of range for {stage_count} stages"
+                )));
+            }
+
+            move_stage(&txn, tournament.id, stage_order, new_stage_order as i16)
+                .await
+                .map_err(|e| Status::internal(format!("could not reorder stages: {e}")))?;
+            stage.stage_order = new_stage_order as i16;
+        }
+
+        txn.commit()
+            .await
+            .map_err(|e| Status::internal(format!("could not commit stage update: {e}")))?;
+
         Ok(Response::new(UpdateStageResponse {
             stage: Some(proto::stages::Stage {
                 name: stage.name,
@@ -249,13 +336,27 @@ impl StageService for StageServiceImpl {
             .tournament_key
             .as_ref()
             .ok_or_else(|| Status::invalid_argument("missing tournament key in stage key"))?;
+        require_permission(
+            &request,
+            tournament_key.id,
+            Permission::EditStages,
+            &self.0.db,
+        )
+        .await?;
+
+        let txn = self
+            .0
+            .db
+            .begin()
+            .await
+            .map_err(|e| Status::internal(format!("could not start transaction: {e}")))?;
 
         // Delete Stage
-        let delete_result =
-            model::stage::Entity::delete_by_id((tournament_key.id, stage_key.stage_order as i16))
-                .exec(&self.0.db)
-                .await
-                .map_err(|e| Status::internal(format!("could not delete stage: {e}")))?;
+        let stage_order = stage_key.stage_order as i16;
+        let delete_result = model::stage::Entity::delete_by_id((tournament_key.id, stage_order))
+            .exec(&txn)
+            .await
+            .map_err(|e| Status::internal(format!("could not delete stage: {e}")))?;
 
         // If no stage was delete, that means that it didn't exist
         if delete_result.rows_affected == 0 {
@@ -265,6 +366,29 @@ impl StageService for StageServiceImpl {
             )));
         }
 
+        // Close the gap left by the stage
+        let later_stages = stage::Entity::find()
+            .filter(stage::Column::TournamentId.eq(tournament_key.id))
+            .filter(stage::Column::StageOrder.gt(stage_order))
+            .order_by_asc(stage::Column::StageOrder)
+            .all(&txn)
+            .await
+            .map_err(|e| Status::internal(format!("could not fetch stages: {e}")))?;
+        for stage in later_stages {
+            set_stage_order(
+                &txn,
+                tournament_key.id,
+                stage.stage_order,
+                stage.stage_order - 1,
+            )
+            .await
+            .map_err(|e| Status::internal(format!("could not reorder stages: {e}")))?;
+        }
+
+        txn.commit()
+            .await
+            .map_err(|e| Status::internal(format!("could not commit stage deletion: {e}")))?;
+
         Ok(Response::new(DeleteStageResponse {}))
     }
 }
@@ -0,0 +1,869 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use model::{
+    pick_ban, pool_bracket, pool_map, qualifier_run, r#match, score,
+    sea_orm_active_enums::{LobbyTeam, PickBanKind},
+    stage, team, team_member, tournament, versus_match,
+};
+use proto::{
+    keys::{MatchKey, PoolBracketKey, PoolMapKey, StageKey, TeamKey, TournamentKey},
+    osu::{api::get_users, User},
+    stats::{
+        self, get_head_to_head_request::Sides, get_match_costs_request::Scope,
+        stats_service_server::StatsService, AggregateMatchCost, BracketHeadToHead,
+        BracketPickBanStats, GetHeadToHeadRequest, GetHeadToHeadResponse, GetMatchCostsRequest,
+        GetMatchCostsResponse, GetPickBanStatsRequest, GetPickBanStatsResponse,
+        GetQualifierSeedingRequest, GetQualifierSeedingResponse, HeadToHeadMap, HeadToHeadMatch,
+        HeadToHeadRecord, MapPickBanStats, MatchCost, PickBanStats, PlayerPair, QualifierMapResult,
+        QualifierSeed, TeamPair,
+    },
+};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use super::{rating::player_team, team::find_team, tournament::find_stage};
+use crate::{
+    stats::{
+        head_to_head::{bracket_records, record, MapComparison, Record},
+        leaderboard::Play,
+        match_cost::{aggregate_match_costs, match_costs},
+        pick_ban::{count_pick_bans, PickBanCounts},
+        seeding::{seed_teams, SeedingMethod},
+    },
+    AppState,
+};
+
+pub struct StatsServiceImpl(pub AppState);
+
+/// Gets the maps in the pool of a stage ordered by their bracket and their order in the bracket.
+pub async fn find_stage_pool(
+    stage: &stage::Model,
+    db: &DatabaseConnection,
+) -> tonic::Result<Vec<pool_map::Model>> {
+    pool_map::Entity::find()
+        .filter(pool_map::Column::TournamentId.eq(stage.tournament_id))
+        .filter(pool_map::Column::StageOrder.eq(stage.stage_order))
+        .order_by_asc(pool_map::Column::BracketOrder)
+        .order_by_asc(pool_map::Column::MapOrder)
+        .all(db)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to get pool maps from database");
+            Status::internal("failed to get pool maps")
+        })
+}
+
+/// Creates the key of a map in a stage's pool
+pub fn pool_map_key(stage_key: StageKey, map: &pool_map::Model) -> PoolMapKey {
+    PoolMapKey {
+        bracket_key: Some(PoolBracketKey {
+            stage_key: Some(stage_key),
+            bracket_order: map.bracket_order as u32,
+        }),
+        map_order: map.map_order as u32,
+    }
+}
+
+/// Creates the key of a team
+fn team_key(tournament_id: i32, team_id: i32) -> TeamKey {
+    TeamKey {
+        tournament_key: Some(TournamentKey { id: tournament_id }),
+        team_id: team_id as u32,
+    }
+}
+
+/// The two sides compared by a head-to-head request
+enum Compared {
+    Teams(team::Model, team::Model),
+    /// The osu user ids of two players
+    Players(i32, i32),
+}
+
+/// Converts a head-to-head record into the on-the-wire format
+fn head_to_head_record(record: Record) -> HeadToHeadRecord {
+    HeadToHeadRecord {
+        wins: record.wins,
+        losses: record.losses,
+        maps: record.maps,
+        win_rate: record.win_rate,
+    }
+}
+
+/// Converts pick and ban counts into the on-the-wire format, calculating the rates relative to the
+/// given number of matches.
+fn pick_ban_stats(counts: PickBanCounts, matches: u32) -> PickBanStats {
+    let rate = |count: u32| {
+        if matches == 0 {
+            0.0
+        } else {
+            count as f64 / matches as f64
+        }
+    };
+    PickBanStats {
+        protects: counts.protects,
+        bans: counts.bans,
+        picks: counts.picks,
+        first_picks: counts.first_picks,
+        protect_rate: rate(counts.protected_matches),
+        ban_rate: rate(counts.banned_matches),
+        pick_rate: rate(counts.picked_matches),
+        first_pick_rate: rate(counts.first_picks),
+    }
+}
+
+impl From<stats::SeedingMethod> for SeedingMethod {
+    fn from(value: stats::SeedingMethod) -> Self {
+        match value {
+            stats::SeedingMethod::Sum => Self::Sum,
+            stats::SeedingMethod::AverageRank => Self::AverageRank,
+            stats::SeedingMethod::ZSum => Self::ZSum,
+            stats::SeedingMethod::PercentileOfMax => Self::PercentileOfMax,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl StatsService for StatsServiceImpl {
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().stage_key))]
+    async fn get_qualifier_seeding(
+        &self,
+        request: Request<GetQualifierSeedingRequest>,
+    ) -> Result<Response<GetQualifierSeedingResponse>, Status> {
+        let db = &self.0.db;
+        let method = SeedingMethod::from(request.get_ref().method());
+        let stage_key = request
+            .get_ref()
+            .stage_key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing stage key"))?;
+        let (_tournament, stage) = find_stage(stage_key, db).await?;
+        if stage.best_of != 0 {
+            return Err(Status::failed_precondition(format!(
+                "stage {} is not a qualifier stage",
+                stage.stage_order
+            )));
+        }
+
+        let pool = find_stage_pool(&stage, db).await?;
+        let map_index = pool
+            .iter()
+            .enumerate()
+            .map(|(i, map)| ((map.bracket_order, map.map_order), i))
+            .collect::<HashMap<_, _>>();
+
+        let runs = qualifier_run::Entity::find()
+            .inner_join(r#match::Entity)
+            .filter(r#match::Column::TournamentId.eq(stage.tournament_id))
+            .filter(r#match::Column::StageOrder.eq(stage.stage_order))
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get qualifier runs from database");
+                Status::internal("failed to get qualifier runs")
+            })?;
+
+        let teams = team::Entity::find()
+            .filter(team::Column::Id.is_in(runs.iter().map(|run| run.team_id)))
+            .order_by_asc(team::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get teams from database");
+                Status::internal("failed to get teams")
+            })?;
+        let team_index = teams
+            .iter()
+            .enumerate()
+            .map(|(i, team)| (team.id, i))
+            .collect::<HashMap<_, _>>();
+
+        let scores = score::Entity::find()
+            .filter(score::Column::MatchId.is_in(runs.iter().map(|run| run.match_id)))
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get scores from database");
+                Status::internal("failed to get scores")
+            })?;
+
+        // Add up the scores of the players in each run
+        let mut run_scores = HashMap::<_, u64>::new();
+        for score in scores {
+            let Some(&map) = map_index.get(&(score.bracket_order, score.map_order)) else {
+                continue;
+            };
+            *run_scores.entry((score.match_id, map)).or_default() += score.score as u64;
+        }
+
+        // Keep the best run of each team on each map
+        let run_teams = runs
+            .iter()
+            .map(|run| (run.match_id, team_index[&run.team_id]))
+            .collect::<HashMap<_, _>>();
+        let mut team_scores = vec![vec![0; pool.len()]; teams.len()];
+        for ((match_id, map), score) in run_scores {
+            let team_score = &mut team_scores[run_teams[&match_id]][map];
+            *team_score = (*team_score).max(score);
+        }
+
+        let seeds = seed_teams(method, &team_scores)
+            .into_iter()
+            .map(|seed| {
+                let team = &teams[seed.team];
+                QualifierSeed {
+                    team_key: Some(team_key(team.tournament_id, team.id)),
+                    team_name: team.name.clone(),
+                    seed: seed.seed,
+                    total: seed.total,
+                    map_results: team_scores[seed.team]
+                        .iter()
+                        .zip(seed.map_ranks)
+                        .zip(seed.map_values)
+                        .map(|((&score, rank), value)| QualifierMapResult { score, rank, value })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Ok(Response::new(GetQualifierSeedingResponse {
+            maps: pool
+                .iter()
+                .map(|map| pool_map_key(*stage_key, map))
+                .collect(),
+            seeds,
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().stage_key))]
+    async fn get_pick_ban_stats(
+        &self,
+        request: Request<GetPickBanStatsRequest>,
+    ) -> Result<Response<GetPickBanStatsResponse>, Status> {
+        let db = &self.0.db;
+        let stage_key = request
+            .get_ref()
+            .stage_key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing stage key"))?;
+        let (_tournament, stage) = find_stage(stage_key, db).await?;
+
+        let brackets = stage
+            .find_related(pool_bracket::Entity)
+            .order_by_asc(pool_bracket::Column::BracketOrder)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get pool brackets from database");
+                Status::internal("failed to get pool brackets")
+            })?;
+        let bracket_index = brackets
+            .iter()
+            .enumerate()
+            .map(|(i, bracket)| (bracket.bracket_order, i))
+            .collect::<HashMap<_, _>>();
+        let pool = find_stage_pool(&stage, db).await?;
+        let map_index = pool
+            .iter()
+            .enumerate()
+            .map(|(i, map)| ((map.bracket_order, map.map_order), i))
+            .collect::<HashMap<_, _>>();
+        let map_brackets = pool
+            .iter()
+            .map(|map| bracket_index[&map.bracket_order])
+            .collect::<Vec<_>>();
+
+        let pick_bans = pick_ban::Entity::find()
+            .filter(pick_ban::Column::TournamentId.eq(stage.tournament_id))
+            .filter(pick_ban::Column::StageOrder.eq(stage.stage_order))
+            .order_by_asc(pick_ban::Column::MatchId)
+            .order_by_asc(pick_ban::Column::ActionOrder)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get pick bans from database");
+                Status::internal("failed to get pick bans")
+            })?;
+        let logs = pick_bans
+            .chunk_by(|a, b| a.match_id == b.match_id)
+            .map(|log| {
+                log.iter()
+                    .map(|pick_ban| {
+                        let map = map_index[&(pick_ban.bracket_order, pick_ban.map_order)];
+                        (pick_ban.kind, map)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let counts = count_pick_bans(
+            &map_brackets,
+            brackets.len(),
+            logs.iter().map(Vec::as_slice),
+        );
+
+        Ok(Response::new(GetPickBanStatsResponse {
+            matches: counts.matches,
+            maps: pool
+                .iter()
+                .zip(counts.maps)
+                .map(|(map, map_counts)| MapPickBanStats {
+                    map_key: Some(pool_map_key(*stage_key, map)),
+                    stats: Some(pick_ban_stats(map_counts, counts.matches)),
+                })
+                .collect(),
+            brackets: brackets
+                .into_iter()
+                .zip(counts.brackets)
+                .map(|(bracket, bracket_counts)| BracketPickBanStats {
+                    bracket_key: Some(PoolBracketKey {
+                        stage_key: Some(*stage_key),
+                        bracket_order: bracket.bracket_order as u32,
+                    }),
+                    name: bracket.name,
+                    stats: Some(pick_ban_stats(bracket_counts, counts.matches)),
+                })
+                .collect(),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(scope = ?request.get_ref().scope))]
+    async fn get_match_costs(
+        &self,
+        request: Request<GetMatchCostsRequest>,
+    ) -> Result<Response<GetMatchCostsResponse>, Status> {
+        let db = &self.0.db;
+        let request = request.into_inner();
+        let (tournament_id, stage_order) = match request.scope {
+            Some(Scope::StageKey(stage_key)) => {
+                let (_tournament, stage) = find_stage(&stage_key, db).await?;
+                (stage.tournament_id, Some(stage.stage_order))
+            }
+            Some(Scope::TournamentKey(tournament_key)) => {
+                let tournament = tournament::Entity::find_active_by_id(tournament_key.id)
+                    .one(db)
+                    .await
+                    .map_err(|error| {
+                        error!(%error, "failed to get tournament from database");
+                        Status::internal("failed to get tournament")
+                    })?
+                    .ok_or_else(|| {
+                        Status::not_found(format!(
+                            "tournament with id {} does not exist",
+                            tournament_key.id
+                        ))
+                    })?;
+                (tournament.id, None)
+            }
+            None => return Err(Status::invalid_argument("missing stage or tournament key")),
+        };
+        let team = match &request.team_key {
+            Some(team_key) => {
+                let team = find_team(team_key, db).await?;
+                if team.tournament_id != tournament_id {
+                    return Err(Status::invalid_argument(format!(
+                        "team {} does not play in tournament {tournament_id}",
+                        team.id
+                    )));
+                }
+                Some(team)
+            }
+            None => None,
+        };
+
+        let mut query = versus_match::Entity::find()
+            .find_also_related(r#match::Entity)
+            .filter(r#match::Column::TournamentId.eq(tournament_id));
+        if let Some(stage_order) = stage_order {
+            query = query.filter(r#match::Column::StageOrder.eq(stage_order));
+        }
+        if let Some(team) = &team {
+            query = query.filter(
+                Condition::any()
+                    .add(versus_match::Column::TeamRed.eq(team.id))
+                    .add(versus_match::Column::TeamBlue.eq(team.id)),
+            );
+        }
+        let matches = query
+            .order_by_asc(r#match::Column::Date)
+            .order_by_asc(r#match::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get versus matches from database");
+                Status::internal("failed to get versus matches")
+            })?
+            .into_iter()
+            .filter_map(|(versus, found_match)| Some((versus, found_match?)))
+            .collect::<Vec<_>>();
+        let match_ids = matches
+            .iter()
+            .map(|(versus, _)| versus.match_id)
+            .collect::<Vec<_>>();
+
+        let scores = score::Entity::find()
+            .filter(score::Column::MatchId.is_in(match_ids.iter().copied()))
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get scores from database");
+                Status::internal("failed to get scores")
+            })?;
+        let tiebreakers = pick_ban::Entity::find()
+            .filter(pick_ban::Column::MatchId.is_in(match_ids.iter().copied()))
+            .filter(pick_ban::Column::Kind.eq(PickBanKind::Tiebreaker))
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get tiebreakers from database");
+                Status::internal("failed to get tiebreakers")
+            })?
+            .into_iter()
+            .map(|pick| (pick.match_id, (pick.bracket_order, pick.map_order)))
+            .collect::<HashMap<_, _>>();
+        let members = team_member::Entity::find()
+            .filter(
+                team_member::Column::TeamId.is_in(
+                    matches
+                        .iter()
+                        .flat_map(|(versus, _)| [versus.team_red, versus.team_blue]),
+                ),
+            )
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get team members from database");
+                Status::internal("failed to get team members")
+            })?
+            .into_iter()
+            .map(|member| (member.team_id, member.user_id))
+            .collect::<HashSet<_>>();
+
+        // Maps are identified by their stage, bracket and order, since a tournament's matches are
+        // played in different stages
+        let mut map_index = HashMap::new();
+        let mut match_plays = HashMap::<_, Vec<_>>::new();
+        // The lobby color each player played with in each match
+        let mut lobby_teams = HashMap::new();
+        for score in &scores {
+            if let Some(team) = score.team {
+                lobby_teams.insert((score.match_id, score.player_id), team);
+            }
+            let key = (score.stage_order, score.bracket_order, score.map_order);
+            let next_index = map_index.len();
+            let map = *map_index.entry(key).or_insert(next_index);
+            match_plays.entry(score.match_id).or_default().push(Play {
+                player_id: score.player_id,
+                map,
+                score: score.score as u64,
+            });
+        }
+
+        let mut costs = vec![];
+        let mut player_teams = HashMap::new();
+        for (versus, found_match) in &matches {
+            let Some(plays) = match_plays.get(&versus.match_id) else {
+                continue;
+            };
+            let tiebreaker = tiebreakers
+                .get(&versus.match_id)
+                .and_then(|&(bracket, map)| {
+                    map_index
+                        .get(&(found_match.stage_order, bracket, map))
+                        .copied()
+                });
+
+            for cost in match_costs(plays, tiebreaker) {
+                let lobby_team = lobby_teams
+                    .get(&(versus.match_id, cost.player_id))
+                    .copied()
+                    .unwrap_or(LobbyTeam::None);
+                let side = player_team(versus, &members, cost.player_id, lobby_team);
+                // The lobby's scores are needed for the medians, but only the team's players
+                // are returned
+                if team.as_ref().is_some_and(|team| side != Some(team.id)) {
+                    continue;
+                }
+                if let Some(team_id) = side {
+                    player_teams.insert(cost.player_id, team_id);
+                }
+                costs.push((found_match, side, cost));
+            }
+        }
+
+        let aggregates = aggregate_match_costs(costs.iter().map(|(_, _, cost)| cost));
+        let player_ids = aggregates
+            .iter()
+            .map(|aggregate| aggregate.player_id as u32)
+            .collect::<Vec<_>>();
+        let users = get_users(&self.0.cache, &self.0.osu, &player_ids)
+            .await
+            .map_err(|error| {
+                error!(%error, "error getting users");
+                Status::internal("error getting users")
+            })?;
+
+        Ok(Response::new(GetMatchCostsResponse {
+            match_costs: costs
+                .into_iter()
+                .map(|(found_match, player_team, cost)| MatchCost {
+                    match_key: Some(MatchKey {
+                        stage_key: Some(StageKey {
+                            tournament_key: Some(TournamentKey { id: tournament_id }),
+                            stage_order: found_match.stage_order as u32,
+                        }),
+                        match_id: found_match.id as u32,
+                    }),
+                    user_id: cost.player_id as u32,
+                    team_key: player_team.map(|team_id| team_key(tournament_id, team_id)),
+                    maps_played: cost.maps_played,
+                    played_tiebreaker: cost.played_tiebreaker,
+                    match_cost: cost.match_cost,
+                })
+                .collect(),
+            players: aggregates
+                .into_iter()
+                .zip(users)
+                .map(|(aggregate, user)| AggregateMatchCost {
+                    user: Some(user.unwrap_or_else(|| User::missing(aggregate.player_id as u32))),
+                    team_key: player_teams
+                        .get(&aggregate.player_id)
+                        .map(|&team_id| team_key(tournament_id, team_id)),
+                    matches_played: aggregate.matches_played,
+                    maps_played: aggregate.maps_played,
+                    average_match_cost: aggregate.average_match_cost,
+                })
+                .collect(),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(sides = ?request.get_ref().sides))]
+    async fn get_head_to_head(
+        &self,
+        request: Request<GetHeadToHeadRequest>,
+    ) -> Result<Response<GetHeadToHeadResponse>, Status> {
+        let db = &self.0.db;
+        let request = request.into_inner();
+        let compared = match request.sides {
+            Some(Sides::Teams(TeamPair {
+                first: Some(first),
+                second: Some(second),
+            })) => {
+                let first = find_team(&first, db).await?;
+                let second = find_team(&second, db).await?;
+                if first.tournament_id != second.tournament_id {
+                    return Err(Status::invalid_argument(format!(
+                        "teams {} and {} play in different tournaments",
+                        first.id, second.id
+                    )));
+                }
+                if first.id == second.id {
+                    return Err(Status::invalid_argument(
+                        "cannot compare a team with itself",
+                    ));
+                }
+                if request
+                    .tournament_key
+                    .is_some_and(|key| key.id != first.tournament_id)
+                {
+                    return Err(Status::invalid_argument(format!(
+                        "teams {} and {} do not play in the requested tournament",
+                        first.id, second.id
+                    )));
+                }
+                Compared::Teams(first, second)
+            }
+            Some(Sides::Teams(_)) => return Err(Status::invalid_argument("missing team key")),
+            Some(Sides::Players(PlayerPair { first, second })) => {
+                if first == second {
+                    return Err(Status::invalid_argument(
+                        "cannot compare a player with themselves",
+                    ));
+                }
+                Compared::Players(first as i32, second as i32)
+            }
+            None => return Err(Status::invalid_argument("missing teams or players")),
+        };
+
+        let tournament_ids = match (&compared, request.tournament_key) {
+            (Compared::Teams(first, _), _) => vec![first.tournament_id],
+            (Compared::Players(..), Some(tournament_key)) => {
+                let tournament = tournament::Entity::find_active_by_id(tournament_key.id)
+                    .one(db)
+                    .await
+                    .map_err(|error| {
+                        error!(%error, "failed to get tournament from database");
+                        Status::internal("failed to get tournament")
+                    })?
+                    .ok_or_else(|| {
+                        Status::not_found(format!(
+                            "tournament with id {} does not exist",
+                            tournament_key.id
+                        ))
+                    })?;
+                vec![tournament.id]
+            }
+            (Compared::Players(..), None) => tournament::Entity::find_active()
+                .select_only()
+                .column(tournament::Column::Id)
+                .into_tuple::<i32>()
+                .all(db)
+                .await
+                .map_err(|error| {
+                    error!(%error, "failed to get tournaments from database");
+                    Status::internal("failed to get tournaments")
+                })?,
+        };
+
+        let mut query = versus_match::Entity::find()
+            .find_also_related(r#match::Entity)
+            .filter(r#match::Column::TournamentId.is_in(tournament_ids.iter().copied()));
+        query = match &compared {
+            Compared::Teams(first, second) => query.filter(
+                Condition::any()
+                    .add(
+                        Condition::all()
+                            .add(versus_match::Column::TeamRed.eq(first.id))
+                            .add(versus_match::Column::TeamBlue.eq(second.id)),
+                    )
+                    .add(
+                        Condition::all()
+                            .add(versus_match::Column::TeamRed.eq(second.id))
+                            .add(versus_match::Column::TeamBlue.eq(first.id)),
+                    ),
+            ),
+            &Compared::Players(first, second) => {
+                let mut players_by_match = HashMap::<_, HashSet<_>>::new();
+                for (match_id, player_id) in score::Entity::find()
+                    .select_only()
+                    .column(score::Column::MatchId)
+                    .column(score::Column::PlayerId)
+                    .filter(score::Column::PlayerId.is_in([first, second]))
+                    .filter(score::Column::TournamentId.is_in(tournament_ids.iter().copied()))
+                    .into_tuple::<(i32, i32)>()
+                    .all(db)
+                    .await
+                    .map_err(|error| {
+                        error!(%error, "failed to get scores from database");
+                        Status::internal("failed to get scores")
+                    })?
+                {
+                    players_by_match
+                        .entry(match_id)
+                        .or_default()
+                        .insert(player_id);
+                }
+                query.filter(
+                    versus_match::Column::MatchId.is_in(
+                        players_by_match
+                            .into_iter()
+                            .filter(|(_, players)| players.len() == 2)
+                            .map(|(match_id, _)| match_id),
+                    ),
+                )
+            }
+        };
+        let mut matches = query
+            .order_by_asc(r#match::Column::Date)
+            .order_by_asc(r#match::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get versus matches from database");
+                Status::internal("failed to get versus matches")
+            })?
+            .into_iter()
+            .filter_map(|(versus, found_match)| Some((versus, found_match?)))
+            .collect::<Vec<_>>();
+
+        let mut score_query = score::Entity::find().filter(
+            score::Column::MatchId.is_in(matches.iter().map(|(versus, _)| versus.match_id)),
+        );
+        let members = match &compared {
+            Compared::Teams(first, second) => team_member::Entity::find()
+                .filter(team_member::Column::TeamId.is_in([first.id, second.id]))
+                .all(db)
+                .await
+                .map_err(|error| {
+                    error!(%error, "failed to get team members from database");
+                    Status::internal("failed to get team members")
+                })?
+                .into_iter()
+                .map(|member| (member.team_id, member.user_id))
+                .collect::<HashSet<_>>(),
+            &Compared::Players(first, second) => {
+                score_query = score_query.filter(score::Column::PlayerId.is_in([first, second]));
+                team_member::Entity::find()
+                    .filter(team_member::Column::UserId.is_in([first, second]))
+                    .all(db)
+                    .await
+                    .map_err(|error| {
+                        error!(%error, "failed to get team members from database");
+                        Status::internal("failed to get team members")
+                    })?
+                    .into_iter()
+                    .map(|member| (member.team_id, member.user_id))
+                    .collect::<HashSet<_>>()
+            }
+        };
+        let scores = score_query.all(db).await.map_err(|error| {
+            error!(%error, "failed to get scores from database");
+            Status::internal("failed to get scores")
+        })?;
+
+        // Two players only played against each other in matches in which they were on opposite
+        // sides, not in those in which they played for the same team
+        if let &Compared::Players(first, second) = &compared {
+            let mut lobby_teams = HashMap::new();
+            for score in &scores {
+                if let Some(team) = score.team {
+                    lobby_teams.insert((score.match_id, score.player_id), team);
+                }
+            }
+            let side = |versus: &versus_match::Model, player_id| {
+                let lobby_team = lobby_teams
+                    .get(&(versus.match_id, player_id))
+                    .copied()
+                    .unwrap_or(LobbyTeam::None);
+                player_team(versus, &members, player_id, lobby_team)
+            };
+            matches.retain(
+                |(versus, _)| match (side(versus, first), side(versus, second)) {
+                    (Some(first_team), Some(second_team)) => first_team != second_team,
+                    _ => false,
+                },
+            );
+        }
+
+        let bracket_names = pool_bracket::Entity::find()
+            .filter(
+                pool_bracket::Column::TournamentId.is_in(
+                    matches
+                        .iter()
+                        .map(|(_, found_match)| found_match.tournament_id),
+                ),
+            )
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get pool brackets from database");
+                Status::internal("failed to get pool brackets")
+            })?
+            .into_iter()
+            .map(|bracket| {
+                (
+                    (
+                        bracket.tournament_id,
+                        bracket.stage_order,
+                        bracket.bracket_order,
+                    ),
+                    bracket.name,
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        // The combined scores of both sides on each map of each match
+        let versus_matches = matches
+            .iter()
+            .map(|(versus, _)| (versus.match_id, versus))
+            .collect::<HashMap<_, _>>();
+        let mut match_maps = HashMap::<_, BTreeMap<_, [Option<u64>; 2]>>::new();
+        for score in &scores {
+            let Some(versus) = versus_matches.get(&score.match_id) else {
+                continue;
+            };
+            let side = match &compared {
+                Compared::Teams(first, _) => player_team(
+                    versus,
+                    &members,
+                    score.player_id,
+                    score.team.unwrap_or(LobbyTeam::None),
+                )
+                .map(|team_id| if team_id == first.id { 0 } else { 1 }),
+                &Compared::Players(first, _) => Some(if score.player_id == first { 0 } else { 1 }),
+            };
+            let Some(side) = side else {
+                continue;
+            };
+            let map_scores = match_maps
+                .entry(score.match_id)
+                .or_default()
+                .entry((score.bracket_order, score.map_order))
+                .or_default();
+            *map_scores[side].get_or_insert(0) += score.score as u64;
+        }
+
+        let mut compared_maps = vec![];
+        let mut head_to_head_matches = vec![];
+        for (versus, found_match) in &matches {
+            let stage_key = StageKey {
+                tournament_key: Some(TournamentKey {
+                    id: found_match.tournament_id,
+                }),
+                stage_order: found_match.stage_order as u32,
+            };
+            let mut maps = vec![];
+            for (&(bracket_order, map_order), map_scores) in
+                match_maps.get(&versus.match_id).into_iter().flatten()
+            {
+                // Only maps that both sides played can be compared
+                let [Some(first_score), Some(second_score)] = *map_scores else {
+                    continue;
+                };
+                let bracket_name = bracket_names
+                    .get(&(
+                        found_match.tournament_id,
+                        found_match.stage_order,
+                        bracket_order,
+                    ))
+                    .map(String::as_str)
+                    .unwrap_or_default();
+                compared_maps.push((
+                    bracket_name,
+                    bracket_order,
+                    MapComparison {
+                        first_score,
+                        second_score,
+                    },
+                ));
+                maps.push(HeadToHeadMap {
+                    map_key: Some(PoolMapKey {
+                        bracket_key: Some(PoolBracketKey {
+                            stage_key: Some(stage_key),
+                            bracket_order: bracket_order as u32,
+                        }),
+                        map_order: map_order as u32,
+                    }),
+                    bracket_name: bracket_name.to_owned(),
+                    first_score,
+                    second_score,
+                });
+            }
+            head_to_head_matches.push(HeadToHeadMatch {
+                match_key: Some(MatchKey {
+                    stage_key: Some(stage_key),
+                    match_id: found_match.id as u32,
+                }),
+                date: Some(found_match.date.into()),
+                maps,
+            });
+        }
+
+        let total = record(compared_maps.iter().map(|(_, _, map)| map));
+        let brackets = bracket_records(compared_maps);
+        Ok(Response::new(GetHeadToHeadResponse {
+            matches: head_to_head_matches,
+            total: Some(head_to_head_record(total)),
+            brackets: brackets
+                .into_iter()
+                .map(|bracket| BracketHeadToHead {
+                    name: bracket.name,
+                    record: Some(head_to_head_record(bracket.record)),
+                })
+                .collect(),
+        }))
+    }
+}
@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+
+use futures::{stream::FuturesOrdered, TryStreamExt};
+use model::{team, team_member, tournament};
+use proto::{
+    keys::{TeamKey, TournamentKey},
+    osu::api::get_users,
+    team::{
+        team_service_server::TeamService, AddTeamMemberRequest, AddTeamMemberResponse,
+        CreateTeamRequest, CreateTeamResponse, DeleteTeamRequest, DeleteTeamResponse,
+        GetAllTeamsRequest, GetAllTeamsResponse, GetTeamRequest, GetTeamResponse,
+        RemoveTeamMemberRequest, RemoveTeamMemberResponse, Team, UpdateTeamRequest,
+        UpdateTeamResponse,
+    },
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    IntoActiveModel, LoaderTrait, ModelTrait, QueryFilter, QueryOrder, SqlErr, TransactionTrait,
+};
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use super::rating::spawn_rating_replay;
+use crate::{
+    osu::profile::{game_mode, get_profile},
+    permission::{require_permission, Permission},
+    restriction::Restrictions,
+    AppState,
+};
+
+/// Finds the team with the given key and makes sure it belongs to the tournament in the key.
+pub async fn find_team(team_key: &TeamKey, db: &DatabaseConnection) -> tonic::Result<team::Model> {
+    let tournament_key = team_key
+        .tournament_key
+        .as_ref()
+        .ok_or_else(|| Status::invalid_argument("missing tournament key in team key"))?;
+
+    team::Entity::find_by_id(team_key.team_id as i32)
+        .filter(team::Column::TournamentId.eq(tournament_key.id))
+        // Teams of deleted tournaments are hidden along with their tournament
+        .inner_join(tournament::Entity)
+        .filter(tournament::Column::DeletedAt.is_null())
+        .one(db)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to get team from database");
+            Status::internal("failed to get team")
+        })?
+        .ok_or_else(|| {
+            Status::not_found(format!(
+                "team {} in tournament {} does not exist",
+                team_key.team_id, tournament_key.id
+            ))
+        })
+}
+
+/// Maps unique constraint violations to an `already_exists` status and everything else to an
+/// internal error.
+fn team_write_error(error: DbErr, name: &str) -> Status {
+    match error.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => {
+            Status::already_exists(format!("team with name '{name}' already exists"))
+        }
+        _ => {
+            error!(%error, "failed to write team to database");
+            Status::internal("failed to write team")
+        }
+    }
+}
+
+/// Collects the registered ranks of the given team members keyed by their user id
+fn registered_ranks(members: &[team_member::Model]) -> HashMap<u32, u32> {
+    members
+        .iter()
+        .filter_map(|member| Some((member.user_id as u32, member.registered_rank? as u32)))
+        .collect()
+}
+
+/// Inserts a team along with its members, given as their user ids and registered ranks.
+/// This happens in a single transaction, so either all of it is created or none of it.
+async fn insert_team(
+    db: &DatabaseConnection,
+    team: team::ActiveModel,
+    members: &[(i32, Option<i32>)],
+) -> tonic::Result<(team::Model, Vec<team_member::Model>)> {
+    let name = team.name.clone().unwrap();
+    let txn = db.begin().await.map_err(|error| {
+        error!(%error, "failed to start transaction");
+        Status::internal("failed to create team")
+    })?;
+
+    let team = team
+        .insert(&txn)
+        .await
+        .map_err(|error| team_write_error(error, &name))?;
+
+    let members = members
+        .iter()
+        .map(|&(user_id, registered_rank)| team_member::Model {
+            team_id: team.id,
+            user_id,
+            registered_rank,
+        })
+        .collect::<Vec<_>>();
+    if !members.is_empty() {
+        team_member::Entity::insert_many(
+            members
+                .iter()
+                .cloned()
+                .map(IntoActiveModel::into_active_model),
+        )
+        .exec(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to insert team members");
+            Status::internal("failed to insert team members")
+        })?;
+    }
+
+    txn.commit().await.map_err(|error| {
+        error!(%error, "failed to commit transaction");
+        Status::internal("failed to create team")
+    })?;
+
+    Ok((team, members))
+}
+
+/// Deletes a team along with its members.
+/// This happens in a single transaction, so a team that has already played matches keeps its
+/// members.
+async fn delete_team(db: &DatabaseConnection, team: team::Model) -> tonic::Result<()> {
+    let txn = db.begin().await.map_err(|error| {
+        error!(%error, "failed to start transaction");
+        Status::internal("failed to delete team")
+    })?;
+
+    // The members reference the team, so they have to go first
+    team_member::Entity::delete_many()
+        .filter(team_member::Column::TeamId.eq(team.id))
+        .exec(&txn)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to delete team members");
+            Status::internal("failed to delete team members")
+        })?;
+
+    team.delete(&txn)
+        .await
+        .map_err(|error| match error.sql_err() {
+            Some(SqlErr::ForeignKeyConstraintViolation(_)) => {
+                Status::failed_precondition("team has already played matches")
+            }
+            _ => {
+                error!(%error, "failed to delete team");
+                Status::internal("failed to delete team")
+            }
+        })?;
+
+    txn.commit().await.map_err(|error| {
+        error!(%error, "failed to commit transaction");
+        Status::internal("failed to delete team")
+    })
+}
+
+pub struct TeamServiceImpl(pub AppState);
+
+impl TeamServiceImpl {
+    /// Fetches the profiles of the given users in the tournament's mode and makes sure they
+    /// fulfill the tournament's restrictions.
+    /// Returns each user together with the rank that should be stored on registration, so the
+    /// members don't have to be fetched again for the response.
+    async fn check_registration(
+        &self,
+        tournament: &tournament::Model,
+        user_ids: &[u32],
+    ) -> tonic::Result<Vec<(proto::osu::User, Option<u32>)>> {
+        let mode = game_mode(tournament.mode);
+        let profiles = user_ids
+            .iter()
+            .map(|&user_id| get_profile(&self.0.cache, &self.0.osu, user_id, mode))
+            .collect::<FuturesOrdered<_>>()
+            .try_collect::<Vec<_>>();
+        let (profiles, restrictions) =
+            tokio::join!(profiles, Restrictions::load(tournament, &self.0.db));
+        let profiles = profiles
+            .map_err(|error| {
+                error!(%error, "could not get user profile from osu api");
+                Status::internal("could not get user profile from osu api")
+            })?
+            .into_iter()
+            .zip(user_ids)
+            .map(|(profile, user_id)| {
+                profile
+                    .ok_or_else(|| Status::not_found(format!("osu user {user_id} does not exist")))
+            })
+            .collect::<tonic::Result<Vec<_>>>()?;
+
+        let restrictions = restrictions?;
+        restrictions.check_all(&profiles)?;
+        Ok(profiles
+            .into_iter()
+            .map(|profile| {
+                let rank = restrictions.registration_rank(&profile);
+                (profile.user, rank)
+            })
+            .collect())
+    }
+
+    /// Fetches the osu user data of all given team members
+    async fn fetch_members(
+        &self,
+        members: &[team_member::Model],
+    ) -> tonic::Result<Vec<proto::osu::User>> {
+        let user_ids = members
+            .iter()
+            .map(|member| member.user_id as u32)
+            .collect::<Vec<_>>();
+        let users = get_users(&self.0.cache, &self.0.osu, &user_ids)
+            .await
+            .map_err(|error| {
+                error!(%error, "could not get team member from osu api");
+                Status::internal("could not get team member from osu api")
+            })?;
+        Ok(user_ids
+            .into_iter()
+            .zip(users)
+            .map(|(user_id, user)| user.unwrap_or_else(|| proto::osu::User::missing(user_id)))
+            .collect())
+    }
+
+    /// Loads a team's members and converts the team into the on-the-wire format
+    async fn to_proto(&self, team: team::Model) -> tonic::Result<Team> {
+        let members = team
+            .find_related(team_member::Entity)
+            .order_by_asc(team_member::Column::UserId)
+            .all(&self.0.db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get team members from database");
+                Status::internal("failed to get team members")
+            })?;
+
+        Ok(Team {
+            team_key: Some(TeamKey {
+                tournament_key: Some(TournamentKey {
+                    id: team.tournament_id,
+                }),
+                team_id: team.id as u32,
+            }),
+            name: team.name,
+            user: self.fetch_members(&members).await?,
+            registered_ranks: registered_ranks(&members),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl TeamService for TeamServiceImpl {
+    type GetAllStream =
+        futures::stream::Iter<std::vec::IntoIter<Result<GetAllTeamsResponse, Status>>>;
+
+    #[tracing::instrument(skip_all)]
+    async fn get_all(
+        &self,
+        request: Request<GetAllTeamsRequest>,
+    ) -> Result<Response<Self::GetAllStream>, Status> {
+        let db = &self.0.db;
+        let tournament_key = request
+            .get_ref()
+            .tournament_id
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing tournament key"))?;
+
+        let Some(tournament) = tournament::Entity::find_active_by_id(tournament_key.id)
+            .one(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get tournament from database");
+                Status::internal("failed to get tournament")
+            })?
+        else {
+            return Err(Status::not_found(format!(
+                "tournament with id {} does not exist",
+                tournament_key.id
+            )));
+        };
+
+        let teams = tournament
+            .find_related(team::Entity)
+            .order_by_asc(team::Column::Name)
+            .all(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get teams from database");
+                Status::internal("failed to get teams")
+            })?;
+
+        let members = teams
+            .load_many(
+                team_member::Entity::find().order_by_asc(team_member::Column::UserId),
+                db,
+            )
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get team members from database");
+                Status::internal("failed to get team members")
+            })?;
+
+        // Fetch the user data of every team's members at once
+        let all_members = members.iter().flatten().cloned().collect::<Vec<_>>();
+        let mut users = self.fetch_members(&all_members).await?.into_iter();
+
+        // Transform the teams to the on-the-wire format
+        let teams = teams
+            .into_iter()
+            .zip(members)
+            .map(|(team, members)| {
+                Ok(GetAllTeamsResponse {
+                    team: Some(Team {
+                        team_key: Some(TeamKey {
+                            tournament_key: Some(*tournament_key),
+                            team_id: team.id as u32,
+                        }),
+                        name: team.name,
+                        user: users.by_ref().take(members.len()).collect(),
+                        registered_ranks: registered_ranks(&members),
+                    }),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Response::new(futures::stream::iter(teams)))
+    }
+
+    #[tracing::instrument(skip_all, fields(team_id = request.get_ref().team_id))]
+    async fn get(
+        &self,
+        request: Request<GetTeamRequest>,
+    ) -> Result<Response<GetTeamResponse>, Status> {
+        let team_id = request.get_ref().team_id;
+        let team = team::Entity::find_by_id(team_id as i32)
+            // Teams of deleted tournaments are hidden along with their tournament
+            .inner_join(tournament::Entity)
+            .filter(tournament::Column::DeletedAt.is_null())
+            .one(&self.0.db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get team from database");
+                Status::internal("failed to get team")
+            })?;
+
+        let team = match team {
+            Some(team) => Some(self.to_proto(team).await?),
+            None => None,
+        };
+
+        Ok(Response::new(GetTeamResponse { team }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn create(
+        &self,
+        request: Request<CreateTeamRequest>,
+    ) -> Result<Response<CreateTeamResponse>, Status> {
+        use ActiveValue as A;
+        let db = &self.0.db;
+        let tournament_key = request
+            .get_ref()
+            .tournament_key
+            .ok_or_else(|| Status::invalid_argument("missing tournament key"))?;
+        require_permission(&request, tournament_key.id, Permission::EditTeams, db).await?;
+        let request = request.into_inner();
+
+        // We don't allow empty team names
+        if request.name.trim().is_empty() {
+            return Err(Status::invalid_argument("empty team name"));
+        }
+
+        let tournament = tournament::Entity::find_active_by_id(tournament_key.id)
+            .one(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get tournament from database");
+                Status::internal("failed to get tournament")
+            })?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "tournament with id {} does not exist",
+                    tournament_key.id
+                ))
+            })?;
+
+        // Make sure all members are actual osu users before inserting anything
+        let mut user_ids = request.user_ids;
+        user_ids.sort_unstable();
+        user_ids.dedup();
+        let (users, ranks): (Vec<_>, Vec<_>) = self
+            .check_registration(&tournament, &user_ids)
+            .await?
+            .into_iter()
+            .unzip();
+
+        let team = team::ActiveModel {
+            id: A::NotSet,
+            tournament_id: A::Set(tournament_key.id),
+            name: A::Set(request.name.clone()),
+        };
+        let members = user_ids
+            .iter()
+            .zip(ranks)
+            .map(|(&user_id, rank)| (user_id as i32, rank.map(|rank| rank as i32)))
+            .collect::<Vec<_>>();
+        let (team, members) = insert_team(db, team, &members).await?;
+
+        Ok(Response::new(CreateTeamResponse {
+            team: Some(Team {
+                team_key: Some(TeamKey {
+                    tournament_key: Some(tournament_key),
+                    team_id: team.id as u32,
+                }),
+                name: team.name,
+                user: users,
+                registered_ranks: registered_ranks(&members),
+            }),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key))]
+    async fn update(
+        &self,
+        request: Request<UpdateTeamRequest>,
+    ) -> Result<Response<UpdateTeamResponse>, Status> {
+        use ActiveValue as A;
+        let db = &self.0.db;
+        let team_key = request
+            .get_ref()
+            .key
+            .ok_or_else(|| Status::invalid_argument("missing team key"))?;
+        let team = find_team(&team_key, db).await?;
+        require_permission(&request, team.tournament_id, Permission::EditTeams, db).await?;
+        let request = request.into_inner();
+
+        let mut team = team.into_active_model();
+        if let Some(name) = request.name {
+            // We don't allow empty team names
+            if name.trim().is_empty() {
+                return Err(Status::invalid_argument("empty team name"));
+            }
+            team.name = A::Set(name);
+        }
+
+        let name = team.name.clone().unwrap();
+        let team = team
+            .update(db)
+            .await
+            .map_err(|error| team_write_error(error, &name))?;
+
+        Ok(Response::new(UpdateTeamResponse {
+            team: Some(self.to_proto(team).await?),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key))]
+    async fn delete(
+        &self,
+        request: Request<DeleteTeamRequest>,
+    ) -> Result<Response<DeleteTeamResponse>, Status> {
+        let db = &self.0.db;
+        let team_key = request
+            .get_ref()
+            .key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing team key"))?;
+        let team = find_team(team_key, db).await?;
+        require_permission(&request, team.tournament_id, Permission::EditTeams, db).await?;
+
+        delete_team(db, team).await?;
+
+        Ok(Response::new(DeleteTeamResponse {}))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key, user_id = request.get_ref().user_id))]
+    async fn add_member(
+        &self,
+        request: Request<AddTeamMemberRequest>,
+    ) -> Result<Response<AddTeamMemberResponse>, Status> {
+        use ActiveValue as A;
+        let db = &self.0.db;
+        let team_key = request
+            .get_ref()
+            .key
+            .ok_or_else(|| Status::invalid_argument("missing team key"))?;
+        let team = find_team(&team_key, db).await?;
+        require_permission(&request, team.tournament_id, Permission::EditTeams, db).await?;
+        let request = request.into_inner();
+
+        let tournament = tournament::Entity::find_active_by_id(team.tournament_id)
+            .one(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get tournament from database");
+                Status::internal("failed to get tournament")
+            })?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "tournament with id {} does not exist",
+                    team.tournament_id
+                ))
+            })?;
+        let (user, rank) = self
+            .check_registration(&tournament, &[request.user_id])
+            .await?
+            .remove(0);
+
+        // The rank is stored so later rank changes don't invalidate the team
+        team_member::ActiveModel {
+            team_id: A::Set(team.id),
+            user_id: A::Set(user.user_id as i32),
+            registered_rank: A::Set(rank.map(|rank| rank as i32)),
+        }
+        .insert(db)
+        .await
+        .map_err(|error| match error.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => Status::already_exists(format!(
+                "user {} is already a member of team {}",
+                user.user_id, team.id
+            )),
+            _ => {
+                error!(%error, "failed to insert team member");
+                Status::internal("failed to insert team member")
+            }
+        })?;
+
+        // The sides players are rated on depend on the team members
+        spawn_rating_replay(self.0.clone());
+
+        Ok(Response::new(AddTeamMemberResponse { user: Some(user) }))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key, user_id = request.get_ref().user_id))]
+    async fn remove_member(
+        &self,
+        request: Request<RemoveTeamMemberRequest>,
+    ) -> Result<Response<RemoveTeamMemberResponse>, Status> {
+        let db = &self.0.db;
+        let team_key = request
+            .get_ref()
+            .key
+            .ok_or_else(|| Status::invalid_argument("missing team key"))?;
+        let team = find_team(&team_key, db).await?;
+        require_permission(&request, team.tournament_id, Permission::EditTeams, db).await?;
+        let request = request.into_inner();
+
+        let delete_result = team_member::Entity::delete_by_id((team.id, request.user_id as i32))
+            .exec(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to delete team member");
+                Status::internal("failed to delete team member")
+            })?;
+
+        // If no member was deleted, that means that the user was not in the team
+        if delete_result.rows_affected == 0 {
+            return Err(Status::not_found(format!(
+                "user {} is not a member of team {}",
+                request.user_id, team.id
+            )));
+        }
+
+        // The sides players are rated on depend on the team members
+        spawn_rating_replay(self.0.clone());
+
+        Ok(Response::new(RemoveTeamMemberResponse {}))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sea_orm::{DatabaseBackend, DbErr, MockDatabase, Value};
+
+    use super::*;
+    use crate::routes::test_util::{rolled_back, statement};
+
+    #[tokio::test]
+    async fn create_rolls_back_on_failure() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![team::Model {
+                id: 1,
+                tournament_id: 1,
+                name: "Test Team".to_owned(),
+            }]])
+            // Inserting the members fails
+            .append_query_errors([DbErr::Custom("injected failure".to_owned())])
+            .into_connection();
+
+        let team = team::ActiveModel {
+            id: ActiveValue::NotSet,
+            tournament_id: ActiveValue::Set(1),
+            name: ActiveValue::Set("Test Team".to_owned()),
+        };
+        let created = insert_team(&db, team, &[(2, Some(100)), (3, None)]).await;
+
+        assert!(created.is_err());
+        assert_eq!(
+            db.into_transaction_log(),
+            [rolled_back([
+                statement(
+                    r#"INSERT INTO "team" ("tournament_id", "name") VALUES ($1, $2) RETURNING "id", "tournament_id", "name""#,
+                    [1i32.into(), "Test Team".into()],
+                ),
+                statement(
+                    r#"INSERT INTO "team_member" ("team_id", "user_id", "registered_rank") VALUES ($1, $2, $3), ($4, $5, $6) RETURNING "team_id", "user_id""#,
+                    [
+                        1i32.into(),
+                        2i32.into(),
+                        100i32.into(),
+                        1i32.into(),
+                        3i32.into(),
+                        Value::Int(None),
+                    ],
+                ),
+            ])]
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//! Helpers for testing the routes against a mock database
+
+use std::collections::BTreeMap;
+
+use sea_orm::{DbBackend, MockExecResult, Statement, Transaction, Value};
+
+/// The result of a statement that changed a single row
+pub fn exec_ok() -> MockExecResult {
+    MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+    }
+}
+
+/// A row with the result of a count query
+pub fn count(n: i64) -> BTreeMap<&'static str, Value> {
+    BTreeMap::from([("num_items", Value::BigInt(Some(n)))])
+}
+
+/// A postgres statement with the given values bound to its parameters
+pub fn statement(sql: &str, values: impl IntoIterator<Item = Value>) -> Statement {
+    Statement::from_sql_and_values(DbBackend::Postgres, sql, values)
+}
+
+/// The log entry of a transaction that ran the given statements and was committed
+pub fn committed(statements: impl IntoIterator<Item = Statement>) -> Transaction {
+    transaction(statements, "COMMIT")
+}
+
+/// The log entry of a transaction that ran the given statements and was rolled back
+pub fn rolled_back(statements: impl IntoIterator<Item = Statement>) -> Transaction {
+    transaction(statements, "ROLLBACK")
+}
+
+fn transaction(statements: impl IntoIterator<Item = Statement>, end: &str) -> Transaction {
+    Transaction::many(
+        [Statement::from_string(DbBackend::Postgres, "BEGIN")]
+            .into_iter()
+            .chain(statements)
+            .chain([Statement::from_string(DbBackend::Postgres, end)]),
+    )
+}
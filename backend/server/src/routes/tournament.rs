@@ -1,31 +1,44 @@
 use futures::TryFutureExt;
 use itertools::izip;
 use sea_orm::{
-    query::*, ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait,
+    query::*,
+    sea_query::{Expr, OnConflict},
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
     IntoActiveModel, ModelTrait,
 };
-use sqlx::types::chrono::NaiveDateTime;
+use sqlx::types::chrono::{NaiveDateTime, Utc};
 use tonic::{Request, Response, Status};
 
-use model::{sea_orm_active_enums::OsuMode, *};
+use model::{
+    sea_orm_active_enums::{OsuMode, TournamentRole},
+    *,
+};
 use proto::{
     keys::StageKey,
     tournaments::{
         Country, CountryList, CreateTournamentRequest, CreateTournamentResponse,
         DeleteTournamentRequest, DeleteTournamentResponse, GetAllTournamentsRequest,
-        GetAllTournamentsResponse, GetTournamentRequest, RangeList, UpdateTournamentRequest,
-        UpdateTournamentResponse,
+        GetAllTournamentsResponse, GetTournamentRequest, PurgeTournamentRequest,
+        PurgeTournamentResponse, RangeList, RestoreTournamentRequest, RestoreTournamentResponse,
+        UpdateTournamentRequest, UpdateTournamentResponse,
     },
 };
 use proto::{
     keys::TournamentKey,
     tournaments::{
-        tournament_service_server::TournamentService, GetTournamentResponse, RankRange, Tournament,
+        tournament_service_server::TournamentService, AddStaffRoleRequest, AddStaffRoleResponse,
+        BwsFormula, GetStaffRequest, GetStaffResponse, GetTournamentResponse, RankRange,
+        RemoveStaffRoleRequest, RemoveStaffRoleResponse, StaffMember, Tournament,
     },
 };
 use tracing::error;
 
-use crate::{routes::convert_start_end, AppState};
+use super::rating::spawn_rating_replay;
+use crate::{
+    permission::{require_login, require_permission, Permission},
+    routes::convert_start_end,
+    AppState,
+};
 
 pub async fn find_stage(
     stage_key: &StageKey,
@@ -36,7 +49,7 @@ pub async fn find_stage(
         .as_ref()
         .ok_or_else(|| Status::invalid_argument("missing tournament key in stage key"))?;
 
-    let res = tournament::Entity::find_by_id(tournament_key.id)
+    let res = tournament::Entity::find_active_by_id(tournament_key.id)
         .find_also_related(stage::Entity)
         .filter(stage::Column::StageOrder.eq(stage_key.stage_order))
         .one(db)
@@ -63,6 +76,232 @@ pub async fn find_stage(
     Ok((tournament, stage))
 }
 
+/// Inserts a tournament along with its host and its restrictions.
+/// This happens in a single transaction, so either all of it is created or none of it.
+async fn insert_tournament(
+    db: &DatabaseConnection,
+    tournament: tournament::ActiveModel,
+    host_id: i32,
+    rank_restrictions: &[RankRange],
+    country_restrictions: &[Country],
+) -> tonic::Result<tournament::Model> {
+    use ActiveValue as A;
+    let txn = db
+        .begin()
+        .await
+        .map_err(|e| Status::internal(format!("could not start transaction: {e}")))?;
+
+    let tournament = tournament
+        .insert(&txn)
+        .await
+        .map_err(|e| Status::internal(format!("failed to insert tournament: {e}")))?;
+
+    // The user who created the tournament is its host
+    tournament_staff::ActiveModel {
+        tournament_id: A::Set(tournament.id),
+        user_id: A::Set(host_id),
+        role: A::Set(TournamentRole::Host),
+    }
+    .insert(&txn)
+    .await
+    .map_err(|error| {
+        error!(%error, "failed to insert tournament host");
+        Status::internal("failed to create tournament host")
+    })?;
+
+    for (i, range) in rank_restrictions.iter().enumerate() {
+        let restriction = rank_restriction::ActiveModel {
+            tournament_id: A::Set(tournament.id),
+            tier: A::Set(i as i16),
+            min: A::Set(range.min as i32),
+            max: A::Set(range.max as i32),
+        };
+
+        restriction
+            .insert(&txn)
+            .await
+            .map_err(|e| Status::internal(format!("failed to create rank restriction: {e}")))?;
+    }
+
+    for country in country_restrictions {
+        let restriction = country_restriction::ActiveModel {
+            tournament_id: A::Set(tournament.id),
+            country_code: A::Set(country.country_code.clone()),
+        };
+
+        restriction
+            .insert(&txn)
+            .await
+            .map_err(|e| Status::internal(format!("failed to create country restriction: {e}")))?;
+    }
+
+    txn.commit()
+        .await
+        .map_err(|e| Status::internal(format!("could not commit tournament: {e}")))?;
+
+    Ok(tournament)
+}
+
+/// Updates a tournament and replaces its rank restrictions, if new ones are given.
+/// This happens in a single transaction, so either all of it is updated or none of it.
+async fn update_tournament(
+    db: &DatabaseConnection,
+    tournament: tournament::ActiveModel,
+    rank_restrictions: Option<&[RankRange]>,
+) -> tonic::Result<()> {
+    use ActiveValue as A;
+    let tournament_id = tournament.id.clone().unwrap();
+    let txn = db
+        .begin()
+        .await
+        .map_err(|e| Status::internal(format!("could not start transaction: {e}")))?;
+
+    if let Some(ranges) = rank_restrictions {
+        rank_restriction::Entity::delete_many()
+            .filter(rank_restriction::Column::TournamentId.eq(tournament_id))
+            .exec(&txn)
+            .await
+            .map_err(|e| Status::internal(format!("could not delete rank restrictions: {e}")))?;
+
+        for (i, range) in ranges.iter().enumerate() {
+            let restriction = rank_restriction::ActiveModel {
+                tournament_id: A::Set(tournament_id),
+                tier: A::Set(i as i16),
+                min: A::Set(range.min as i32),
+                max: A::Set(range.max as i32),
+            };
+
+            restriction
+                .insert(&txn)
+                .await
+                .map_err(|e| Status::internal(format!("failed to create rank restriction: {e}")))?;
+        }
+    }
+
+    tournament
+        .update(&txn)
+        .await
+        .map_err(|e| Status::internal(format!("failed to update tournament: {e}")))?;
+
+    txn.commit()
+        .await
+        .map_err(|e| Status::internal(format!("could not commit tournament update: {e}")))?;
+
+    Ok(())
+}
+
+/// Deletes a tournament along with everything that belongs to it, starting with the rows that
+/// reference others. This happens in a single transaction, so either everything is deleted or
+/// nothing is.
+async fn purge_tournament(db: &DatabaseConnection, tournament_id: i32) -> tonic::Result<()> {
+    let txn = db
+        .begin()
+        .await
+        .map_err(|e| Status::internal(format!("could not start transaction: {e}")))?;
+    let delete_error = |e: DbErr| Status::internal(format!("could not purge tournament: {e}"));
+
+    let matches = r#match::Entity::find()
+        .select_only()
+        .column(r#match::Column::Id)
+        .filter(r#match::Column::TournamentId.eq(tournament_id))
+        .into_query();
+    let teams = team::Entity::find()
+        .select_only()
+        .column(team::Column::Id)
+        .filter(team::Column::TournamentId.eq(tournament_id))
+        .into_query();
+
+    pick_ban::Entity::delete_many()
+        .filter(pick_ban::Column::TournamentId.eq(tournament_id))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    score::Entity::delete_many()
+        .filter(score::Column::TournamentId.eq(tournament_id))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    match_link::Entity::delete_many()
+        .filter(match_link::Column::MatchId.in_subquery(matches.clone()))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    qualifier_run::Entity::delete_many()
+        .filter(qualifier_run::Column::MatchId.in_subquery(matches.clone()))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    versus_match::Entity::delete_many()
+        .filter(versus_match::Column::MatchId.in_subquery(matches))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    r#match::Entity::delete_many()
+        .filter(r#match::Column::TournamentId.eq(tournament_id))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    pool_map::Entity::delete_many()
+        .filter(pool_map::Column::TournamentId.eq(tournament_id))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    pool_bracket::Entity::delete_many()
+        .filter(pool_bracket::Column::TournamentId.eq(tournament_id))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    stage::Entity::delete_many()
+        .filter(stage::Column::TournamentId.eq(tournament_id))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    team_member::Entity::delete_many()
+        .filter(team_member::Column::TeamId.in_subquery(teams))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    team::Entity::delete_many()
+        .filter(team::Column::TournamentId.eq(tournament_id))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    rank_restriction::Entity::delete_many()
+        .filter(rank_restriction::Column::TournamentId.eq(tournament_id))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    country_restriction::Entity::delete_many()
+        .filter(country_restriction::Column::TournamentId.eq(tournament_id))
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+    // Staff roles are deleted along with the tournament
+    tournament::Entity::delete_by_id(tournament_id)
+        .exec(&txn)
+        .await
+        .map_err(delete_error)?;
+
+    txn.commit()
+        .await
+        .map_err(|e| Status::internal(format!("could not commit tournament purge: {e}")))
+}
+
+/// Makes sure that a BWS formula yields ranks that are no worse than the global rank
+fn validate_bws_formula(formula: &BwsFormula) -> tonic::Result<()> {
+    if !(formula.base > 0.0 && formula.base <= 1.0) {
+        return Err(Status::invalid_argument(
+            "the BWS base must be greater than 0 and at most 1",
+        ));
+    }
+    if !(formula.exponent >= 0.0 && formula.exponent.is_finite()) {
+        return Err(Status::invalid_argument(
+            "the BWS exponent must be a non-negative number",
+        ));
+    }
+    Ok(())
+}
+
 pub struct TournamentServiceImpl(pub AppState);
 
 #[tonic::async_trait]
@@ -77,7 +316,7 @@ impl TournamentService for TournamentServiceImpl {
         _request: Request<GetAllTournamentsRequest>,
     ) -> Result<Response<Self::GetAllStream>, Status> {
         let db = &self.0.db;
-        let tournaments = tournament::Entity::find()
+        let tournaments = tournament::Entity::find_active()
             .all(db)
             .map_err(|e| Status::internal(format!("failed to get all tournaments: {e}")))
             .await?;
@@ -108,7 +347,7 @@ impl TournamentService for TournamentServiceImpl {
             .iter()
             .map(|tournament| match &tournament.banner {
                 Some(banner_name) => {
-                    return std::fs::read(self.0.paths.banner(banner_name)).map(Option::Some);
+                    std::fs::read(self.0.paths.banner(banner_name)).map(Option::Some)
                 }
                 None => Ok(None),
             })
@@ -158,6 +397,10 @@ impl TournamentService for TournamentServiceImpl {
                             banner,
                             start_date: tournament.start_date.map(Into::into),
                             end_date: tournament.end_date.map(Into::into),
+                            bws_formula: Some(BwsFormula {
+                                base: tournament.bws_base,
+                                exponent: tournament.bws_exponent,
+                            }),
                         }),
                         rank_restrictions,
                         country_restrictions,
@@ -183,7 +426,7 @@ impl TournamentService for TournamentServiceImpl {
                 Status::invalid_argument("missing tournament id in request")
             })?
             .id;
-        let Some(tournament) = tournament::Entity::find_by_id(id)
+        let Some(tournament) = tournament::Entity::find_active_by_id(id)
             .one(&self.0.db)
             .await
             .map_err(|e| {
@@ -263,6 +506,10 @@ impl TournamentService for TournamentServiceImpl {
                 banner,
                 start_date: tournament.start_date.map(Into::into),
                 end_date: tournament.start_date.map(Into::into),
+                bws_formula: Some(BwsFormula {
+                    base: tournament.bws_base,
+                    exponent: tournament.bws_exponent,
+                }),
             }),
             country_restrictions: Some(CountryList { countries }),
             rank_restrictions: Some(RangeList { ranges }),
@@ -277,6 +524,7 @@ impl TournamentService for TournamentServiceImpl {
         request: Request<CreateTournamentRequest>,
     ) -> Result<Response<CreateTournamentResponse>, Status> {
         use ActiveValue as A;
+        let host_id = require_login(&request)?.osu_user_id;
         let request = request.into_inner();
         let tournament = request
             .tournament
@@ -286,6 +534,9 @@ impl TournamentService for TournamentServiceImpl {
 
         // TODO Validate stuff like the rank ranges being in the right order
         let (start_date, end_date) = convert_start_end(tournament.start_date, tournament.end_date)?;
+        if let Some(ref formula) = tournament.bws_formula {
+            validate_bws_formula(formula)?;
+        }
 
         let tournament_model = model::tournament::ActiveModel {
             id: A::NotSet,
@@ -299,41 +550,38 @@ impl TournamentService for TournamentServiceImpl {
             banner: A::NotSet,
             start_date: A::Set(start_date),
             end_date: A::Set(end_date),
+            // Without a formula, the database default is used
+            bws_base: tournament
+                .bws_formula
+                .map_or(A::NotSet, |formula| A::Set(formula.base)),
+            bws_exponent: tournament
+                .bws_formula
+                .map_or(A::NotSet, |formula| A::Set(formula.exponent)),
+            deleted_at: A::NotSet,
         };
-        let tournament_model = tournament_model.insert(&self.0.db).await.map_err(|e| {
+        let rank_restrictions = request
+            .rank_restrictions
+            .map(|list| list.ranges)
+            .unwrap_or_default();
+        let country_restrictions = request
+            .country_restrictions
+            .map(|list| list.countries)
+            .unwrap_or_default();
+        let tournament_model = insert_tournament(
+            &self.0.db,
+            tournament_model,
+            host_id as i32,
+            &rank_restrictions,
+            &country_restrictions,
+        )
+        .await
+        .map_err(|e| {
             Status::internal(format!(
-                "failed to create tournament with name '{name}': {e}"
+                "failed to create tournament with name '{name}': {}",
+                e.message()
             ))
         })?;
 
-        if let Some(ref rank_restrictions) = request.rank_restrictions {
-            for (i, range) in rank_restrictions.ranges.iter().enumerate() {
-                let restriction = model::rank_restriction::ActiveModel {
-                    tournament_id: A::Set(tournament_model.id),
-                    tier: A::Set(i as i16),
-                    min: A::Set(range.min as i32),
-                    max: A::Set(range.max as i32),
-                };
-
-                restriction.insert(&self.0.db).await.map_err(|e| {
-                    Status::internal(format!("failed to create rank restriction: {e}"))
-                })?;
-            }
-        }
-
-        if let Some(ref country_restrictions) = request.country_restrictions {
-            for country in country_restrictions.countries.iter() {
-                let restriction = model::country_restriction::ActiveModel {
-                    tournament_id: A::Set(tournament_model.id),
-                    country_code: A::Set(country.country_code.clone()),
-                };
-
-                restriction.insert(&self.0.db).await.map_err(|e| {
-                    Status::internal(format!("failed to create country restriction: {e}"))
-                })?;
-            }
-        }
-
         Ok(Response::new(CreateTournamentResponse {
             key: Some(TournamentKey {
                 id: tournament_model.id,
@@ -352,7 +600,14 @@ impl TournamentService for TournamentServiceImpl {
             .ok_or_else(|| Status::invalid_argument("missing tournament id"))?
             .id;
         use ActiveValue as A;
-        let model = tournament::Entity::find_by_id(tournament_id)
+        require_permission(
+            &request,
+            tournament_id,
+            Permission::EditTournament,
+            &self.0.db,
+        )
+        .await?;
+        let model = tournament::Entity::find_active_by_id(tournament_id)
             .one(&self.0.db)
             .await
             .map_err(|e| Status::internal(format!("failed to fetch tournament: {e}")))?
@@ -370,33 +625,10 @@ impl TournamentService for TournamentServiceImpl {
             model.shorthand = A::Set(shorthand.clone());
         }
 
-        if let Some(ranges) = request
-            .get_ref()
-            .rank_restrictions
-            .as_ref()
-            .map(|r| r.ranges.as_slice())
-        {
-            // TODO Probably validate rank ranges
-            rank_restriction::Entity::delete_many()
-                .filter(rank_restriction::Column::TournamentId.eq(tournament_id))
-                .exec(&self.0.db)
-                .await
-                .map_err(|e| {
-                    Status::internal(format!("could not delete rank restrictions: {e}"))
-                })?;
-
-            for (i, range) in ranges.iter().enumerate() {
-                let restriction = rank_restriction::ActiveModel {
-                    tournament_id: A::Set(tournament_id),
-                    tier: A::Set(i as i16),
-                    min: A::Set(range.min as i32),
-                    max: A::Set(range.max as i32),
-                };
-
-                restriction.insert(&self.0.db).await.map_err(|e| {
-                    Status::internal(format!("failed to create rank restriction: {e}"))
-                })?;
-            }
+        if let Some(formula) = request.get_ref().bws_formula.as_ref() {
+            validate_bws_formula(formula)?;
+            model.bws_base = A::Set(formula.base);
+            model.bws_exponent = A::Set(formula.exponent);
         }
 
         if let Some(format) = request.get_ref().format {
@@ -407,10 +639,13 @@ impl TournamentService for TournamentServiceImpl {
             model.bws = A::Set(bws);
         }
 
-        model
-            .update(&self.0.db)
-            .await
-            .map_err(|e| Status::internal(format!("failed to fetch tournament: {e}")))?;
+        // TODO Probably validate rank ranges
+        let rank_restrictions = request
+            .get_ref()
+            .rank_restrictions
+            .as_ref()
+            .map(|r| r.ranges.as_slice());
+        update_tournament(&self.0.db, model, rank_restrictions).await?;
 
         Ok(Response::new(UpdateTournamentResponse {}))
     }
@@ -425,11 +660,386 @@ impl TournamentService for TournamentServiceImpl {
             .as_ref()
             .ok_or_else(|| Status::invalid_argument("missing tournament id"))?
             .id;
-        tournament::Entity::delete_by_id(id)
+        require_permission(&request, id, Permission::DeleteTournament, &self.0.db).await?;
+        let result = tournament::Entity::update_many()
+            .col_expr(
+                tournament::Column::DeletedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(tournament::Column::Id.eq(id))
+            .filter(tournament::Column::DeletedAt.is_null())
             .exec(&self.0.db)
             .await
             .map_err(|e| Status::internal(format!("could not delete tournament: {e}")))?;
 
+        if result.rows_affected == 0 {
+            return Err(Status::not_found(format!(
+                "tournament with id {id} not found"
+            )));
+        }
+
+        spawn_rating_replay(self.0.clone());
+
         Ok(Response::new(DeleteTournamentResponse {}))
     }
+
+    async fn restore(
+        &self,
+        request: Request<RestoreTournamentRequest>,
+    ) -> Result<Response<RestoreTournamentResponse>, Status> {
+        let id = request
+            .get_ref()
+            .key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing tournament id"))?
+            .id;
+        require_permission(&request, id, Permission::RestoreTournament, &self.0.db).await?;
+        let result = tournament::Entity::update_many()
+            .col_expr(
+                tournament::Column::DeletedAt,
+                Expr::value(None::<NaiveDateTime>),
+            )
+            .filter(tournament::Column::Id.eq(id))
+            .filter(tournament::Column::DeletedAt.is_not_null())
+            .exec(&self.0.db)
+            .await
+            .map_err(|e| Status::internal(format!("could not restore tournament: {e}")))?;
+
+        if result.rows_affected == 0 {
+            return Err(Status::not_found(format!(
+                "deleted tournament with id {id} not found"
+            )));
+        }
+
+        spawn_rating_replay(self.0.clone());
+
+        Ok(Response::new(RestoreTournamentResponse {}))
+    }
+
+    async fn purge(
+        &self,
+        request: Request<PurgeTournamentRequest>,
+    ) -> Result<Response<PurgeTournamentResponse>, Status> {
+        let id = request
+            .get_ref()
+            .key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing tournament id"))?
+            .id;
+        require_permission(&request, id, Permission::DeleteTournament, &self.0.db).await?;
+
+        // Deleted tournaments can be purged as well, so this doesn't only look for active ones
+        let tournament = tournament::Entity::find_by_id(id)
+            .one(&self.0.db)
+            .await
+            .map_err(|e| Status::internal(format!("failed to fetch tournament: {e}")))?
+            .ok_or_else(|| Status::not_found(format!("tournament with id {id} not found")))?;
+
+        purge_tournament(&self.0.db, id).await?;
+
+        // The tournament is gone at this point, so a banner that can't be removed is only logged
+        if let Some(banner) = tournament.banner {
+            let path = self.0.paths.banner(&banner);
+            match std::fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                Err(error) => error!(%error, ?path, "failed to remove banner of purged tournament"),
+            }
+        }
+
+        spawn_rating_replay(self.0.clone());
+
+        Ok(Response::new(PurgeTournamentResponse {}))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key))]
+    async fn get_staff(
+        &self,
+        request: Request<GetStaffRequest>,
+    ) -> Result<Response<GetStaffResponse>, Status> {
+        let tournament_id = request
+            .get_ref()
+            .key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing tournament key"))?
+            .id;
+
+        let roles = tournament_staff::Entity::find()
+            .filter(tournament_staff::Column::TournamentId.eq(tournament_id))
+            // The staff of deleted tournaments is hidden along with their tournament
+            .inner_join(tournament::Entity)
+            .filter(tournament::Column::DeletedAt.is_null())
+            .order_by_asc(tournament_staff::Column::UserId)
+            .order_by_asc(tournament_staff::Column::Role)
+            .all(&self.0.db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to get staff from database");
+                Status::internal("failed to get staff")
+            })?;
+
+        let staff = roles
+            .chunk_by(|a, b| a.user_id == b.user_id)
+            .map(|roles| StaffMember {
+                user_id: roles[0].user_id as u32,
+                roles: roles.iter().map(|staff| staff.role.into()).collect(),
+            })
+            .collect();
+
+        Ok(Response::new(GetStaffResponse { staff }))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key, user_id = request.get_ref().user_id))]
+    async fn add_staff_role(
+        &self,
+        request: Request<AddStaffRoleRequest>,
+    ) -> Result<Response<AddStaffRoleResponse>, Status> {
+        use ActiveValue as A;
+        let db = &self.0.db;
+        let tournament_id = request
+            .get_ref()
+            .key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing tournament key"))?
+            .id;
+        let role = staff_role(request.get_ref().role)?;
+        require_permission(&request, tournament_id, role_permission(role), db).await?;
+
+        tournament_staff::Entity::insert(tournament_staff::ActiveModel {
+            tournament_id: A::Set(tournament_id),
+            user_id: A::Set(request.get_ref().user_id as i32),
+            role: A::Set(role),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                tournament_staff::Column::TournamentId,
+                tournament_staff::Column::UserId,
+                tournament_staff::Column::Role,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .do_nothing()
+        .exec(db)
+        .await
+        .map_err(|error| {
+            error!(%error, "failed to insert staff role");
+            Status::internal("failed to add staff role")
+        })?;
+
+        Ok(Response::new(AddStaffRoleResponse {}))
+    }
+
+    #[tracing::instrument(skip_all, fields(key = ?request.get_ref().key, user_id = request.get_ref().user_id))]
+    async fn remove_staff_role(
+        &self,
+        request: Request<RemoveStaffRoleRequest>,
+    ) -> Result<Response<RemoveStaffRoleResponse>, Status> {
+        let db = &self.0.db;
+        let tournament_id = request
+            .get_ref()
+            .key
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing tournament key"))?
+            .id;
+        let user_id = request.get_ref().user_id as i32;
+        let role = staff_role(request.get_ref().role)?;
+        require_permission(&request, tournament_id, role_permission(role), db).await?;
+
+        if role == TournamentRole::Host {
+            let other_hosts = tournament_staff::Entity::find()
+                .filter(tournament_staff::Column::TournamentId.eq(tournament_id))
+                .filter(tournament_staff::Column::Role.eq(TournamentRole::Host))
+                .filter(tournament_staff::Column::UserId.ne(user_id))
+                .count(db)
+                .await
+                .map_err(|error| {
+                    error!(%error, "failed to count hosts");
+                    Status::internal("failed to get hosts")
+                })?;
+            if other_hosts == 0 {
+                return Err(Status::failed_precondition(
+                    "cannot remove the last host of a tournament",
+                ));
+            }
+        }
+
+        tournament_staff::Entity::delete_by_id((tournament_id, user_id, role))
+            .exec(db)
+            .await
+            .map_err(|error| {
+                error!(%error, "failed to delete staff role");
+                Status::internal("failed to remove staff role")
+            })?;
+
+        Ok(Response::new(RemoveStaffRoleResponse {}))
+    }
+}
+
+/// Converts a staff role from the on-the-wire format
+fn staff_role(role: i32) -> tonic::Result<TournamentRole> {
+    TournamentRole::try_from(role)
+        .map_err(|role| Status::invalid_argument(format!("invalid staff role {role}")))
+}
+
+/// The permission that is needed to add or remove a staff role
+fn role_permission(role: TournamentRole) -> Permission {
+    match role {
+        TournamentRole::Host | TournamentRole::Admin => Permission::ManageAdmins,
+        _ => Permission::ManageStaff,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sea_orm::{DatabaseBackend, DbErr, MockDatabase, MockExecResult, Statement, Value};
+
+    use super::*;
+    use crate::routes::test_util::{committed, rolled_back, statement};
+
+    fn tournament() -> tournament::Model {
+        tournament::Model {
+            id: 1,
+            name: "Test Tournament".to_owned(),
+            shorthand: "TT".to_owned(),
+            format: 0,
+            bws: false,
+            mode: OsuMode::Osu,
+            banner: None,
+            start_date: None,
+            end_date: None,
+            bws_base: 0.9937,
+            bws_exponent: 2.0,
+            deleted_at: None,
+        }
+    }
+
+    fn rank_restriction(tier: i16) -> rank_restriction::Model {
+        rank_restriction::Model {
+            tournament_id: 1,
+            tier,
+            min: 1,
+            max: 10000,
+        }
+    }
+
+    fn ranges() -> Vec<RankRange> {
+        vec![
+            RankRange { min: 1, max: 100 },
+            RankRange {
+                min: 101,
+                max: 1000,
+            },
+        ]
+    }
+
+    fn insert_rank_restriction(tier: i16, min: i32, max: i32) -> Statement {
+        statement(
+            r#"INSERT INTO "rank_restriction" ("tournament_id", "tier", "min", "max") VALUES ($1, $2, $3, $4) RETURNING "tournament_id", "tier", "min", "max""#,
+            [1i32.into(), tier.into(), min.into(), max.into()],
+        )
+    }
+
+    /// The statements that create the test tournament with a host and the test rank ranges
+    fn create_statements() -> Vec<Statement> {
+        vec![
+            statement(
+                r#"INSERT INTO "tournament" ("id", "name", "shorthand", "format", "bws", "mode", "banner", "start_date", "end_date", "bws_base", "bws_exponent", "deleted_at") VALUES ($1, $2, $3, $4, $5, CAST($6 AS osu_mode), $7, $8, $9, $10, $11, $12) RETURNING "id", "name", "shorthand", "format", "bws", CAST("mode" AS text), "banner", "start_date", "end_date", "bws_base", "bws_exponent", "deleted_at""#,
+                [
+                    1i32.into(),
+                    "Test Tournament".into(),
+                    "TT".into(),
+                    0i16.into(),
+                    false.into(),
+                    "osu".into(),
+                    Value::String(None),
+                    Value::ChronoDateTime(None),
+                    Value::ChronoDateTime(None),
+                    0.9937f64.into(),
+                    2.0f64.into(),
+                    Value::ChronoDateTime(None),
+                ],
+            ),
+            statement(
+                r#"INSERT INTO "tournament_staff" ("tournament_id", "user_id", "role") VALUES ($1, $2, CAST($3 AS tournament_role)) RETURNING "tournament_id", "user_id", CAST("role" AS text)"#,
+                [1i32.into(), 2i32.into(), "host".into()],
+            ),
+            insert_rank_restriction(0, 1, 100),
+            insert_rank_restriction(1, 101, 1000),
+        ]
+    }
+
+    #[tokio::test]
+    async fn create_commits_everything() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![tournament()]])
+            .append_query_results([vec![tournament_staff::Model {
+                tournament_id: 1,
+                user_id: 2,
+                role: TournamentRole::Host,
+            }]])
+            .append_query_results([vec![rank_restriction(0)], vec![rank_restriction(1)]])
+            .into_connection();
+
+        let tournament = tournament().into_active_model().reset_all();
+        let created = insert_tournament(&db, tournament, 2, &ranges(), &[]).await;
+
+        assert_eq!(created.unwrap().id, 1);
+        assert_eq!(db.into_transaction_log(), [committed(create_statements())]);
+    }
+
+    #[tokio::test]
+    async fn create_rolls_back_on_failure() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![tournament()]])
+            .append_query_results([vec![tournament_staff::Model {
+                tournament_id: 1,
+                user_id: 2,
+                role: TournamentRole::Host,
+            }]])
+            .append_query_results([vec![rank_restriction(0)]])
+            // The second rank restriction fails
+            .append_query_errors([DbErr::Custom("injected failure".to_owned())])
+            .into_connection();
+
+        let tournament = tournament().into_active_model().reset_all();
+        let created = insert_tournament(&db, tournament, 2, &ranges(), &[]).await;
+
+        assert!(created.is_err());
+        assert_eq!(
+            db.into_transaction_log(),
+            [rolled_back(create_statements())]
+        );
+    }
+
+    #[tokio::test]
+    async fn update_rolls_back_on_failure() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 2,
+            }])
+            .append_query_results([vec![rank_restriction(0)]])
+            // The second rank restriction fails after the old ones were already deleted
+            .append_query_errors([DbErr::Custom("injected failure".to_owned())])
+            .into_connection();
+
+        let mut tournament = tournament().into_active_model();
+        tournament.name = ActiveValue::Set("Renamed Tournament".to_owned());
+        let updated = update_tournament(&db, tournament, Some(&ranges())).await;
+
+        assert!(updated.is_err());
+        // The tournament itself was never updated
+        assert_eq!(
+            db.into_transaction_log(),
+            [rolled_back([
+                statement(
+                    r#"DELETE FROM "rank_restriction" WHERE "rank_restriction"."tournament_id" = $1"#,
+                    [1i32.into()],
+                ),
+                insert_rank_restriction(0, 1, 100),
+                insert_rank_restriction(1, 101, 1000),
+            ])]
+        );
+    }
 }
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+/// The scores of two sides on a map they both played in the same match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapComparison {
+    /// The combined score of the first side's players on the map
+    pub first_score: u64,
+    /// The combined score of the second side's players on the map
+    pub second_score: u64,
+}
+
+/// How often the first side beat the second side, seen from the first side
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Record {
+    /// The number of maps on which the first side scored higher
+    pub wins: u32,
+    /// The number of maps on which the second side scored higher
+    pub losses: u32,
+    /// The number of maps both sides played. Maps on which they tied are neither wins nor losses.
+    pub maps: u32,
+    /// The fraction of maps won by the first side
+    pub win_rate: f64,
+}
+
+/// The record of two sides on the maps of one mod bracket
+#[derive(Debug, Clone, PartialEq)]
+pub struct BracketRecord {
+    /// The bracket's name, e.g. "NM", "HD", etc.
+    pub name: String,
+    pub record: Record,
+}
+
+/// Counts the maps won and lost by the first side
+pub fn record<'a>(maps: impl IntoIterator<Item = &'a MapComparison>) -> Record {
+    let mut record = Record::default();
+    for map in maps {
+        record.maps += 1;
+        match map.first_score.cmp(&map.second_score) {
+            std::cmp::Ordering::Greater => record.wins += 1,
+            std::cmp::Ordering::Less => record.losses += 1,
+            std::cmp::Ordering::Equal => {}
+        }
+    }
+    if record.maps > 0 {
+        record.win_rate = record.wins as f64 / record.maps as f64;
+    }
+    record
+}
+
+/// Calculates the record of the first side on each mod bracket.
+///
+/// Brackets are identified by their name, so that e.g. the HD maps of all stages and tournaments
+/// are counted together. Each map comes with its bracket's name and order, and the returned records
+/// are ordered by the lowest order their bracket has and then by name.
+pub fn bracket_records<'a>(
+    maps: impl IntoIterator<Item = (&'a str, i16, MapComparison)>,
+) -> Vec<BracketRecord> {
+    let mut brackets = HashMap::<_, (i16, Vec<_>)>::new();
+    for (name, bracket_order, map) in maps {
+        let (order, comparisons) = brackets.entry(name).or_insert((bracket_order, vec![]));
+        *order = (*order).min(bracket_order);
+        comparisons.push(map);
+    }
+
+    let mut brackets = brackets.into_iter().collect::<Vec<_>>();
+    brackets.sort_by_key(|&(name, (order, _))| (order, name));
+    brackets
+        .into_iter()
+        .map(|(name, (_, comparisons))| BracketRecord {
+            name: name.to_owned(),
+            record: record(&comparisons),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn map(first_score: u64, second_score: u64) -> MapComparison {
+        MapComparison {
+            first_score,
+            second_score,
+        }
+    }
+
+    #[test]
+    fn counts_wins_per_bracket() {
+        let maps = [
+            ("NM", 0, map(500_000, 400_000)),
+            ("HD", 1, map(300_000, 350_000)),
+            ("NM", 0, map(200_000, 200_000)),
+            // The HD bracket is ordered first in another stage
+            ("HD", 0, map(600_000, 100_000)),
+            ("TB", 5, map(700_000, 650_000)),
+        ];
+        let records = bracket_records(maps);
+
+        assert_eq!(
+            records
+                .iter()
+                .map(|bracket| (
+                    bracket.name.as_str(),
+                    bracket.record.wins,
+                    bracket.record.losses
+                ))
+                .collect::<Vec<_>>(),
+            vec![("HD", 1, 1), ("NM", 1, 0), ("TB", 1, 0)]
+        );
+        assert_eq!(records[1].record.maps, 2);
+        assert_eq!(records[1].record.win_rate, 0.5);
+
+        let total = record(maps.iter().map(|(_, _, map)| map));
+        assert_eq!((total.wins, total.losses, total.maps), (3, 1, 5));
+        assert_eq!(total.win_rate, 0.6);
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::seeding::{competition_ranks, z_scores};
+
+/// The ways in which the players of a stage can be ranked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardOrder {
+    /// Players are ordered by their average score
+    AverageScore,
+    /// Players are ordered by their average placement on the maps they played. A lower average
+    /// is better.
+    AveragePlacement,
+    /// Players are ordered by the average z-score of their scores on the maps they played
+    ZScore,
+    /// Players are ordered by the number of maps they played
+    MapsPlayed,
+}
+
+/// A score set by a player on a map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Play {
+    pub player_id: i32,
+    /// The index of the map the score was set on
+    pub map: usize,
+    pub score: u64,
+}
+
+/// The standing of a single player on the leaderboard
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerStanding {
+    pub player_id: i32,
+    /// The player's placement on the leaderboard, starting at 1
+    pub rank: u32,
+    /// The number of scores the player set. Maps played in multiple matches count multiple times.
+    pub maps_played: u32,
+    pub average_score: f64,
+    /// The average placement of the player's scores among all scores on the same map, starting
+    /// at 1
+    pub average_placement: f64,
+    /// The average number of standard deviations the player's scores are above the mean score
+    /// on the same map
+    pub average_z_score: f64,
+}
+
+/// Ranks the players who set the given scores.
+///
+/// Every score counts, so a player who played a map in multiple matches is compared with all
+/// scores on that map multiple times. Tied players share a placement, and the next placement is
+/// skipped accordingly.
+/// The returned standings are ordered by rank and then by player id.
+pub fn rank_players(order: LeaderboardOrder, plays: &[Play]) -> Vec<PlayerStanding> {
+    // Go through the maps in order, so that the averages don't depend on the order of addition
+    let mut maps = BTreeMap::<_, Vec<&Play>>::new();
+    for play in plays {
+        maps.entry(play.map).or_default().push(play);
+    }
+
+    let mut standings = HashMap::new();
+    for map_plays in maps.values() {
+        let scores = map_plays.iter().map(|play| play.score).collect::<Vec<_>>();
+        let placements = competition_ranks(&scores, |a, b| b.cmp(a));
+        let z_scores = z_scores(&scores);
+        for ((play, placement), z_score) in map_plays.iter().zip(placements).zip(z_scores) {
+            let standing = standings
+                .entry(play.player_id)
+                .or_insert_with(|| PlayerStanding {
+                    player_id: play.player_id,
+                    rank: 0,
+                    maps_played: 0,
+                    average_score: 0.0,
+                    average_placement: 0.0,
+                    average_z_score: 0.0,
+                });
+            standing.maps_played += 1;
+            standing.average_score += play.score as f64;
+            standing.average_placement += placement as f64;
+            standing.average_z_score += z_score;
+        }
+    }
+
+    let mut standings = standings.into_values().collect::<Vec<_>>();
+    for standing in &mut standings {
+        let maps_played = standing.maps_played as f64;
+        standing.average_score /= maps_played;
+        standing.average_placement /= maps_played;
+        standing.average_z_score /= maps_played;
+    }
+
+    let ranks = match order {
+        LeaderboardOrder::AverageScore => competition_ranks(&standings, |a, b| {
+            b.average_score.total_cmp(&a.average_score)
+        }),
+        // A lower average placement is better
+        LeaderboardOrder::AveragePlacement => competition_ranks(&standings, |a, b| {
+            a.average_placement.total_cmp(&b.average_placement)
+        }),
+        LeaderboardOrder::ZScore => competition_ranks(&standings, |a, b| {
+            b.average_z_score.total_cmp(&a.average_z_score)
+        }),
+        LeaderboardOrder::MapsPlayed => {
+            competition_ranks(&standings, |a, b| b.maps_played.cmp(&a.maps_played))
+        }
+    };
+    for (standing, rank) in standings.iter_mut().zip(ranks) {
+        standing.rank = rank;
+    }
+
+    standings.sort_by_key(|standing| (standing.rank, standing.player_id));
+    standings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn play(player_id: i32, map: usize, score: u64) -> Play {
+        Play {
+            player_id,
+            map,
+            score,
+        }
+    }
+
+    fn rank_order(order: LeaderboardOrder, plays: &[Play]) -> Vec<(i32, u32)> {
+        rank_players(order, plays)
+            .into_iter()
+            .map(|standing| (standing.player_id, standing.rank))
+            .collect()
+    }
+
+    #[test]
+    fn ranks_players_by_each_order() {
+        // Player 1 only played the first map, on which they won by a landslide.
+        // Player 2 narrowly won both maps against player 3.
+        let plays = [
+            play(1, 0, 900_000),
+            play(2, 0, 500_000),
+            play(3, 0, 400_000),
+            play(2, 1, 410_000),
+            play(3, 1, 400_000),
+        ];
+
+        assert_eq!(
+            rank_order(LeaderboardOrder::AverageScore, &plays),
+            vec![(1, 1), (2, 2), (3, 3)]
+        );
+        assert_eq!(
+            rank_order(LeaderboardOrder::AveragePlacement, &plays),
+            vec![(1, 1), (2, 2), (3, 3)]
+        );
+        assert_eq!(
+            rank_order(LeaderboardOrder::MapsPlayed, &plays),
+            vec![(2, 1), (3, 1), (1, 3)]
+        );
+
+        let standings = rank_players(LeaderboardOrder::ZScore, &plays);
+        let player_2 = standings.iter().find(|s| s.player_id == 2).unwrap();
+        assert_eq!(player_2.maps_played, 2);
+        assert_eq!(player_2.average_score, 455_000.0);
+        assert_eq!(player_2.average_placement, 1.5);
+    }
+
+    #[test]
+    fn ties_share_placements() {
+        let plays = [play(1, 0, 200), play(2, 0, 200), play(3, 0, 100)];
+        let standings = rank_players(LeaderboardOrder::ZScore, &plays);
+
+        assert_eq!(
+            standings
+                .iter()
+                .map(|standing| (standing.player_id, standing.rank))
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (2, 1), (3, 3)]
+        );
+        let z_sum = standings.iter().map(|s| s.average_z_score).sum::<f64>();
+        assert!(z_sum.abs() < 1e-9);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::leaderboard::Play;
+
+/// How much of a player's normalized score on the tiebreaker is added to their match cost
+pub const TIEBREAKER_BONUS: f64 = 0.1;
+
+/// The performance of a single player in a match
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerMatchCost {
+    pub player_id: i32,
+    /// The number of maps the player played in the match
+    pub maps_played: u32,
+    pub played_tiebreaker: bool,
+    pub match_cost: f64,
+}
+
+/// Calculates the match cost of every player who set a score in a match.
+///
+/// A player's score on a map is normalized by dividing it by the median of all scores on the map.
+/// The match cost is the average normalized score of the player, weighted by the cube root of the
+/// number of maps they played relative to the median number of maps played by all players in the
+/// match. Players who played the tiebreaker additionally get [`TIEBREAKER_BONUS`] times their
+/// normalized score on it.
+///
+/// `tiebreaker` is the map that was played as the tiebreaker, if any.
+/// The returned match costs are ordered by descending match cost and then by player id.
+pub fn match_costs(plays: &[Play], tiebreaker: Option<usize>) -> Vec<PlayerMatchCost> {
+    let mut maps = BTreeMap::<_, Vec<&Play>>::new();
+    for play in plays {
+        maps.entry(play.map).or_default().push(play);
+    }
+
+    // The sum of each player's normalized scores and their normalized score on the tiebreaker
+    let mut players = BTreeMap::<_, (u32, f64, Option<f64>)>::new();
+    for (&map, map_plays) in &maps {
+        let scores = map_plays
+            .iter()
+            .map(|play| play.score as f64)
+            .collect::<Vec<_>>();
+        let median_score = median(scores);
+        for play in map_plays {
+            let normalized = if median_score == 0.0 {
+                0.0
+            } else {
+                play.score as f64 / median_score
+            };
+            let (maps_played, sum, tiebreaker_score) = players.entry(play.player_id).or_default();
+            *maps_played += 1;
+            *sum += normalized;
+            if Some(map) == tiebreaker {
+                *tiebreaker_score = Some(normalized);
+            }
+        }
+    }
+
+    let median_maps_played = median(
+        players
+            .values()
+            .map(|&(maps_played, ..)| maps_played as f64)
+            .collect(),
+    );
+    let mut costs = players
+        .into_iter()
+        .map(|(player_id, (maps_played, sum, tiebreaker_score))| {
+            let participation = (maps_played as f64 / median_maps_played).cbrt();
+            let bonus = tiebreaker_score.map_or(0.0, |score| TIEBREAKER_BONUS * score);
+            PlayerMatchCost {
+                player_id,
+                maps_played,
+                played_tiebreaker: tiebreaker_score.is_some(),
+                match_cost: sum / maps_played as f64 * participation + bonus,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    costs.sort_by(|a, b| {
+        b.match_cost
+            .total_cmp(&a.match_cost)
+            .then(a.player_id.cmp(&b.player_id))
+    });
+    costs
+}
+
+/// The match costs of a player aggregated over multiple matches
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateMatchCost {
+    pub player_id: i32,
+    pub matches_played: u32,
+    pub maps_played: u32,
+    pub average_match_cost: f64,
+}
+
+/// Averages the match costs of each player over all matches they played in.
+/// The returned aggregates are ordered by descending average match cost and then by player id.
+pub fn aggregate_match_costs<'a>(
+    costs: impl IntoIterator<Item = &'a PlayerMatchCost>,
+) -> Vec<AggregateMatchCost> {
+    let mut players = HashMap::new();
+    for cost in costs {
+        let aggregate = players
+            .entry(cost.player_id)
+            .or_insert_with(|| AggregateMatchCost {
+                player_id: cost.player_id,
+                matches_played: 0,
+                maps_played: 0,
+                average_match_cost: 0.0,
+            });
+        aggregate.matches_played += 1;
+        aggregate.maps_played += cost.maps_played;
+        aggregate.average_match_cost += cost.match_cost;
+    }
+
+    let mut aggregates = players.into_values().collect::<Vec<_>>();
+    for aggregate in &mut aggregates {
+        aggregate.average_match_cost /= aggregate.matches_played as f64;
+    }
+    aggregates.sort_by(|a, b| {
+        b.average_match_cost
+            .total_cmp(&a.average_match_cost)
+            .then(a.player_id.cmp(&b.player_id))
+    });
+    aggregates
+}
+
+/// Returns the median of the values or 0 if there are none
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    match values.len() {
+        0 => 0.0,
+        len if len % 2 == 0 => (values[middle - 1] + values[middle]) / 2.0,
+        _ => values[middle],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalizes_scores_by_the_median() {
+        // The median score is 400k on the first map and 200k on the second map.
+        // Player 4 only played the second map.
+        let plays = [
+            (1, 0, 600_000),
+            (2, 0, 400_000),
+            (3, 0, 200_000),
+            (1, 1, 300_000),
+            (4, 1, 100_000),
+        ]
+        .map(|(player_id, map, score)| Play {
+            player_id,
+            map,
+            score,
+        });
+        let costs = match_costs(&plays, None);
+
+        let cost = |player_id| {
+            costs
+                .iter()
+                .find(|cost| cost.player_id == player_id)
+                .unwrap()
+                .match_cost
+        };
+        // The players played 2, 1, 1 and 1 maps, so the median is 1 map
+        assert!((cost(1) - (1.5 + 1.5) / 2.0 * 2f64.cbrt()).abs() < 1e-9);
+        assert!((cost(2) - 1.0).abs() < 1e-9);
+        assert!((cost(3) - 0.5).abs() < 1e-9);
+        assert!((cost(4) - 0.5).abs() < 1e-9);
+        assert_eq!(
+            costs.iter().map(|cost| cost.player_id).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn rewards_playing_the_tiebreaker() {
+        let plays = [(1, 0), (2, 0), (1, 1), (2, 1)].map(|(player_id, map)| Play {
+            player_id,
+            map,
+            score: 500_000,
+        });
+        let costs = match_costs(&plays, Some(1));
+
+        assert!(costs.iter().all(|cost| cost.played_tiebreaker));
+        assert!(costs
+            .iter()
+            .all(|cost| (cost.match_cost - (1.0 + TIEBREAKER_BONUS)).abs() < 1e-9));
+    }
+
+    #[test]
+    fn averages_over_matches() {
+        let costs = [
+            PlayerMatchCost {
+                player_id: 1,
+                maps_played: 5,
+                played_tiebreaker: false,
+                match_cost: 1.2,
+            },
+            PlayerMatchCost {
+                player_id: 1,
+                maps_played: 3,
+                played_tiebreaker: false,
+                match_cost: 0.8,
+            },
+            PlayerMatchCost {
+                player_id: 2,
+                maps_played: 4,
+                played_tiebreaker: true,
+                match_cost: 1.1,
+            },
+        ];
+        let aggregates = aggregate_match_costs(&costs);
+
+        assert_eq!(aggregates[0].player_id, 2);
+        assert_eq!(aggregates[1].player_id, 1);
+        assert_eq!(aggregates[1].matches_played, 2);
+        assert_eq!(aggregates[1].maps_played, 8);
+        assert!((aggregates[1].average_match_cost - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,8 @@
+//! Statistics that are calculated from the data stored for a tournament
+
+pub mod head_to_head;
+pub mod leaderboard;
+pub mod match_cost;
+pub mod pick_ban;
+pub mod rating;
+pub mod seeding;
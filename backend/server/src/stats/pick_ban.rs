@@ -0,0 +1,96 @@
+use model::sea_orm_active_enums::PickBanKind;
+
+/// How often a map or a mod bracket was protected, banned and picked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PickBanCounts {
+    /// The number of times it was protected
+    pub protects: u32,
+    /// The number of times it was banned
+    pub bans: u32,
+    /// The number of times it was picked
+    pub picks: u32,
+    /// The number of matches whose first pick it was
+    pub first_picks: u32,
+    /// The number of matches in which it was protected at least once
+    pub protected_matches: u32,
+    /// The number of matches in which it was banned at least once
+    pub banned_matches: u32,
+    /// The number of matches in which it was picked at least once
+    pub picked_matches: u32,
+}
+
+/// The pick and ban counts of every map and every bracket of a pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PickBanStats {
+    /// The number of matches that have picks or bans
+    pub matches: u32,
+    /// The counts for each map of the pool
+    pub maps: Vec<PickBanCounts>,
+    /// The counts for each bracket of the pool
+    pub brackets: Vec<PickBanCounts>,
+}
+
+/// Counts the protects, bans and picks of the maps and brackets in a pool.
+///
+/// `map_brackets[map]` is the index of the bracket the map is in.
+/// Each log contains the kind and the index of the map of each pick or ban in a match, in the
+/// order they happened. Tiebreakers are not counted as picks.
+pub fn count_pick_bans<'a>(
+    map_brackets: &[usize],
+    bracket_count: usize,
+    logs: impl IntoIterator<Item = &'a [(PickBanKind, usize)]>,
+) -> PickBanStats {
+    let mut stats = PickBanStats {
+        matches: 0,
+        maps: vec![PickBanCounts::default(); map_brackets.len()],
+        brackets: vec![PickBanCounts::default(); bracket_count],
+    };
+
+    for log in logs {
+        if log.is_empty() {
+            continue;
+        }
+        stats.matches += 1;
+
+        let mut maps_seen = vec![[false; 3]; stats.maps.len()];
+        let mut brackets_seen = vec![[false; 3]; stats.brackets.len()];
+        let mut first_pick = true;
+        for &(kind, map) in log {
+            let bracket = map_brackets[map];
+            let seen = match kind {
+                PickBanKind::Protect => 0,
+                PickBanKind::Ban => 1,
+                PickBanKind::Pick => 2,
+                PickBanKind::Tiebreaker => continue,
+            };
+            let is_first_pick = kind == PickBanKind::Pick && first_pick;
+            if kind == PickBanKind::Pick {
+                first_pick = false;
+            }
+
+            for (counts, seen) in [
+                (&mut stats.maps[map], &mut maps_seen[map][seen]),
+                (
+                    &mut stats.brackets[bracket],
+                    &mut brackets_seen[bracket][seen],
+                ),
+            ] {
+                let first_in_match = !std::mem::replace(seen, true);
+                let (total, matches) = match kind {
+                    PickBanKind::Protect => (&mut counts.protects, &mut counts.protected_matches),
+                    PickBanKind::Ban => (&mut counts.bans, &mut counts.banned_matches),
+                    _ => (&mut counts.picks, &mut counts.picked_matches),
+                };
+                *total += 1;
+                if first_in_match {
+                    *matches += 1;
+                }
+                if is_first_pick {
+                    counts.first_picks += 1;
+                }
+            }
+        }
+    }
+
+    stats
+}
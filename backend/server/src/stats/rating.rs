@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+/// The rating of a player who hasn't played a rated match yet
+pub const INITIAL_RATING: f64 = 1500.0;
+
+/// The maximum rating a side can gain or lose in a single match. A player who played as many maps
+/// as their side's average gains or loses up to this much, and the others proportionally more or
+/// less.
+pub const K_FACTOR: f64 = 32.0;
+
+/// A player who played for one side of a match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineupPlayer {
+    pub player_id: i32,
+    /// The number of maps the player played in the match
+    pub maps_played: u32,
+}
+
+/// A finished versus match between two lineups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RatedMatch {
+    pub match_id: i32,
+    /// The players who played for the red team
+    pub red: Vec<LineupPlayer>,
+    /// The players who played for the blue team
+    pub blue: Vec<LineupPlayer>,
+    /// The number of maps won by the red team
+    pub score_red: u32,
+    /// The number of maps won by the blue team
+    pub score_blue: u32,
+}
+
+/// The rating of a player after all matches
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerRating {
+    pub player_id: i32,
+    pub rating: f64,
+    pub matches_played: u32,
+}
+
+/// How a player's rating changed in a single match
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatingChange {
+    pub player_id: i32,
+    pub match_id: i32,
+    pub rating_before: f64,
+    pub rating_after: f64,
+}
+
+/// The result of replaying matches
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Ratings {
+    /// The final ratings, ordered by descending rating and then by player id
+    pub players: Vec<PlayerRating>,
+    /// Every rating change, ordered by match and then by player id
+    pub changes: Vec<RatingChange>,
+}
+
+/// Calculates the ratings of all players by replaying the matches in the given order, starting
+/// with every player at [`INITIAL_RATING`].
+///
+/// Each side's rating is the average rating of its players, weighted by the number of maps they
+/// played. The Elo expectation of the sides is compared with the fraction of maps each side won,
+/// and the resulting change of up to [`K_FACTOR`] is scaled for each player by the maps they
+/// played relative to their side's average, so that a substitute who played a single map is
+/// affected less than a player who played the whole match.
+///
+/// Matches in which a side has no players or no maps were played are skipped, as are players who
+/// didn't play any maps. The result only depends on the order of the matches, so replaying the
+/// same matches always gives the same ratings.
+pub fn replay_ratings(matches: &[RatedMatch]) -> Ratings {
+    let mut players = BTreeMap::<i32, (f64, u32)>::new();
+    let mut changes = vec![];
+
+    for rated_match in matches {
+        let mut red = lineup(&rated_match.red);
+        let mut blue = lineup(&rated_match.blue);
+        // A player listed on both sides can't be attributed to either of them
+        let shared = red
+            .keys()
+            .filter(|player_id| blue.contains_key(player_id))
+            .copied()
+            .collect::<Vec<_>>();
+        for player_id in shared {
+            red.remove(&player_id);
+            blue.remove(&player_id);
+        }
+        let maps = rated_match.score_red + rated_match.score_blue;
+        if red.is_empty() || blue.is_empty() || maps == 0 {
+            continue;
+        }
+
+        let rating = |player_id: &i32| {
+            players
+                .get(player_id)
+                .map_or(INITIAL_RATING, |&(rating, _)| rating)
+        };
+        let team_rating = |lineup: &BTreeMap<i32, u32>| {
+            let total_maps = lineup.values().sum::<u32>() as f64;
+            lineup
+                .iter()
+                .map(|(player_id, &maps)| rating(player_id) * maps as f64)
+                .sum::<f64>()
+                / total_maps
+        };
+        let expected_red =
+            1.0 / (1.0 + 10f64.powf((team_rating(&blue) - team_rating(&red)) / 400.0));
+        let actual_red = rated_match.score_red as f64 / maps as f64;
+        let change_red = K_FACTOR * (actual_red - expected_red);
+
+        let mut match_changes = vec![];
+        for (lineup, change) in [(&red, change_red), (&blue, -change_red)] {
+            let average_maps = lineup.values().sum::<u32>() as f64 / lineup.len() as f64;
+            for (&player_id, &maps) in lineup {
+                let rating_before = rating(&player_id);
+                match_changes.push(RatingChange {
+                    player_id,
+                    match_id: rated_match.match_id,
+                    rating_before,
+                    rating_after: rating_before + change * maps as f64 / average_maps,
+                });
+            }
+        }
+
+        // All changes are based on the ratings before the match
+        match_changes.sort_by_key(|change| change.player_id);
+        for change in &match_changes {
+            let (rating, matches_played) = players
+                .entry(change.player_id)
+                .or_insert((INITIAL_RATING, 0));
+            *rating = change.rating_after;
+            *matches_played += 1;
+        }
+        changes.extend(match_changes);
+    }
+
+    let mut players = players
+        .into_iter()
+        .map(|(player_id, (rating, matches_played))| PlayerRating {
+            player_id,
+            rating,
+            matches_played,
+        })
+        .collect::<Vec<_>>();
+    players.sort_by(|a, b| {
+        b.rating
+            .total_cmp(&a.rating)
+            .then(a.player_id.cmp(&b.player_id))
+    });
+    Ratings { players, changes }
+}
+
+/// Gets the maps played by each player of a lineup, leaving out players who didn't play
+fn lineup(players: &[LineupPlayer]) -> BTreeMap<i32, u32> {
+    let mut lineup = BTreeMap::new();
+    for player in players.iter().filter(|player| player.maps_played > 0) {
+        *lineup.entry(player.player_id).or_default() += player.maps_played;
+    }
+    lineup
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn player(player_id: i32, maps_played: u32) -> LineupPlayer {
+        LineupPlayer {
+            player_id,
+            maps_played,
+        }
+    }
+
+    fn rated_match(
+        match_id: i32,
+        red: Vec<LineupPlayer>,
+        blue: Vec<LineupPlayer>,
+        score_red: u32,
+        score_blue: u32,
+    ) -> RatedMatch {
+        RatedMatch {
+            match_id,
+            red,
+            blue,
+            score_red,
+            score_blue,
+        }
+    }
+
+    #[test]
+    fn splits_changes_by_maps_played() {
+        // Player 2 only subbed in for two of the eight maps
+        let matches = [rated_match(
+            1,
+            vec![player(1, 8), player(2, 2), player(3, 6)],
+            vec![player(4, 8), player(5, 8)],
+            5,
+            3,
+        )];
+        let ratings = replay_ratings(&matches);
+
+        let change = |player_id| {
+            let change = ratings
+                .changes
+                .iter()
+                .find(|change| change.player_id == player_id)
+                .unwrap();
+            change.rating_after - change.rating_before
+        };
+        // Both sides start at the same rating, so red was expected to win half of the maps
+        let change_red = K_FACTOR * (5.0 / 8.0 - 0.5);
+        assert!((change(1) - change_red * 8.0 / (16.0 / 3.0)).abs() < 1e-9);
+        assert!((change(2) - change_red * 2.0 / (16.0 / 3.0)).abs() < 1e-9);
+        assert!((change(4) + change_red).abs() < 1e-9);
+        // The total change of each side is the same as if all players had played every map
+        let red_total = change(1) + change(2) + change(3);
+        assert!((red_total - 3.0 * change_red).abs() < 1e-9);
+    }
+
+    #[test]
+    fn replays_matches_in_order() {
+        let matches = [
+            rated_match(1, vec![player(1, 5)], vec![player(2, 5)], 3, 2),
+            rated_match(2, vec![player(2, 5)], vec![player(3, 5)], 3, 2),
+            // Nobody played this match
+            rated_match(3, vec![player(1, 0)], vec![player(3, 5)], 0, 0),
+            rated_match(4, vec![player(3, 5)], vec![player(1, 5)], 3, 0),
+        ];
+        let ratings = replay_ratings(&matches);
+
+        assert_eq!(ratings, replay_ratings(&matches));
+        assert_eq!(
+            ratings
+                .changes
+                .iter()
+                .map(|change| (change.match_id, change.player_id))
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (1, 2), (2, 2), (2, 3), (4, 1), (4, 3)]
+        );
+        // Each change continues from the player's previous rating
+        assert_eq!(
+            ratings.changes[2].rating_before,
+            ratings.changes[1].rating_after
+        );
+        assert_eq!(ratings.players[0].player_id, 3);
+        assert_eq!(ratings.players[0].matches_played, 2);
+        let total = ratings.players.iter().map(|p| p.rating).sum::<f64>();
+        assert!((total - 3.0 * INITIAL_RATING).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,211 @@
+/// The ways in which teams can be seeded from their qualifier scores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedingMethod {
+    /// Teams are ordered by the sum of their scores on all maps
+    Sum,
+    /// Teams are ordered by their average placement on each map
+    AverageRank,
+    /// Teams are ordered by the sum of the z-scores of their scores on each map
+    ZSum,
+    /// Teams are ordered by the sum of their scores relative to the highest score on each map
+    PercentileOfMax,
+}
+
+/// The result of seeding a single team
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeamSeed {
+    /// The index of the team in the score table that was seeded
+    pub team: usize,
+    /// The team's placement on each map, starting at 1
+    pub map_ranks: Vec<u32>,
+    /// The value that each map contributes to the team's total.
+    /// This depends on the seeding method, i.e. this is the score, the rank, the z-score or the
+    /// score relative to the highest score on the map.
+    pub map_values: Vec<f64>,
+    /// The value the teams are ordered by
+    pub total: f64,
+    /// The team's final seed, starting at 1
+    pub seed: u32,
+}
+
+/// Seeds teams from their scores on the maps of a qualifier pool.
+///
+/// `scores[team][map]` is the score the team set on the map. A team that did not play a map is
+/// expected to have a score of 0 on it.
+///
+/// Tied teams share a placement, and the next placement is skipped accordingly, i.e. two teams
+/// sharing first place are followed by a team in third place.
+/// The returned seeds are ordered by seed.
+pub fn seed_teams(method: SeedingMethod, scores: &[Vec<u64>]) -> Vec<TeamSeed> {
+    let map_count = scores.first().map_or(0, Vec::len);
+    assert!(
+        scores.iter().all(|team| team.len() == map_count),
+        "every team needs a score for every map"
+    );
+
+    let mut seeds = scores
+        .iter()
+        .enumerate()
+        .map(|(team, _)| TeamSeed {
+            team,
+            map_ranks: Vec::with_capacity(map_count),
+            map_values: Vec::with_capacity(map_count),
+            total: 0.0,
+            seed: 0,
+        })
+        .collect::<Vec<_>>();
+
+    for map in 0..map_count {
+        let map_scores = scores.iter().map(|team| team[map]).collect::<Vec<_>>();
+        let ranks = competition_ranks(&map_scores, |a, b| b.cmp(a));
+        let values = map_values(method, &map_scores, &ranks);
+        for ((seed, rank), value) in seeds.iter_mut().zip(ranks).zip(values) {
+            seed.map_ranks.push(rank);
+            seed.map_values.push(value);
+        }
+    }
+
+    for seed in &mut seeds {
+        let sum = seed.map_values.iter().sum::<f64>();
+        seed.total = match method {
+            SeedingMethod::AverageRank if map_count > 0 => sum / map_count as f64,
+            _ => sum,
+        };
+    }
+
+    let totals = seeds.iter().map(|seed| seed.total).collect::<Vec<_>>();
+    let final_ranks = match method {
+        // A lower average rank is better
+        SeedingMethod::AverageRank => competition_ranks(&totals, |a, b| a.total_cmp(b)),
+        _ => competition_ranks(&totals, |a, b| b.total_cmp(a)),
+    };
+    for (seed, rank) in seeds.iter_mut().zip(final_ranks) {
+        seed.seed = rank;
+    }
+
+    seeds.sort_by_key(|seed| (seed.seed, seed.team));
+    seeds
+}
+
+/// Calculates the value each team gets on a map for the given seeding method
+fn map_values(method: SeedingMethod, scores: &[u64], ranks: &[u32]) -> Vec<f64> {
+    match method {
+        SeedingMethod::Sum => scores.iter().map(|&score| score as f64).collect(),
+        SeedingMethod::AverageRank => ranks.iter().map(|&rank| rank as f64).collect(),
+        SeedingMethod::ZSum => z_scores(scores),
+        SeedingMethod::PercentileOfMax => {
+            let max = scores.iter().copied().max().unwrap_or(0);
+            scores
+                .iter()
+                .map(|&score| {
+                    if max == 0 {
+                        0.0
+                    } else {
+                        score as f64 / max as f64
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+/// Calculates how many standard deviations each score is above the mean of all scores
+pub(crate) fn z_scores(scores: &[u64]) -> Vec<f64> {
+    let n = scores.len() as f64;
+    let mean = scores.iter().map(|&score| score as f64).sum::<f64>() / n;
+    let variance = scores
+        .iter()
+        .map(|&score| (score as f64 - mean).powi(2))
+        .sum::<f64>()
+        / n;
+    let std_dev = variance.sqrt();
+    scores
+        .iter()
+        .map(|&score| {
+            // If everyone has the same score, nobody is better than average
+            if std_dev == 0.0 {
+                0.0
+            } else {
+                (score as f64 - mean) / std_dev
+            }
+        })
+        .collect()
+}
+
+/// Assigns placements starting at 1 to the values according to the given ordering, where values
+/// that are ordered first get the better placements.
+/// Equal values share a placement.
+pub(crate) fn competition_ranks<T>(
+    values: &[T],
+    mut cmp: impl FnMut(&T, &T) -> std::cmp::Ordering,
+) -> Vec<u32> {
+    let mut order = (0..values.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| cmp(&values[a], &values[b]));
+
+    let mut ranks = vec![0; values.len()];
+    for (position, &index) in order.iter().enumerate() {
+        ranks[index] = match position {
+            0 => 1,
+            _ => {
+                let previous = order[position - 1];
+                if cmp(&values[previous], &values[index]).is_eq() {
+                    ranks[previous]
+                } else {
+                    position as u32 + 1
+                }
+            }
+        };
+    }
+    ranks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn seed_order(method: SeedingMethod, scores: &[Vec<u64>]) -> Vec<(usize, u32)> {
+        seed_teams(method, scores)
+            .into_iter()
+            .map(|seed| (seed.team, seed.seed))
+            .collect()
+    }
+
+    #[test]
+    fn methods_can_disagree() {
+        // Team 0 wins the first map by a landslide, team 1 narrowly wins the other two
+        let scores = vec![
+            vec![900_000, 400_000, 400_000],
+            vec![300_000, 420_000, 420_000],
+            vec![200_000, 410_000, 410_000],
+        ];
+
+        assert_eq!(
+            seed_order(SeedingMethod::Sum, &scores),
+            vec![(0, 1), (1, 2), (2, 3)]
+        );
+        assert_eq!(
+            seed_order(SeedingMethod::AverageRank, &scores),
+            vec![(1, 1), (0, 2), (2, 2)]
+        );
+        assert_eq!(
+            seed_order(SeedingMethod::PercentileOfMax, &scores),
+            vec![(0, 1), (1, 2), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn ties_share_placements() {
+        let scores = vec![vec![100], vec![200], vec![200], vec![0]];
+        let seeds = seed_teams(SeedingMethod::ZSum, &scores);
+
+        assert_eq!(
+            seeds
+                .iter()
+                .map(|seed| (seed.team, seed.seed))
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (2, 1), (0, 3), (3, 4)]
+        );
+        let z_sum = seeds.iter().map(|seed| seed.total).sum::<f64>();
+        assert!(z_sum.abs() < 1e-9);
+    }
+}
@@ -1,88 +1,133 @@
-//! This module contains utilities for cacheing values using Redis.
+//! This module contains utilities for cacheing values in a [`CacheBackend`], usually redis.
+
+pub mod backend;
+pub mod local;
 
 use std::fmt::Display;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{convert::Infallible, future::Future};
 
-use deadpool_redis::redis::{AsyncCommands, FromRedisValue};
 use miette::{Context, IntoDiagnostic};
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
+use tracing::warn;
+
+pub use backend::{Cache, CacheBackend, CacheWrite, MemoryBackend, RedisBackend};
+pub use local::LocalCache;
+
+/// The number of seconds during which no other refresh of the same entries is started.
+/// This also limits how often a failing refresh is retried.
+const REFRESH_LOCK_TIME: u64 = 30;
 
-/// A trait for structs cached in the redis store
+/// A trait for structs stored in the cache
 #[async_trait::async_trait]
-pub trait Cacheable: Serialize + DeserializeOwned + Send + Sync {
+pub trait Cacheable: Clone + Serialize + DeserializeOwned + Send + Sync + 'static {
     type KeyType: ?Sized + Display + Send + Sync;
 
-    /// Returns a unique string for this type with which all entries in the redis store are prefixed.
+    /// Returns a unique string for this type with which all of its entries in the cache are prefixed.
     fn type_key() -> &'static str;
 
-    /// Returns a key that identifies the specific object among the entries in the redis store.
+    /// Returns a key that identifies the specific object among the entries in the cache.
     fn key(&self) -> &Self::KeyType;
 
-    /// Returns the key that the current object would have in the redis store
+    /// Returns the key that the current object would have in the cache
     fn full_key(&self) -> String {
         format!("{}:{}", Self::type_key(), self.key())
     }
 
-    /// Returns the full redis store key for an object with the given key type
+    /// Returns the full cache key for an object with the given key type
     fn full_key_with(key: &Self::KeyType) -> String {
         format!("{}:{}", Self::type_key(), key)
     }
 
-    /// Stores a value in redis.
+    /// Returns the in-process cache in front of the backend for this type, if it should have one.
+    /// See the [`local`] module.
+    fn local_cache() -> Option<&'static LocalCache<Self>> {
+        None
+    }
+
+    /// Stores a value in the cache.
     ///
     /// # Arguments
     ///
-    /// * `redis` - A connection to the redis instance
+    /// * `backend` - The backend the cache is stored in
     /// * `v` - The value to store.
     /// * `expiry_time` - An optional number of seconds until the entry expires.
     ///
     /// # Errors
     ///
-    /// An error can occur when serialization fails, or the set command in the redis store fails.
+    /// An error can occur when serialization fails, or storing it fails.
+    ///
+    async fn cache(&self, backend: &Cache, expiry_time: Option<usize>) -> Result<(), CacheError> {
+        cache(backend, self, expiry_time).await
+    }
+
+    /// Stores multiple values in the cache at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend the cache is stored in
+    /// * `values` - The values to store.
+    /// * `expiry_time` - An optional number of seconds until the entries expire.
+    ///
+    /// # Errors
+    ///
+    /// An error can occur when serialization fails, or storing them fails.
     ///
-    async fn cache(
-        &self,
-        redis: &deadpool_redis::Pool,
+    async fn cache_many(
+        backend: &Cache,
+        values: &[Self],
         expiry_time: Option<usize>,
     ) -> Result<(), CacheError> {
-        cache(redis, self, expiry_time).await
+        cache_many(backend, values, expiry_time).await
     }
 
-    /// Removes a value from redis.
+    /// Removes a value from the cache.
     ///
     /// # Arguments
     ///
-    /// * `redis` - A connection to the redis instance
+    /// * `backend` - The backend the cache is stored in
     /// * `key` - The key to remove.
     ///
     /// # Errors
     ///
-    /// An error can occur when deserialization fails, or the del command in the redis store fails.
+    /// An error can occur when deserialization fails, or removing it fails.
     ///
-    async fn uncache(
-        redis: &deadpool_redis::Pool,
-        key: &Self::KeyType,
-    ) -> Result<Option<Self>, CacheError> {
-        uncache(redis, key).await
+    async fn uncache(backend: &Cache, key: &Self::KeyType) -> Result<Option<Self>, CacheError> {
+        uncache(backend, key).await
     }
 
-    /// Gets a value from redis. Or `Ok(None)` if it doesn't exist
+    /// Gets a value from the cache. Or `Ok(None)` if it doesn't exist
     ///
     /// # Arguments
     ///
     /// * `key` - The value's ksy.
-    /// * `redis` - A connection to the redis instance
+    /// * `backend` - The backend the cache is stored in
     ///
     /// # Errors
     ///
-    /// An error can occur when deserialization fails, or the get command in the redis store fails.
+    /// An error can occur when deserialization fails, or getting it fails.
     ///
-    async fn get_cached(
-        key: &Self::KeyType,
-        redis: &deadpool_redis::Pool,
-    ) -> Result<Option<Self>, CacheError> {
-        get_cached(redis, key).await
+    async fn get_cached(key: &Self::KeyType, backend: &Cache) -> Result<Option<Self>, CacheError> {
+        get_cached(backend, key).await
+    }
+
+    /// Gets multiple values from the cache at once. Values that don't exist are `None`.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend the cache is stored in
+    /// * `keys` - The values' keys.
+    ///
+    /// # Errors
+    ///
+    /// An error can occur when deserialization fails, or getting them fails.
+    ///
+    async fn get_cached_many(
+        backend: &Cache,
+        keys: &[&Self::KeyType],
+    ) -> Result<Vec<Option<Self>>, CacheError> {
+        get_cached_many(backend, keys).await
     }
 
     /// Tries to get a value from the cache and returns it, if it exist.
@@ -91,17 +136,17 @@ pub trait Cacheable: Serialize + DeserializeOwned + Send + Sync {
     ///
     /// # Arguments
     ///
-    /// * `redis` - A connection to the redis instance
+    /// * `backend` - The backend the cache is stored in
     /// * `key` - The value's key.
     /// * `expiry_time` - An optional number of seconds until the entry expires.
     /// * `get_fn` - A function that gets an instance of the value.
     ///
     /// # Errors
     ///
-    /// An error can occur during (de-)seriaization or if the redis set command fails.
+    /// An error can occur during (de-)seriaization or if storing it fails.
     ///
     async fn get_cached_or_infallible<Fut>(
-        redis: &deadpool_redis::Pool,
+        backend: &Cache,
         key: &Self::KeyType,
         expiry_time: Option<usize>,
         get_fn: impl FnOnce() -> Fut + Send,
@@ -109,7 +154,7 @@ pub trait Cacheable: Serialize + DeserializeOwned + Send + Sync {
     where
         Fut: Future<Output = Self> + Send,
     {
-        get_cached_or_infallible(redis, key, expiry_time, get_fn).await
+        get_cached_or_infallible(backend, key, expiry_time, get_fn).await
     }
 
     /// Tries to get a value from the cache and returns it, if it exist.
@@ -118,18 +163,18 @@ pub trait Cacheable: Serialize + DeserializeOwned + Send + Sync {
     ///
     /// # Arguments
     ///
-    /// * `redis` - A connection to the redis instance
+    /// * `backend` - The backend the cache is stored in
     /// * `key` - The value's key.
     /// * `expiry_time` - An optional number of seconds until the entry expires.
     /// * `get_fn` - A function that gets an instance of the value but might return an error.
     ///
     /// # Errors
     ///
-    /// An error can occur during (de-)seriaization, if the redis set command fails or if the `get_fn`
+    /// An error can occur during (de-)seriaization, if storing it fails or if the `get_fn`
     /// fails.
     ///
     async fn get_cached_or<E, Fut>(
-        redis: &deadpool_redis::Pool,
+        backend: &Cache,
         key: &Self::KeyType,
         expiry_time: Option<usize>,
         get_fn: impl FnOnce() -> Fut + Send,
@@ -138,10 +183,126 @@ pub trait Cacheable: Serialize + DeserializeOwned + Send + Sync {
         E: 'static + std::error::Error + Send + Sync,
         Fut: Future<Output = Result<Self, E>> + Send,
     {
-        get_cached_or(redis, key, expiry_time, get_fn).await
+        get_cached_or(backend, key, expiry_time, get_fn).await
+    }
+
+    /// Tries to get a value from the cache according to a [`CachePolicy`].
+    /// Fresh values are returned directly. Stale values are returned as well, but refreshed in
+    /// the background using `get_fn`. If there is no value, calls `get_fn` to get one, caches it
+    /// and returns it.
+    ///
+    /// `get_fn` returns `None` if the value doesn't exist, which is cached as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend the cache is stored in
+    /// * `key` - The value's key.
+    /// * `policy` - How long the value and its absence are cached.
+    /// * `get_fn` - A function that gets an instance of the value but might return an error.
+    ///
+    /// # Errors
+    ///
+    /// An error can occur during (de-)seriaization, if storing it fails or if the `get_fn`
+    /// fails while there is no stale value.
+    ///
+    async fn get_cached_or_revalidate<E, Fut>(
+        backend: &Cache,
+        key: &Self::KeyType,
+        policy: CachePolicy,
+        get_fn: impl FnOnce() -> Fut + Send + 'static,
+    ) -> Result<Option<Self>, CacheError>
+    where
+        E: 'static + std::error::Error + Send + Sync,
+        Fut: Future<Output = Result<Option<Self>, E>> + Send + 'static,
+    {
+        get_cached_or_revalidate(backend, key, policy, get_fn).await
     }
 }
 
+/// How long values are cached when they may be served stale while they are refreshed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// The number of seconds after which a value is stale and gets refreshed
+    pub soft_ttl: u64,
+    /// The number of seconds after which a value is removed from the cache.
+    /// Until then, the stale value is served if refreshing it fails.
+    pub hard_ttl: u64,
+    /// The number of seconds for which it is remembered that a value doesn't exist
+    pub not_found_ttl: u64,
+}
+
+/// A cached value along with the time it becomes stale
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry<V> {
+    /// The unix timestamp in seconds at which the value becomes stale
+    stale_at: u64,
+    /// The value or `None` if it is known not to exist
+    pub value: Option<V>,
+}
+
+impl<V> CacheEntry<V> {
+    /// Creates an entry for a value that is stored without a [`CachePolicy`] and never stale
+    fn permanent(value: V) -> Self {
+        Self {
+            stale_at: u64::MAX,
+            value: Some(value),
+        }
+    }
+
+    /// Whether the value should be refreshed
+    pub fn is_stale(&self) -> bool {
+        unix_time() >= self.stale_at
+    }
+}
+
+/// Stores values in the backend and tells other instances to drop them from their local caches,
+/// if the type has a local cache
+async fn write<V: Cacheable>(backend: &Cache, writes: Vec<CacheWrite>) -> CacheResult<()> {
+    let keys = V::local_cache().is_some().then(|| {
+        writes
+            .iter()
+            .map(|write| write.key.clone())
+            .collect::<Vec<_>>()
+    });
+    backend.set_many(writes).await?;
+    if let Some(keys) = keys {
+        backend.invalidate_elsewhere(&keys).await?;
+    }
+    Ok(())
+}
+
+/// Stores entries in the type's local cache, if it has one
+fn cache_locally<V: Cacheable>(entries: impl IntoIterator<Item = (String, CacheEntry<V>)>) {
+    if let Some(local) = V::local_cache() {
+        for (full_key, entry) in entries {
+            local.insert(full_key, entry);
+        }
+    }
+}
+
+/// Looks up entries in the type's local cache.
+/// Returns the entries for each key and the indices of the keys that weren't found.
+fn get_locally<V: Cacheable>(full_keys: &[String]) -> (Vec<Option<CacheEntry<V>>>, Vec<usize>) {
+    let entries = full_keys
+        .iter()
+        .map(|full_key| V::local_cache().and_then(|local| local.get(full_key)))
+        .collect::<Vec<_>>();
+    let missing = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.is_none())
+        .map(|(i, _)| i)
+        .collect();
+    (entries, missing)
+}
+
+/// Returns the current unix timestamp in seconds
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
 pub type CacheResult<T> = Result<T, CacheError>;
 
 /// An error that can occur during caching
@@ -157,107 +318,165 @@ pub enum CacheError {
     Request(miette::Error),
 }
 
-/// Stores a value in redis.
+/// Stores a value in the cache.
 ///
 /// # Arguments
 ///
-/// * `redis` - A connection to the redis instance
+/// * `backend` - The backend the cache is stored in
 /// * `v` - The value to store.
 /// * `expiry_time` - An optional number of seconds until the entry expires.
 ///
 /// # Errors
 ///
-/// An error can occur when serialization fails, or the set command in the redis store fails.
+/// An error can occur when serialization fails, or storing it fails.
 ///
 pub async fn cache<V: Cacheable>(
-    redis: &deadpool_redis::Pool,
+    backend: &Cache,
     v: &V,
     expiry_time: Option<usize>,
 ) -> Result<(), CacheError> {
-    let serialized = serde_json::to_string(v)?;
-    let mut conn = redis.get().await?;
+    cache_many(backend, std::slice::from_ref(v), expiry_time).await
+}
 
-    if let Some(time) = expiry_time {
-        conn.set_ex::<String, String, String>(v.full_key(), serialized, time as u64)
-    } else {
-        conn.set::<String, String, String>(v.full_key(), serialized)
+/// Stores multiple values in the cache at once.
+///
+/// # Arguments
+///
+/// * `backend` - The backend the cache is stored in
+/// * `values` - The values to store.
+/// * `expiry_time` - An optional number of seconds until the entries expire.
+///
+/// # Errors
+///
+/// An error can occur when serialization fails, or storing them fails.
+///
+pub async fn cache_many<V: Cacheable>(
+    backend: &Cache,
+    values: &[V],
+    expiry_time: Option<usize>,
+) -> Result<(), CacheError> {
+    if values.is_empty() {
+        return Ok(());
     }
-    .await?;
 
+    let writes = values
+        .iter()
+        .map(|v| {
+            Ok(CacheWrite {
+                key: v.full_key(),
+                value: serde_json::to_string(v)?,
+                expiry: expiry_time.map(|time| time as u64),
+            })
+        })
+        .collect::<CacheResult<Vec<_>>>()?;
+    write::<V>(backend, writes).await?;
+
+    cache_locally(
+        values
+            .iter()
+            .map(|v| (v.full_key(), CacheEntry::permanent(v.clone()))),
+    );
     Ok(())
 }
 
-/// Removes a value from redis.
+/// Removes a value from the cache.
 ///
 /// # Arguments
 ///
-/// * `redis` - A connection to the redis instance
+/// * `backend` - The backend the cache is stored in
 /// * `key` - The key to remove.
 ///
 /// # Errors
 ///
-/// An error can occur when deserialization fails, or the del command in the redis store fails.
+/// An error can occur when deserialization fails, or removing it fails.
 ///
-pub async fn uncache<V: Cacheable>(
-    redis: &deadpool_redis::Pool,
-    key: &V::KeyType,
-) -> CacheResult<Option<V>> {
-    let mut conn = redis.get().await?;
+pub async fn uncache<V: Cacheable>(backend: &Cache, key: &V::KeyType) -> CacheResult<Option<V>> {
+    let full_key = V::full_key_with(key);
+    if let Some(local) = V::local_cache() {
+        local.remove(&full_key);
+    }
 
-    let Some(s) = conn
-        .get_del::<_, deadpool_redis::redis::Value>(V::full_key_with(key))
-        .await
-        .and_then(|v| match v {
-            // If it doesn't exist, we just return "None"
-            deadpool_redis::redis::Value::Nil => Ok(None),
-            // Otherwise we try to convert it to a string to parse later
-            v => String::from_redis_value(&v).map(Some),
-        })?
-    else {
-        return Ok(None);
-    };
+    let value = backend.get_del(&full_key).await?;
+    if V::local_cache().is_some() {
+        backend.invalidate_elsewhere(&[full_key]).await?;
+    }
 
     // Try to parse it to the output value
-    serde_json::from_str::<V>(&s)
-        .map(Some)
+    value
+        .map(|s| serde_json::from_str::<V>(&s))
+        .transpose()
         .map_err(CacheError::from)
 }
 
-/// Gets a value from redis. Or `Ok(None)` if it doesn't exist
+/// Gets a value from the cache. Or `Ok(None)` if it doesn't exist
 ///
 /// # Arguments
 ///
-/// * `redis` - A connection to the redis instance
+/// * `backend` - The backend the cache is stored in
 /// * `v` - The value's key.
 ///
 /// # Errors
 ///
-/// An error can occur when deserialization fails, or the get command in the redis store fails.
+/// An error can occur when deserialization fails, or getting it fails.
 ///
 pub async fn get_cached<V: Cacheable>(
-    redis: &deadpool_redis::Pool,
+    backend: &Cache,
     key: &V::KeyType,
 ) -> Result<Option<V>, CacheError> {
-    let mut conn = redis.get().await?;
-    // Try to find the value in the cache
-    let Some(s) = conn
-        .get::<String, deadpool_redis::redis::Value>(V::full_key_with(key))
-        .await
-        .and_then(|v| match v {
-            // If it doesn't exist, we just return "None"
-            deadpool_redis::redis::Value::Nil => Ok(None),
-            // Otherwise we try to convert it to a string to parse later
-            v => String::from_redis_value(&v).map(Some),
-        })?
-    else {
-        // If it's not found, just return
-        return Ok(None);
-    };
+    let mut values = get_cached_many(backend, &[key]).await?;
+    Ok(values.pop().flatten())
+}
 
-    // Try to parse it to the output value
-    serde_json::from_str::<V>(&s)
-        .map(Some)
-        .map_err(CacheError::from)
+/// Gets multiple values from the cache at once. Values that don't exist are `None`.
+///
+/// # Arguments
+///
+/// * `backend` - The backend the cache is stored in
+/// * `keys` - The values' keys.
+///
+/// # Errors
+///
+/// An error can occur when deserialization fails, or getting them fails.
+///
+pub async fn get_cached_many<V: Cacheable>(
+    backend: &Cache,
+    keys: &[&V::KeyType],
+) -> Result<Vec<Option<V>>, CacheError> {
+    let full_keys = keys
+        .iter()
+        .map(|key| V::full_key_with(key))
+        .collect::<Vec<_>>();
+    let (mut entries, missing) = get_locally::<V>(&full_keys);
+
+    // Look for the values that aren't cached locally in the backend
+    let values = get_raw(backend, &full_keys, &missing).await?;
+    let mut found = Vec::with_capacity(missing.len());
+    for (i, value) in missing.into_iter().zip(values) {
+        if let Some(s) = value {
+            let entry = CacheEntry::permanent(serde_json::from_str::<V>(&s)?);
+            found.push((full_keys[i].clone(), entry.clone()));
+            entries[i] = Some(entry);
+        }
+    }
+    cache_locally(found);
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| entry.and_then(|entry| entry.value))
+        .collect())
+}
+
+/// Gets the raw values of the keys at the given indices from the backend at once
+async fn get_raw(
+    backend: &Cache,
+    full_keys: &[String],
+    indices: &[usize],
+) -> CacheResult<Vec<Option<String>>> {
+    let keys = indices
+        .iter()
+        .map(|&i| full_keys[i].clone())
+        .collect::<Vec<_>>();
+    backend.get_many(&keys).await
 }
 
 /// Tries to get a value from the cache and returns it, if it exist.
@@ -266,17 +485,17 @@ pub async fn get_cached<V: Cacheable>(
 ///
 /// # Arguments
 ///
-/// * `redis` - A connection to the redis instance
+/// * `backend` - The backend the cache is stored in
 /// * `key` - The value's key.
 /// * `expiry_time` - An optional number of seconds until the entry expires.
 /// * `get_fn` - A function that gets an instance of the value.
 ///
 /// # Errors
 ///
-/// An error can occur during (de-)seriaization or if the redis set command fails.
+/// An error can occur during (de-)seriaization or if storing it fails.
 ///
 pub async fn get_cached_or_infallible<V, Fut>(
-    redis: &deadpool_redis::Pool,
+    backend: &Cache,
     key: &V::KeyType,
     expiry_time: Option<usize>,
     get_fn: impl FnOnce() -> Fut,
@@ -285,7 +504,7 @@ where
     V: Cacheable,
     Fut: Future<Output = V>,
 {
-    get_cached_or::<V, Infallible, _>(redis, key, expiry_time, || async { Ok(get_fn().await) })
+    get_cached_or::<V, Infallible, _>(backend, key, expiry_time, || async { Ok(get_fn().await) })
         .await
 }
 
@@ -295,18 +514,18 @@ where
 ///
 /// # Arguments
 ///
-/// * `redis` - A connection to the redis instance
+/// * `backend` - The backend the cache is stored in
 /// * `key` - The value's key.
 /// * `expiry_time` - An optional number of seconds until the entry expires.
 /// * `get_fn` - A function that gets an instance of the value but might return an error.
 ///
 /// # Errors
 ///
-/// An error can occur during (de-)seriaization, if the redis set command fails or if the `get_fn`
+/// An error can occur during (de-)seriaization, if storing it fails or if the `get_fn`
 /// fails.
 ///
 pub async fn get_cached_or<V, E, Fut>(
-    redis: &deadpool_redis::Pool,
+    backend: &Cache,
     key: &V::KeyType,
     expiry_time: Option<usize>,
     get_fn: impl FnOnce() -> Fut,
@@ -317,7 +536,7 @@ where
     Fut: Future<Output = Result<V, E>>,
 {
     // Try to find the value in the cache
-    if let Ok(Some(v)) = get_cached::<V>(redis, key).await {
+    if let Ok(Some(v)) = get_cached::<V>(backend, key).await {
         // If found, just return it
         return Ok(v);
     }
@@ -330,6 +549,322 @@ where
         .map_err(CacheError::Request)?;
 
     // Cache the value and return it
-    cache(redis, &v, expiry_time).await?;
+    cache(backend, &v, expiry_time).await?;
     Ok(v)
 }
+
+/// Stores values and the absence of values in the cache as [`CacheEntry`]s.
+///
+/// # Arguments
+///
+/// * `backend` - The backend the cache is stored in
+/// * `found` - The values to store.
+/// * `not_found` - The keys of values that are known not to exist.
+/// * `policy` - How long the values and their absence are cached.
+///
+/// # Errors
+///
+/// An error can occur when serialization fails, or storing them fails.
+///
+pub async fn cache_entries<V: Cacheable>(
+    backend: &Cache,
+    found: &[V],
+    not_found: &[&V::KeyType],
+    policy: CachePolicy,
+) -> CacheResult<()> {
+    if found.is_empty() && not_found.is_empty() {
+        return Ok(());
+    }
+
+    let now = unix_time();
+    let mut writes = Vec::with_capacity(found.len() + not_found.len());
+    let mut entries = Vec::with_capacity(found.len() + not_found.len());
+    for v in found {
+        let entry = CacheEntry {
+            stale_at: now + policy.soft_ttl,
+            value: Some(v.clone()),
+        };
+        writes.push(CacheWrite {
+            key: v.full_key(),
+            value: serde_json::to_string(&entry)?,
+            expiry: Some(policy.hard_ttl),
+        });
+        entries.push((v.full_key(), entry));
+    }
+    for key in not_found {
+        let entry = CacheEntry {
+            stale_at: now + policy.not_found_ttl,
+            value: None,
+        };
+        writes.push(CacheWrite {
+            key: V::full_key_with(key),
+            value: serde_json::to_string(&entry)?,
+            expiry: Some(policy.not_found_ttl),
+        });
+        entries.push((V::full_key_with(key), entry));
+    }
+    write::<V>(backend, writes).await?;
+
+    cache_locally(entries);
+    Ok(())
+}
+
+/// Gets multiple [`CacheEntry`]s from the cache at once.
+/// Entries that don't exist or can't be deserialized (e.g. because they were stored in an older
+/// format) are `None`.
+///
+/// # Arguments
+///
+/// * `backend` - The backend the cache is stored in
+/// * `keys` - The values' keys.
+///
+/// # Errors
+///
+/// An error can occur when getting them fails.
+///
+pub async fn get_entries<V: Cacheable>(
+    backend: &Cache,
+    keys: &[&V::KeyType],
+) -> CacheResult<Vec<Option<CacheEntry<V>>>> {
+    let full_keys = keys
+        .iter()
+        .map(|key| V::full_key_with(key))
+        .collect::<Vec<_>>();
+    let (mut entries, missing) = get_locally::<V>(&full_keys);
+
+    // Look for the entries that aren't cached locally in the backend
+    let values = get_raw(backend, &full_keys, &missing).await?;
+    let mut found = Vec::with_capacity(missing.len());
+    for (i, value) in missing.into_iter().zip(values) {
+        if let Some(entry) = value.and_then(|s| serde_json::from_str::<CacheEntry<V>>(&s).ok()) {
+            found.push((full_keys[i].clone(), entry.clone()));
+            entries[i] = Some(entry);
+        }
+    }
+    cache_locally(found);
+
+    Ok(entries)
+}
+
+/// Runs a refresh of cache entries in the background and logs it if it fails.
+/// While a refresh with the same name has been started in the last [`REFRESH_LOCK_TIME`]
+/// seconds, no new one is started, so that stale entries that are requested often don't cause a
+/// refresh on every request.
+///
+/// # Arguments
+///
+/// * `backend` - The backend the cache is stored in
+/// * `name` - A name identifying the refreshed entries, e.g. their full key.
+/// * `refresh` - The future refreshing the entries.
+///
+pub fn spawn_refresh<Fut>(backend: &Cache, name: String, refresh: Fut)
+where
+    Fut: Future<Output = CacheResult<()>> + Send + 'static,
+{
+    let backend = backend.clone();
+    tokio::spawn(async move {
+        match backend
+            .set_if_absent(&format!("refresh:{name}"), "1", REFRESH_LOCK_TIME)
+            .await
+        {
+            Ok(true) => {}
+            // Someone else is refreshing the entries already
+            Ok(false) => return,
+            Err(error) => {
+                warn!(%error, name, "could not lock cache entries for refreshing");
+                return;
+            }
+        }
+
+        if let Err(error) = refresh.await {
+            warn!(%error, name, "could not refresh stale cache entries");
+        }
+    });
+}
+
+/// Tries to get a value from the cache according to a [`CachePolicy`].
+/// Fresh values are returned directly. Stale values are returned as well, but refreshed in the
+/// background using `get_fn`. If there is no value, calls `get_fn` to get one, caches it and
+/// returns it.
+///
+/// `get_fn` returns `None` if the value doesn't exist, which is cached as well.
+///
+/// # Arguments
+///
+/// * `backend` - The backend the cache is stored in
+/// * `key` - The value's key.
+/// * `policy` - How long the value and its absence are cached.
+/// * `get_fn` - A function that gets an instance of the value but might return an error.
+///
+/// # Errors
+///
+/// An error can occur during (de-)seriaization, if storing it fails or if the `get_fn`
+/// fails while there is no stale value.
+///
+pub async fn get_cached_or_revalidate<V, E, Fut>(
+    backend: &Cache,
+    key: &V::KeyType,
+    policy: CachePolicy,
+    get_fn: impl FnOnce() -> Fut + Send + 'static,
+) -> CacheResult<Option<V>>
+where
+    V: Cacheable,
+    E: 'static + std::error::Error + Send + Sync,
+    Fut: Future<Output = Result<Option<V>, E>> + Send + 'static,
+{
+    let full_key = V::full_key_with(key);
+    // If the cache fails, act as if there is no entry
+    let entry = get_entries::<V>(backend, &[key])
+        .await
+        .ok()
+        .and_then(|entries| entries.into_iter().next().flatten());
+
+    match entry {
+        Some(entry) if !entry.is_stale() => Ok(entry.value),
+        // Serve the stale value and refresh it in the background
+        Some(entry) => {
+            let refresh_backend = backend.clone();
+            let refresh = async move {
+                let value = get_fn()
+                    .await
+                    .into_diagnostic()
+                    .wrap_err("error requesting value")
+                    .map_err(CacheError::Request)?;
+                store_entry(&refresh_backend, full_key, value.as_ref(), policy).await
+            };
+            spawn_refresh(backend, V::full_key_with(key), refresh);
+            Ok(entry.value)
+        }
+        None => {
+            let value = get_fn()
+                .await
+                .into_diagnostic()
+                .wrap_err("error requesting value")
+                .map_err(CacheError::Request)?;
+            store_entry(backend, full_key, value.as_ref(), policy).await?;
+            Ok(value)
+        }
+    }
+}
+
+/// Stores a value or its absence under the given full key
+async fn store_entry<V: Cacheable>(
+    backend: &Cache,
+    full_key: String,
+    value: Option<&V>,
+    policy: CachePolicy,
+) -> CacheResult<()> {
+    let (stale_after, expiry) = if value.is_some() {
+        (policy.soft_ttl, policy.hard_ttl)
+    } else {
+        (policy.not_found_ttl, policy.not_found_ttl)
+    };
+    let entry = CacheEntry {
+        stale_at: unix_time() + stale_after,
+        value: value.cloned(),
+    };
+
+    let writes = vec![CacheWrite {
+        key: full_key.clone(),
+        value: serde_json::to_string(&entry)?,
+        expiry: Some(expiry),
+    }];
+    write::<V>(backend, writes).await?;
+
+    cache_locally([(full_key, entry)]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{cache_entries, get_entries, Cache, CachePolicy, Cacheable, MemoryBackend};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Token {
+        id: u32,
+        secret: String,
+    }
+
+    impl Cacheable for Token {
+        type KeyType = u32;
+
+        fn type_key() -> &'static str {
+            "token"
+        }
+
+        fn key(&self) -> &Self::KeyType {
+            &self.id
+        }
+    }
+
+    fn token(id: u32) -> Token {
+        Token {
+            id,
+            secret: format!("secret {id}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_and_removes_values_in_memory() {
+        let backend: Cache = Arc::new(MemoryBackend::new());
+
+        token(1).cache(&backend, None).await.unwrap();
+        token(2).cache(&backend, None).await.unwrap();
+        assert_eq!(
+            Token::get_cached(&1, &backend).await.unwrap(),
+            Some(token(1))
+        );
+        assert_eq!(
+            Token::get_cached_many(&backend, &[&2, &3]).await.unwrap(),
+            vec![Some(token(2)), None]
+        );
+
+        // Removing a value returns it exactly once
+        assert_eq!(Token::uncache(&backend, &1).await.unwrap(), Some(token(1)));
+        assert_eq!(Token::uncache(&backend, &1).await.unwrap(), None);
+        assert_eq!(Token::get_cached(&1, &backend).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn expires_values_in_memory() {
+        let backend: Cache = Arc::new(MemoryBackend::new());
+
+        token(1).cache(&backend, Some(1)).await.unwrap();
+        token(2).cache(&backend, None).await.unwrap();
+        assert!(Token::get_cached(&1, &backend).await.unwrap().is_some());
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(Token::get_cached(&1, &backend).await.unwrap(), None);
+        assert_eq!(Token::uncache(&backend, &1).await.unwrap(), None);
+        assert_eq!(
+            Token::get_cached(&2, &backend).await.unwrap(),
+            Some(token(2))
+        );
+    }
+
+    #[tokio::test]
+    async fn caches_missing_values() {
+        let backend: Cache = Arc::new(MemoryBackend::new());
+        let policy = CachePolicy {
+            soft_ttl: 60,
+            hard_ttl: 120,
+            not_found_ttl: 60,
+        };
+
+        cache_entries(&backend, &[token(1)], &[&2], policy)
+            .await
+            .unwrap();
+        let entries = get_entries::<Token>(&backend, &[&1, &2, &3]).await.unwrap();
+
+        let [Some(found), Some(missing), None] = entries.as_slice() else {
+            panic!("unexpected entries {entries:?}");
+        };
+        assert_eq!(found.value, Some(token(1)));
+        assert_eq!(missing.value, None);
+        assert!(!found.is_stale() && !missing.is_stale());
+    }
+}
@@ -0,0 +1,316 @@
+//! The stores cached values are kept in.
+//!
+//! [`RedisBackend`] shares the cache between all instances of the backend, while
+//! [`MemoryBackend`] keeps it inside the process, which is enough for a single instance and for
+//! tests.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use deadpool_redis::redis::AsyncCommands;
+
+use super::{local, CacheResult};
+
+/// A shared handle to the cache backend
+pub type Cache = Arc<dyn CacheBackend>;
+
+/// A value to store in a [`CacheBackend`]
+#[derive(Debug, Clone)]
+pub struct CacheWrite {
+    pub key: String,
+    pub value: String,
+    /// The number of seconds until the value expires or `None` if it doesn't expire
+    pub expiry: Option<u64>,
+}
+
+/// A key-value store with expiring string values and hashes, modeled after the redis commands
+/// the cache needs
+#[async_trait::async_trait]
+pub trait CacheBackend: Send + Sync {
+    /// Gets the values of multiple keys. Values that don't exist are `None`.
+    async fn get_many(&self, keys: &[String]) -> CacheResult<Vec<Option<String>>>;
+
+    /// Stores multiple values at once
+    async fn set_many(&self, writes: Vec<CacheWrite>) -> CacheResult<()>;
+
+    /// Stores a value if its key doesn't exist yet. Returns whether the value was stored.
+    async fn set_if_absent(&self, key: &str, value: &str, expiry: u64) -> CacheResult<bool>;
+
+    /// Removes a value and returns it
+    async fn get_del(&self, key: &str) -> CacheResult<Option<String>>;
+
+    /// Sets a field of a hash, creating the hash if it doesn't exist
+    async fn hash_set(&self, key: &str, field: &str, value: &str) -> CacheResult<()>;
+
+    /// Gets all fields of a hash. A hash that doesn't exist has no fields.
+    async fn hash_get_all(&self, key: &str) -> CacheResult<HashMap<String, String>>;
+
+    /// Removes fields from a hash
+    async fn hash_delete(&self, key: &str, fields: &[String]) -> CacheResult<()>;
+
+    /// Tells other instances sharing this backend to drop the keys from their local caches.
+    /// See the [`local`] module.
+    async fn invalidate_elsewhere(&self, _keys: &[String]) -> CacheResult<()> {
+        Ok(())
+    }
+}
+
+/// A cache backend storing values in redis
+pub struct RedisBackend {
+    pool: deadpool_redis::Pool,
+}
+
+impl RedisBackend {
+    pub fn new(pool: deadpool_redis::Pool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl CacheBackend for RedisBackend {
+    async fn get_many(&self, keys: &[String]) -> CacheResult<Vec<Option<String>>> {
+        // MGET needs at least one key
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut conn = self.pool.get().await?;
+        let values = deadpool_redis::redis::cmd("MGET")
+            .arg(keys)
+            .query_async::<Vec<Option<String>>>(&mut conn)
+            .await?;
+        Ok(values)
+    }
+
+    async fn set_many(&self, writes: Vec<CacheWrite>) -> CacheResult<()> {
+        if writes.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = deadpool_redis::redis::pipe();
+        for write in writes {
+            match write.expiry {
+                Some(expiry) => pipe.set_ex(write.key, write.value, expiry),
+                None => pipe.set(write.key, write.value),
+            }
+            .ignore();
+        }
+
+        let mut conn = self.pool.get().await?;
+        pipe.query_async::<()>(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn set_if_absent(&self, key: &str, value: &str, expiry: u64) -> CacheResult<bool> {
+        let mut conn = self.pool.get().await?;
+        let stored = deadpool_redis::redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("NX")
+            .arg("EX")
+            .arg(expiry)
+            .query_async::<Option<String>>(&mut conn)
+            .await?;
+        Ok(stored.is_some())
+    }
+
+    async fn get_del(&self, key: &str) -> CacheResult<Option<String>> {
+        let mut conn = self.pool.get().await?;
+        Ok(conn.get_del::<_, Option<String>>(key).await?)
+    }
+
+    async fn hash_set(&self, key: &str, field: &str, value: &str) -> CacheResult<()> {
+        let mut conn = self.pool.get().await?;
+        conn.hset::<_, _, _, ()>(key, field, value).await?;
+        Ok(())
+    }
+
+    async fn hash_get_all(&self, key: &str) -> CacheResult<HashMap<String, String>> {
+        let mut conn = self.pool.get().await?;
+        Ok(conn.hgetall::<_, HashMap<String, String>>(key).await?)
+    }
+
+    async fn hash_delete(&self, key: &str, fields: &[String]) -> CacheResult<()> {
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        let mut conn = self.pool.get().await?;
+        conn.hdel::<_, _, ()>(key, fields).await?;
+        Ok(())
+    }
+
+    async fn invalidate_elsewhere(&self, keys: &[String]) -> CacheResult<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = deadpool_redis::redis::pipe();
+        for key in keys {
+            local::publish_invalidation(&mut pipe, key);
+        }
+
+        let mut conn = self.pool.get().await?;
+        pipe.query_async::<()>(&mut conn).await?;
+        Ok(())
+    }
+}
+
+/// How often expired values are removed from a [`MemoryBackend`]. Until then, they are only
+/// ignored.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A cache backend storing values in the memory of this process.
+/// Values are lost when the process exits and aren't shared with other instances.
+pub struct MemoryBackend {
+    store: Mutex<MemoryStore>,
+}
+
+struct MemoryStore {
+    values: HashMap<String, MemoryValue>,
+    /// When expired values were last removed
+    purged_at: Instant,
+}
+
+enum MemoryValue {
+    String {
+        value: String,
+        expires_at: Option<Instant>,
+    },
+    Hash(HashMap<String, String>),
+}
+
+impl MemoryValue {
+    fn is_expired(&self, now: Instant) -> bool {
+        match self {
+            Self::String {
+                expires_at: Some(expires_at),
+                ..
+            } => *expires_at <= now,
+            _ => false,
+        }
+    }
+}
+
+impl MemoryStore {
+    /// Gets a value unless it has expired
+    fn get(&self, key: &str, now: Instant) -> Option<&MemoryValue> {
+        self.values.get(key).filter(|value| !value.is_expired(now))
+    }
+
+    fn set(&mut self, key: String, value: String, expiry: Option<u64>, now: Instant) {
+        let expires_at = expiry.map(|expiry| now + Duration::from_secs(expiry));
+        self.values
+            .insert(key, MemoryValue::String { value, expires_at });
+    }
+
+    /// Removes expired values if they haven't been removed for a while
+    fn purge(&mut self, now: Instant) {
+        if now.duration_since(self.purged_at) >= PURGE_INTERVAL {
+            self.values.retain(|_, value| !value.is_expired(now));
+            self.purged_at = now;
+        }
+    }
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self {
+            store: Mutex::new(MemoryStore {
+                values: HashMap::new(),
+                purged_at: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl CacheBackend for MemoryBackend {
+    async fn get_many(&self, keys: &[String]) -> CacheResult<Vec<Option<String>>> {
+        let store = self.store.lock().unwrap();
+        let now = Instant::now();
+        Ok(keys
+            .iter()
+            .map(|key| match store.get(key, now) {
+                Some(MemoryValue::String { value, .. }) => Some(value.clone()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn set_many(&self, writes: Vec<CacheWrite>) -> CacheResult<()> {
+        let mut store = self.store.lock().unwrap();
+        let now = Instant::now();
+        store.purge(now);
+        for write in writes {
+            store.set(write.key, write.value, write.expiry, now);
+        }
+        Ok(())
+    }
+
+    async fn set_if_absent(&self, key: &str, value: &str, expiry: u64) -> CacheResult<bool> {
+        let mut store = self.store.lock().unwrap();
+        let now = Instant::now();
+        if store.get(key, now).is_some() {
+            return Ok(false);
+        }
+        store.set(key.to_owned(), value.to_owned(), Some(expiry), now);
+        Ok(true)
+    }
+
+    async fn get_del(&self, key: &str) -> CacheResult<Option<String>> {
+        let mut store = self.store.lock().unwrap();
+        let now = Instant::now();
+        if !matches!(store.get(key, now), Some(MemoryValue::String { .. })) {
+            return Ok(None);
+        }
+        match store.values.remove(key) {
+            Some(MemoryValue::String { value, .. }) => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn hash_set(&self, key: &str, field: &str, value: &str) -> CacheResult<()> {
+        let mut store = self.store.lock().unwrap();
+        let now = Instant::now();
+        if !matches!(store.get(key, now), Some(MemoryValue::Hash(_))) {
+            store
+                .values
+                .insert(key.to_owned(), MemoryValue::Hash(HashMap::new()));
+        }
+        if let Some(MemoryValue::Hash(hash)) = store.values.get_mut(key) {
+            hash.insert(field.to_owned(), value.to_owned());
+        }
+        Ok(())
+    }
+
+    async fn hash_get_all(&self, key: &str) -> CacheResult<HashMap<String, String>> {
+        let store = self.store.lock().unwrap();
+        match store.get(key, Instant::now()) {
+            Some(MemoryValue::Hash(hash)) => Ok(hash.clone()),
+            _ => Ok(HashMap::new()),
+        }
+    }
+
+    async fn hash_delete(&self, key: &str, fields: &[String]) -> CacheResult<()> {
+        let mut store = self.store.lock().unwrap();
+        if let Some(MemoryValue::Hash(hash)) = store.values.get_mut(key) {
+            for field in fields {
+                hash.remove(field);
+            }
+            if hash.is_empty() {
+                store.values.remove(key);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+//! An optional in-process cache in front of redis.
+//!
+//! Types opt into it by returning a [`LocalCache`] from [`Cacheable::local_cache`]. Whenever an
+//! instance writes or removes a value in redis, it publishes the value's key, so that all other
+//! instances drop their local copy. Local caches are only used while the invalidations are being
+//! received, since values could be outdated otherwise.
+//!
+//! [`Cacheable::local_cache`]: super::Cacheable::local_cache
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex,
+    },
+    time::Duration,
+};
+
+use deadpool_redis::redis::Pipeline;
+use futures::StreamExt;
+use tracing::{info, warn};
+
+use super::{CacheEntry, CacheResult};
+
+/// The redis channel on which the keys of changed values are published
+const INVALIDATION_CHANNEL: &str = "cache-invalidation";
+
+/// The number of seconds to wait before reconnecting after the invalidations can't be received
+const RECONNECT_DELAY: u64 = 5;
+
+/// Identifies this instance, so that it doesn't drop values it just stored itself
+static INSTANCE_ID: LazyLock<u64> = LazyLock::new(rand::random);
+
+/// Whether the invalidations by other instances are currently being received
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// The local caches of all types, so that they can be invalidated
+static LOCAL_CACHES: Mutex<Vec<Box<dyn Invalidate>>> = Mutex::new(Vec::new());
+
+/// A cache whose entries can be removed by their full key
+trait Invalidate: Send + Sync {
+    fn invalidate(&self, full_key: &str);
+
+    fn invalidate_all(&self);
+}
+
+impl<V: Clone + Send + Sync + 'static> Invalidate for moka::sync::Cache<String, CacheEntry<V>> {
+    fn invalidate(&self, full_key: &str) {
+        moka::sync::Cache::invalidate(self, full_key);
+    }
+
+    fn invalidate_all(&self) {
+        moka::sync::Cache::invalidate_all(self);
+    }
+}
+
+/// A bounded in-process cache for the values of one type, indexed by their full key
+pub struct LocalCache<V> {
+    entries: moka::sync::Cache<String, CacheEntry<V>>,
+}
+
+impl<V: Clone + Send + Sync + 'static> LocalCache<V> {
+    /// Creates a local cache holding at most `max_entries` values, each for at most `ttl`.
+    /// The ttl should be shorter than the time values are stored in redis, since it also limits
+    /// how long a value may be outdated if an invalidation arrives late.
+    pub fn new(max_entries: u64, ttl: Duration) -> Self {
+        let entries = moka::sync::Cache::builder()
+            .max_capacity(max_entries)
+            .time_to_live(ttl)
+            .build();
+        LOCAL_CACHES.lock().unwrap().push(Box::new(entries.clone()));
+        Self { entries }
+    }
+
+    pub(super) fn get(&self, full_key: &str) -> Option<CacheEntry<V>> {
+        if !LISTENING.load(Ordering::SeqCst) {
+            return None;
+        }
+        self.entries.get(full_key)
+    }
+
+    pub(super) fn insert(&self, full_key: String, entry: CacheEntry<V>) {
+        if LISTENING.load(Ordering::SeqCst) {
+            self.entries.insert(full_key, entry);
+        }
+    }
+
+    pub(super) fn remove(&self, full_key: &str) {
+        self.entries.invalidate(full_key);
+    }
+}
+
+/// Adds a command to the pipeline which tells other instances to drop the key from their local
+/// caches
+pub(super) fn publish_invalidation(pipe: &mut Pipeline, full_key: &str) {
+    pipe.publish(INVALIDATION_CHANNEL, format!("{}:{full_key}", *INSTANCE_ID))
+        .ignore();
+}
+
+/// Receives the invalidations published by other instances and drops the invalidated values from
+/// the local caches. This never returns and reconnects whenever the connection is lost.
+///
+/// # Arguments
+///
+/// * `redis_url` - The url of the redis instance
+///
+pub async fn listen_for_invalidations(redis_url: String) {
+    loop {
+        if let Err(error) = receive_invalidations(&redis_url).await {
+            warn!(%error, "could not receive cache invalidations");
+        }
+        // Invalidations might be missed until the connection is back
+        LISTENING.store(false, Ordering::SeqCst);
+        invalidate_all();
+        tokio::time::sleep(Duration::from_secs(RECONNECT_DELAY)).await;
+    }
+}
+
+/// Receives invalidations until the connection is closed
+async fn receive_invalidations(redis_url: &str) -> CacheResult<()> {
+    let client = deadpool_redis::redis::Client::open(redis_url)?;
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(INVALIDATION_CHANNEL).await?;
+
+    // Values might have changed while the invalidations weren't received
+    invalidate_all();
+    LISTENING.store(true, Ordering::SeqCst);
+    info!("receiving cache invalidations");
+
+    let instance_id = INSTANCE_ID.to_string();
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let payload = message.get_payload::<String>()?;
+        let Some((instance, full_key)) = payload.split_once(':') else {
+            continue;
+        };
+        // This instance's own caches are up to date already
+        if instance == instance_id {
+            continue;
+        }
+        for cache in LOCAL_CACHES.lock().unwrap().iter() {
+            cache.invalidate(full_key);
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes all values from all local caches
+fn invalidate_all() {
+    for cache in LOCAL_CACHES.lock().unwrap().iter() {
+        cache.invalidate_all();
+    }
+}
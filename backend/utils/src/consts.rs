@@ -1,5 +1,8 @@
 pub const OSU_CLIENT_ID: &str = "OSU_CLIENT_ID";
 pub const OSU_CLIENT_SECRET: &str = "OSU_CLIENT_SECRET";
+pub const OSU_FIXTURES_DIR: &str = "OSU_FIXTURES_DIR";
+pub const OSU_REQUESTS_PER_MINUTE: &str = "OSU_REQUESTS_PER_MINUTE";
+pub const OSU_REQUEST_BURST: &str = "OSU_REQUEST_BURST";
 pub const DATABASE_URL: &str = "DATABASE_URL";
 pub const REDIS_URL: &str = "REDIS_URL";
 pub const FRONTEND_METHOD: &str = "FRONTEND_METHOD";
@@ -69,6 +69,7 @@ impl HashedToken {
     }
 }
 
+#[derive(Clone)]
 pub struct EncryptedToken {
     nonce: Nonce<<Aes256Gcm as AeadCore>::NonceSize>,
     token: Vec<u8>,
@@ -165,7 +166,7 @@ pub fn verify_aes_key() -> Result<(), EnvError> {
 
     if dbg!(aes_key.len()) != 32 {
         return Err(EnvError::InvalidAesKeyLength(
-            aes_key_base64.as_bytes().len(),
+            aes_key_base64.len(),
         ));
     }
     Ok(())
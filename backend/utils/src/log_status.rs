@@ -22,7 +22,7 @@ pub trait LogStatus: Sized {
 
 impl<T> LogStatus for Result<T, Status> {
     fn log_status(self, level: Level) -> Self {
-        self.map_err(|status| {
+        self.inspect_err(|status| {
             match level {
                 Level::TRACE => {
                     trace!("{}", status.message());
@@ -40,8 +40,6 @@ impl<T> LogStatus for Result<T, Status> {
                     error!("{}", status.message());
                 },
             }
-
-            status
         })
     }
 }